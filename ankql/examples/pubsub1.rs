@@ -1,4 +1,4 @@
-use ankql::ast::Predicate;
+use ankql::ast::{Literal, Predicate};
 use ankql::parser::parse_selection;
 use ankql::selection::filter::{FilterIterator, FilterResult, Filterable};
 use std::collections::HashMap;
@@ -33,10 +33,10 @@ pub struct TestItem {
 impl Filterable for TestItem {
     fn collection(&self) -> &str { "users" }
 
-    fn value(&self, name: &str) -> Option<String> {
+    fn value(&self, name: &str) -> Option<Literal> {
         match name {
-            "name" => Some(self.name.clone()),
-            "age" => Some(self.age.clone()),
+            "name" => Some(Literal::String(self.name.clone())),
+            "age" => Some(Literal::String(self.age.clone())),
             _ => None,
         }
     }
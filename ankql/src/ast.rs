@@ -5,7 +5,13 @@ pub enum Expr {
     Literal(Literal),
     Identifier(Identifier),
     Predicate(Predicate),
-    InfixExpr { left: Box<Expr>, operator: InfixOperator, right: Box<Expr> },
+    InfixExpr {
+        left: Box<Expr>,
+        operator: InfixOperator,
+        right: Box<Expr>,
+    },
+    /// The right hand side of IN, or the lower and upper bounds of BETWEEN
+    ExprList(Vec<Expr>),
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -38,6 +44,66 @@ impl std::fmt::Display for Predicate {
     }
 }
 
+/// A selection along with the order and window of the results
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Query {
+    pub predicate: Predicate,
+    pub order_by: Vec<OrderByItem>,
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+}
+
+impl From<Predicate> for Query {
+    fn from(predicate: Predicate) -> Self { Self { predicate, order_by: Vec::new(), limit: None, offset: None } }
+}
+
+impl std::fmt::Display for Query {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, "{}", crate::selection::sql::generate_query_sql(self)) }
+}
+
+/// An aggregation over the entities matching a selection, with a row for each distinct combination of the GROUP BY
+/// properties' values, or a single row if there are none
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Aggregation {
+    pub select: Vec<SelectItem>,
+    pub predicate: Predicate,
+    pub group_by: Vec<Identifier>,
+}
+
+impl std::fmt::Display for Aggregation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", crate::selection::sql::generate_aggregation_sql(self))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SelectItem {
+    /// One of the GROUP BY properties
+    Property(Identifier),
+    /// An aggregate function applied to a property, or to whole entities (`*`) when the argument is None
+    Aggregate { function: AggregateFunction, argument: Option<Identifier> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Min,
+    Max,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderByItem {
+    pub identifier: Identifier,
+    pub direction: OrderDirection,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderDirection {
+    Asc,
+    Desc,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ComparisonOperator {
     Equal,              // =
@@ -46,8 +112,13 @@ pub enum ComparisonOperator {
     GreaterThanOrEqual, // >=
     LessThan,           // <
     LessThanOrEqual,    // <=
-    In,                 // IN
-    Between,            // BETWEEN
+    In,                 // IN (a, b, ...)
+    Between,            // BETWEEN a AND b (inclusive)
+    Like,               // LIKE 'pattern', where % matches any sequence of characters and _ matches any one character
+    ILike,              // ILIKE 'pattern', which is LIKE ignoring case
+    StartsWith,         // STARTS WITH 'prefix'
+    Contains,           // CONTAINS 'substring'
+    Matches,            // MATCHES 'search terms', which is a full-text search for all of the terms in any order
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -15,6 +15,40 @@ impl TryFrom<String> for Predicate {
     fn try_from(value: String) -> Result<Self, Self::Error> { parser::parse_selection(&value) }
 }
 
+impl<'a> TryFrom<&'a str> for ast::Query {
+    type Error = ParseError;
+
+    fn try_from(value: &'a str) -> Result<Self, Self::Error> { parser::parse_query(value) }
+}
+impl TryFrom<String> for ast::Query {
+    type Error = ParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> { parser::parse_query(&value) }
+}
+
+/// A query with placeholders and the values to bind to them
+impl<'a, P: AsRef<[ast::Literal]>> TryFrom<(&'a str, P)> for Predicate {
+    type Error = ParseError;
+
+    fn try_from((value, params): (&'a str, P)) -> Result<Self, Self::Error> { parser::parse_selection_with_params(value, params.as_ref()) }
+}
+impl<'a, P: AsRef<[ast::Literal]>> TryFrom<(&'a str, P)> for ast::Query {
+    type Error = ParseError;
+
+    fn try_from((value, params): (&'a str, P)) -> Result<Self, Self::Error> { parser::parse_query_with_params(value, params.as_ref()) }
+}
+
+impl<'a> TryFrom<&'a str> for ast::Aggregation {
+    type Error = ParseError;
+
+    fn try_from(value: &'a str) -> Result<Self, Self::Error> { parser::parse_aggregation(value) }
+}
+impl TryFrom<String> for ast::Aggregation {
+    type Error = ParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> { parser::parse_aggregation(&value) }
+}
+
 impl TryFrom<ast::Expr> for Predicate {
     type Error = ParseError;
 
@@ -7,7 +7,10 @@ pub enum ParseError {
     EmptyExpression,
     UnexpectedRule { expected: &'static str, got: grammar::Rule },
     InvalidPredicate(String),
+    InvalidOperand(String),
     MissingOperand(&'static str),
+    InvalidAggregation(String),
+    InvalidParameters(String),
 }
 
 impl std::fmt::Display for ParseError {
@@ -19,7 +22,10 @@ impl std::fmt::Display for ParseError {
                 write!(f, "Expected {}, got {:?}", expected, got)
             }
             Self::InvalidPredicate(msg) => write!(f, "Invalid predicate: {}", msg),
+            Self::InvalidOperand(msg) => write!(f, "Invalid operand: {}", msg),
             Self::MissingOperand(side) => write!(f, "Missing {} operand", side),
+            Self::InvalidAggregation(msg) => write!(f, "Invalid aggregation: {}", msg),
+            Self::InvalidParameters(msg) => write!(f, "Invalid parameters: {}", msg),
         }
     }
 }
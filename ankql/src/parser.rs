@@ -27,108 +27,343 @@ fn debug_print_pairs(pairs: Pairs<grammar::Rule>) {
 
 /// Parse a selection expression into a predicate AST.
 /// The selection must be a valid boolean expression using AND, OR, and comparison operators.
-pub fn parse_selection(input: &str) -> Result<ast::Predicate, ParseError> {
-    let pairs = grammar::AnkqlParser::parse(grammar::Rule::Selection, input).map_err(|e| ParseError::SyntaxError(format!("{}", e)))?;
+pub fn parse_selection(input: &str) -> Result<ast::Predicate, ParseError> { parse_selection_with_params(input, &[]) }
+
+/// Parse a selection expression with placeholders for values, binding each to one of `params`. Placeholders are either
+/// all `?`, which take the parameters in order, or all numbered like `$1`, which take the parameter at that position.
+/// Values are never spliced into the query text, so they can't change its meaning however they are quoted.
+pub fn parse_selection_with_params(input: &str, params: &[ast::Literal]) -> Result<ast::Predicate, ParseError> {
+    Ok(parse_rule(grammar::Rule::Selection, input, params)?.predicate)
+}
+
+/// Parse a query, which is a selection followed by optional ORDER BY, LIMIT and OFFSET clauses.
+/// The selection may be omitted, in which case everything is selected.
+pub fn parse_query(input: &str) -> Result<ast::Query, ParseError> { parse_query_with_params(input, &[]) }
+
+/// Parse a query with placeholders for values, binding each to one of `params` as [`parse_selection_with_params`] does
+pub fn parse_query_with_params(input: &str, params: &[ast::Literal]) -> Result<ast::Query, ParseError> {
+    parse_rule(grammar::Rule::Query, input, params)
+}
+
+/// Parse a selection or a query, which is a selection with clauses
+fn parse_rule(rule: grammar::Rule, input: &str, params: &[ast::Literal]) -> Result<ast::Query, ParseError> {
+    let pairs = grammar::AnkqlParser::parse(rule, input).map_err(|e| ParseError::SyntaxError(format!("{}", e)))?;
+    let mut params = Params::new(params);
 
     #[cfg(test)]
     debug_print_pairs(pairs.clone());
 
-    // Since Selection is a silent rule (_), we get the Expr directly
-    let expr = pairs.into_iter().next().ok_or(ParseError::EmptyExpression)?;
-    if expr.as_rule() != grammar::Rule::Expr {
-        return Err(ParseError::UnexpectedRule { expected: "Expr", got: expr.as_rule() });
+    // Since Selection and Query are silent rules (_), we get their parts directly
+    let mut query = ast::Query::from(ast::Predicate::True);
+    for pair in pairs {
+        match pair.as_rule() {
+            grammar::Rule::Expr => query.predicate = parse_expr(pair, &mut params)?.try_into()?,
+            grammar::Rule::OrderByClause => query.order_by = pair.into_inner().map(parse_order_by_item).collect::<Result<_, _>>()?,
+            grammar::Rule::LimitClause => query.limit = Some(parse_count(pair)?),
+            grammar::Rule::OffsetClause => query.offset = Some(parse_count(pair)?),
+            grammar::Rule::EOI => {}
+            _ => return Err(ParseError::UnexpectedRule { expected: "Expr or clause", got: pair.as_rule() }),
+        }
+    }
+
+    params.finish()?;
+    Ok(query)
+}
+
+/// The values bound to the placeholders of a query as it is parsed
+struct Params<'a> {
+    values: &'a [ast::Literal],
+    /// How many `?` placeholders have been bound
+    positional: usize,
+    /// The highest `$n` placeholder which has been bound
+    numbered: usize,
+}
+
+impl<'a> Params<'a> {
+    fn new(values: &'a [ast::Literal]) -> Self { Self { values, positional: 0, numbered: 0 } }
+
+    /// The value for a placeholder, which is `?` or `$n`
+    fn bind(&mut self, placeholder: &str) -> Result<ast::Expr, ParseError> {
+        let position = match placeholder.strip_prefix('$') {
+            None if self.numbered > 0 => return Err(ParseError::InvalidParameters("? and $n placeholders can't be mixed".into())),
+            None => {
+                self.positional += 1;
+                self.positional
+            }
+            Some(_) if self.positional > 0 => return Err(ParseError::InvalidParameters("? and $n placeholders can't be mixed".into())),
+            Some(number) => match number.parse::<usize>() {
+                Ok(position) if position > 0 => {
+                    self.numbered = self.numbered.max(position);
+                    position
+                }
+                _ => return Err(ParseError::InvalidParameters(format!("{} is not a valid placeholder", placeholder))),
+            },
+        };
+        match self.values.get(position - 1) {
+            Some(value) => Ok(ast::Expr::Literal(value.clone())),
+            None => Err(ParseError::InvalidParameters(format!("parameter {} is missing, only {} given", position, self.values.len()))),
+        }
     }
 
-    parse_expr(expr)
+    /// Check that every parameter was bound to a placeholder
+    fn finish(&self) -> Result<(), ParseError> {
+        let expected = self.positional.max(self.numbered);
+        if self.values.len() > expected {
+            return Err(ParseError::InvalidParameters(format!("{} parameters given, but only {} used", self.values.len(), expected)));
+        }
+        Ok(())
+    }
 }
 
-/// Parse a boolean expression, which can be a comparison, AND, or OR expression
-fn parse_expr(pair: Pair<grammar::Rule>) -> Result<ast::Predicate, ParseError> {
+/// Parse an aggregation, which is a SELECT list of GROUP BY properties and aggregate functions, followed by an optional
+/// WHERE clause and an optional GROUP BY clause. For example `SELECT artist, COUNT(*) WHERE year > 2000 GROUP BY artist`.
+pub fn parse_aggregation(input: &str) -> Result<ast::Aggregation, ParseError> {
+    let pairs = grammar::AnkqlParser::parse(grammar::Rule::Aggregation, input).map_err(|e| ParseError::SyntaxError(format!("{}", e)))?;
+    let mut params = Params::new(&[]);
+
+    #[cfg(test)]
+    debug_print_pairs(pairs.clone());
+
+    let mut aggregation = ast::Aggregation { select: Vec::new(), predicate: ast::Predicate::True, group_by: Vec::new() };
+    for pair in pairs {
+        match pair.as_rule() {
+            grammar::Rule::SelectItem => aggregation.select.push(parse_select_item(pair)?),
+            grammar::Rule::WhereClause => {
+                let expr = pair.into_inner().next().ok_or(ParseError::MissingOperand("WHERE"))?;
+                aggregation.predicate = parse_expr(expr, &mut params)?.try_into()?;
+            }
+            grammar::Rule::GroupByClause => aggregation.group_by = pair.into_inner().map(parse_property).collect::<Result<_, _>>()?,
+            grammar::Rule::EOI => {}
+            _ => return Err(ParseError::UnexpectedRule { expected: "SELECT item or clause", got: pair.as_rule() }),
+        }
+    }
+
+    // As in SQL, a property can only be selected if there is a single value of it for each group
+    for item in &aggregation.select {
+        if let ast::SelectItem::Property(identifier) = item {
+            if !aggregation.group_by.contains(identifier) {
+                return Err(ParseError::InvalidAggregation(format!("{:?} is selected but not in the GROUP BY clause", identifier)));
+            }
+        }
+    }
+
+    Ok(aggregation)
+}
+
+/// Parse an item of a SELECT list, which is a property or an aggregate function call
+fn parse_select_item(pair: Pair<grammar::Rule>) -> Result<ast::SelectItem, ParseError> {
+    let inner = pair.into_inner().next().ok_or(ParseError::MissingOperand("SELECT"))?;
+    if inner.as_rule() != grammar::Rule::AggregateCall {
+        return Ok(ast::SelectItem::Property(parse_property(inner)?));
+    }
+
+    let mut parts = inner.into_inner();
+    let function = match parts.next().map(|function| function.as_rule()) {
+        Some(grammar::Rule::Count) => ast::AggregateFunction::Count,
+        Some(grammar::Rule::Sum) => ast::AggregateFunction::Sum,
+        Some(grammar::Rule::Min) => ast::AggregateFunction::Min,
+        Some(grammar::Rule::Max) => ast::AggregateFunction::Max,
+        _ => return Err(ParseError::MissingOperand("aggregate function")),
+    };
+    let argument = parts.next().ok_or(ParseError::MissingOperand("aggregate argument"))?;
+    let argument = match argument.as_rule() {
+        grammar::Rule::Star if function == ast::AggregateFunction::Count => None,
+        grammar::Rule::Star => return Err(ParseError::InvalidAggregation(format!("{:?} requires a property", function))),
+        _ => Some(parse_property(argument)?),
+    };
+    Ok(ast::SelectItem::Aggregate { function, argument })
+}
+
+/// Parse an identifier which must name a property
+fn parse_property(pair: Pair<grammar::Rule>) -> Result<ast::Identifier, ParseError> {
+    match parse_identifier(pair)? {
+        ast::Expr::Identifier(identifier) => Ok(identifier),
+        _ => unreachable!("parse_identifier always returns an identifier"),
+    }
+}
+
+/// Parse a property to order by, and its direction, which defaults to ascending
+fn parse_order_by_item(pair: Pair<grammar::Rule>) -> Result<ast::OrderByItem, ParseError> {
+    let mut parts = pair.into_inner();
+    let identifier = parse_property(parts.next().ok_or(ParseError::MissingOperand("ORDER BY"))?)?;
+    let direction = match parts.next().map(|direction| direction.as_rule()) {
+        Some(grammar::Rule::Desc) => ast::OrderDirection::Desc,
+        _ => ast::OrderDirection::Asc,
+    };
+    Ok(ast::OrderByItem { identifier, direction })
+}
+
+/// Parse the count of a LIMIT or OFFSET clause
+fn parse_count(pair: Pair<grammar::Rule>) -> Result<u64, ParseError> {
+    let count = pair.into_inner().next().ok_or(ParseError::MissingOperand("count"))?;
+    count.as_str().parse().map_err(|e| ParseError::InvalidPredicate(format!("Failed to parse count: {}", e)))
+}
+
+/// Parse an expression, which can be a comparison, AND, or OR expression, or an arithmetic expression
+fn parse_expr(pair: Pair<grammar::Rule>, params: &mut Params) -> Result<ast::Expr, ParseError> {
     assert_eq!(pair.as_rule(), grammar::Rule::Expr, "Expected Expr rule");
     let mut pairs = pair.into_inner();
 
     // Parse the first value
     let first = pairs.next().ok_or(ParseError::MissingOperand("first"))?;
-    let mut result = parse_atomic_expr(first)?;
+    let mut result = parse_operand(first, &mut pairs, params)?;
 
-    // Process operator-value pairs
+    // Process operators, each of which consumes its own operands
     while let Some(op) = pairs.next() {
-        let right = pairs.next().ok_or(ParseError::MissingOperand("right"))?;
         result = match op.as_rule() {
-            grammar::Rule::Eq
-            | grammar::Rule::GtEq
-            | grammar::Rule::Gt
-            | grammar::Rule::LtEq
-            | grammar::Rule::Lt
-            | grammar::Rule::NotEq => create_comparison(result, op.as_rule(), right)?,
-            grammar::Rule::And | grammar::Rule::Or => create_logical_op(op.as_rule(), result, right, &mut pairs)?,
+            rule if is_comparison(rule) => create_comparison(result, op, &mut pairs, params)?,
+            grammar::Rule::And | grammar::Rule::Or => create_logical_op(op.as_rule(), result, &mut pairs, params)?,
             _ => {
                 return Err(ParseError::UnexpectedRule { expected: "comparison operator, And, or Or", got: op.as_rule() });
             }
         };
     }
 
-    result.try_into()
+    Ok(result)
+}
+
+fn infix_operator(rule: grammar::Rule) -> Option<ast::InfixOperator> {
+    match rule {
+        grammar::Rule::Add => Some(ast::InfixOperator::Add),
+        grammar::Rule::Subtract => Some(ast::InfixOperator::Subtract),
+        grammar::Rule::Multiply => Some(ast::InfixOperator::Multiply),
+        grammar::Rule::Divide => Some(ast::InfixOperator::Divide),
+        _ => None,
+    }
+}
+
+/// Parse an operand of a comparison, which is an atomic expression optionally followed by arithmetic on further
+/// atomic expressions taken from `rest`. Multiplication and division bind tighter than addition and subtraction.
+fn parse_operand(first: Pair<grammar::Rule>, rest: &mut Pairs<grammar::Rule>, params: &mut Params) -> Result<ast::Expr, ParseError> {
+    let infix = |left, operator, right| ast::Expr::InfixExpr { left: Box::new(left), operator, right: Box::new(right) };
+
+    // Multiplicative terms are combined as they are read, leaving a sum of terms
+    let mut terms = vec![parse_atomic_expr(first, params)?];
+    let mut operators = Vec::new();
+    while let Some(operator) = rest.peek().and_then(|op| infix_operator(op.as_rule())) {
+        rest.next();
+        check_value(terms.last().expect("there is always a term"))?;
+        let right = check_value(parse_atomic_expr(rest.next().ok_or(ParseError::MissingOperand("arithmetic right"))?, params)?)?;
+        match operator {
+            ast::InfixOperator::Multiply | ast::InfixOperator::Divide => {
+                let left = terms.pop().expect("there is always a term");
+                terms.push(infix(left, operator, right));
+            }
+            ast::InfixOperator::Add | ast::InfixOperator::Subtract => {
+                operators.push(operator);
+                terms.push(right);
+            }
+        }
+    }
+
+    let mut terms = terms.into_iter();
+    let first = terms.next().expect("there is always a term");
+    Ok(operators.into_iter().zip(terms).fold(first, |left, (operator, right)| infix(left, operator, right)))
 }
 
-/// Create a comparison predicate from a left expression and a right pair
-fn create_comparison(left: ast::Expr, op: grammar::Rule, right: Pair<grammar::Rule>) -> Result<ast::Expr, ParseError> {
-    let right_expr = parse_atomic_expr(right)?;
-    let operator = match op {
+/// Reject expressions which can't be used as a value, such as a parenthesized predicate or a list
+fn check_value<E: std::borrow::Borrow<ast::Expr>>(expr: E) -> Result<E, ParseError> {
+    match expr.borrow() {
+        ast::Expr::Predicate(_) | ast::Expr::ExprList(_) => Err(ParseError::InvalidOperand(format!("{:?}", expr.borrow()))),
+        _ => Ok(expr),
+    }
+}
+
+fn is_comparison(rule: grammar::Rule) -> bool {
+    matches!(
+        rule,
+        grammar::Rule::Eq
+            | grammar::Rule::GtEq
+            | grammar::Rule::Gt
+            | grammar::Rule::LtEq
+            | grammar::Rule::Lt
+            | grammar::Rule::NotEq
+            | grammar::Rule::In
+            | grammar::Rule::Between
+            | grammar::Rule::Like
+            | grammar::Rule::ILike
+            | grammar::Rule::StartsWith
+            | grammar::Rule::Contains
+            | grammar::Rule::Matches
+    )
+}
+
+/// Create a comparison predicate from a left expression, taking the operands of the operator from `rest`
+fn create_comparison(
+    left: ast::Expr,
+    op: Pair<grammar::Rule>,
+    rest: &mut Pairs<grammar::Rule>,
+    params: &mut Params,
+) -> Result<ast::Expr, ParseError> {
+    let operator = match op.as_rule() {
         grammar::Rule::Eq => ast::ComparisonOperator::Equal,
         grammar::Rule::GtEq => ast::ComparisonOperator::GreaterThanOrEqual,
         grammar::Rule::Gt => ast::ComparisonOperator::GreaterThan,
         grammar::Rule::LtEq => ast::ComparisonOperator::LessThanOrEqual,
         grammar::Rule::Lt => ast::ComparisonOperator::LessThan,
         grammar::Rule::NotEq => ast::ComparisonOperator::NotEqual,
+        grammar::Rule::In => ast::ComparisonOperator::In,
+        grammar::Rule::Between => ast::ComparisonOperator::Between,
+        grammar::Rule::Like => ast::ComparisonOperator::Like,
+        grammar::Rule::ILike => ast::ComparisonOperator::ILike,
+        grammar::Rule::StartsWith => ast::ComparisonOperator::StartsWith,
+        grammar::Rule::Contains => ast::ComparisonOperator::Contains,
+        grammar::Rule::Matches => ast::ComparisonOperator::Matches,
         _ => {
-            return Err(ParseError::UnexpectedRule { expected: "comparison operator", got: op });
+            return Err(ParseError::UnexpectedRule { expected: "comparison operator", got: op.as_rule() });
+        }
+    };
+
+    let left = check_value(left)?;
+    let right = match operator {
+        ast::ComparisonOperator::In => {
+            let list = rest.next().ok_or(ParseError::MissingOperand("IN list"))?;
+            if list.as_rule() != grammar::Rule::ExpressionList {
+                return Err(ParseError::UnexpectedRule { expected: "ExpressionList", got: list.as_rule() });
+            }
+            ast::Expr::ExprList(list.into_inner().map(|item| parse_atomic_expr(item, params)).collect::<Result<_, _>>()?)
         }
+        ast::ComparisonOperator::Between => {
+            let low = rest.next().ok_or(ParseError::MissingOperand("BETWEEN lower bound"))?;
+            let low = parse_operand(low, rest, params)?;
+            match rest.next() {
+                Some(and) if and.as_rule() == grammar::Rule::And => {}
+                Some(other) => return Err(ParseError::UnexpectedRule { expected: "And", got: other.as_rule() }),
+                None => return Err(ParseError::MissingOperand("BETWEEN upper bound")),
+            }
+            let high = rest.next().ok_or(ParseError::MissingOperand("BETWEEN upper bound"))?;
+            ast::Expr::ExprList(vec![low, parse_operand(high, rest, params)?])
+        }
+        _ => check_value(parse_operand(rest.next().ok_or(ParseError::MissingOperand("right"))?, rest, params)?)?,
     };
-    Ok(ast::Expr::Predicate(ast::Predicate::Comparison { left: Box::new(left), operator, right: Box::new(right_expr) }))
+
+    let comparison = ast::Predicate::Comparison { left: Box::new(left), operator, right: Box::new(right) };
+    // NOT IN, NOT BETWEEN, NOT LIKE and so on carry the NOT on the operator
+    let negated = op.into_inner().any(|inner| inner.as_rule() == grammar::Rule::NotFlag);
+    Ok(ast::Expr::Predicate(if negated { ast::Predicate::Not(Box::new(comparison)) } else { comparison }))
 }
 
-/// Create a logical operation (AND/OR) from a left expression and a right pair
+/// Create a logical operation (AND/OR) from a left expression, taking the right operand from `rest`
 fn create_logical_op(
     op: grammar::Rule,
     left: ast::Expr,
-    right: Pair<grammar::Rule>,
     rest: &mut Pairs<grammar::Rule>,
+    params: &mut Params,
 ) -> Result<ast::Expr, ParseError> {
     let left_pred = left.try_into()?;
 
     // Parse the right side, which might be part of a comparison
-    let right_expr = parse_atomic_expr(right)?;
-    let right_pred = if let Some(next_op) = rest.next() {
-        match next_op.as_rule() {
-            grammar::Rule::Eq
-            | grammar::Rule::GtEq
-            | grammar::Rule::Gt
-            | grammar::Rule::LtEq
-            | grammar::Rule::Lt
-            | grammar::Rule::NotEq => {
-                let next_right = rest.next().ok_or(ParseError::MissingOperand("comparison right"))?;
-                let next_right_expr = parse_atomic_expr(next_right)?;
-                ast::Predicate::Comparison {
-                    left: Box::new(right_expr),
-                    operator: match next_op.as_rule() {
-                        grammar::Rule::Eq => ast::ComparisonOperator::Equal,
-                        grammar::Rule::GtEq => ast::ComparisonOperator::GreaterThanOrEqual,
-                        grammar::Rule::Gt => ast::ComparisonOperator::GreaterThan,
-                        grammar::Rule::LtEq => ast::ComparisonOperator::LessThanOrEqual,
-                        grammar::Rule::Lt => ast::ComparisonOperator::LessThan,
-                        grammar::Rule::NotEq => ast::ComparisonOperator::NotEqual,
-                        _ => unreachable!(),
-                    },
-                    right: Box::new(next_right_expr),
-                }
-            }
-            _ => {
-                return Err(ParseError::UnexpectedRule { expected: "comparison operator", got: next_op.as_rule() });
-            }
+    let right = rest.next().ok_or(ParseError::MissingOperand("right"))?;
+    let right_expr = parse_operand(right, rest, params)?;
+    let right_pred = match rest.peek() {
+        Some(next_op) if is_comparison(next_op.as_rule()) => {
+            let next_op = rest.next().expect("peeked");
+            create_comparison(right_expr, next_op, rest, params)?.try_into()?
         }
-    } else {
-        right_expr.try_into()?
+        Some(next_op) if !matches!(next_op.as_rule(), grammar::Rule::And | grammar::Rule::Or) => {
+            return Err(ParseError::UnexpectedRule { expected: "comparison operator", got: next_op.as_rule() });
+        }
+        _ => right_expr.try_into()?,
     };
 
     Ok(ast::Expr::Predicate(match op {
@@ -138,16 +373,18 @@ fn create_logical_op(
     }))
 }
 
-/// Parse an atomic expression, which can be an identifier, literal, or parenthesized expression
-fn parse_atomic_expr(pair: Pair<grammar::Rule>) -> Result<ast::Expr, ParseError> {
+/// Parse an atomic expression, which can be an identifier, literal, placeholder, or parenthesized expression
+fn parse_atomic_expr(pair: Pair<grammar::Rule>, params: &mut Params) -> Result<ast::Expr, ParseError> {
     match pair.as_rule() {
+        grammar::Rule::Placeholder => params.bind(pair.as_str()),
         grammar::Rule::IdentifierWithOptionalContinuation => parse_identifier(pair),
         grammar::Rule::SingleQuotedString => parse_string_literal(pair),
-        grammar::Rule::Unsigned => parse_number(pair),
+        grammar::Rule::Unsigned | grammar::Rule::Integer | grammar::Rule::Decimal | grammar::Rule::Double => parse_number(pair),
+        grammar::Rule::True => Ok(ast::Expr::Literal(ast::Literal::Boolean(true))),
+        grammar::Rule::False => Ok(ast::Expr::Literal(ast::Literal::Boolean(false))),
         grammar::Rule::ExpressionInParentheses => {
             let inner = pair.into_inner().next().ok_or(ParseError::EmptyExpression)?;
-            let pred = parse_expr(inner)?;
-            Ok(ast::Expr::Predicate(pred))
+            parse_expr(inner, params)
         }
         _ => Err(ParseError::UnexpectedRule { expected: "atomic expression", got: pair.as_rule() }),
     }
@@ -187,7 +424,7 @@ fn parse_identifier(pair: Pair<grammar::Rule>) -> Result<ast::Expr, ParseError>
     }
 }
 
-/// Parse a string literal, removing the surrounding quotes
+/// Parse a string literal, removing the surrounding quotes and unescaping any within it
 fn parse_string_literal(pair: Pair<grammar::Rule>) -> Result<ast::Expr, ParseError> {
     if pair.as_rule() != grammar::Rule::SingleQuotedString {
         return Err(ParseError::UnexpectedRule { expected: "SingleQuotedString", got: pair.as_rule() });
@@ -197,20 +434,25 @@ fn parse_string_literal(pair: Pair<grammar::Rule>) -> Result<ast::Expr, ParseErr
     if !s.starts_with('\'') || !s.ends_with('\'') {
         return Err(ParseError::InvalidPredicate("String literal must be quoted".into()));
     }
-    let s = &s[1..s.len() - 1];
+    // Quotes within the string are doubled, as in SQL
+    let s = s[1..s.len() - 1].replace("''", "'");
 
-    Ok(ast::Expr::Literal(ast::Literal::String(s.to_string())))
+    Ok(ast::Expr::Literal(ast::Literal::String(s)))
 }
 
-/// Parse a number literal
+/// Parse a number literal. Numbers with a fraction or an exponent are floats
 fn parse_number(pair: Pair<grammar::Rule>) -> Result<ast::Expr, ParseError> {
-    if pair.as_rule() != grammar::Rule::Unsigned {
-        return Err(ParseError::UnexpectedRule { expected: "Unsigned", got: pair.as_rule() });
-    }
-
-    let num = pair.as_str().trim().parse::<i64>().map_err(|e| ParseError::InvalidPredicate(format!("Failed to parse number: {}", e)))?;
+    let literal = match pair.as_rule() {
+        grammar::Rule::Unsigned | grammar::Rule::Integer => ast::Literal::Integer(
+            pair.as_str().trim().parse::<i64>().map_err(|e| ParseError::InvalidPredicate(format!("Failed to parse number: {}", e)))?,
+        ),
+        grammar::Rule::Decimal | grammar::Rule::Double => ast::Literal::Float(
+            pair.as_str().trim().parse::<f64>().map_err(|e| ParseError::InvalidPredicate(format!("Failed to parse number: {}", e)))?,
+        ),
+        _ => return Err(ParseError::UnexpectedRule { expected: "number", got: pair.as_rule() }),
+    };
 
-    Ok(ast::Expr::Literal(ast::Literal::Integer(num)))
+    Ok(ast::Expr::Literal(literal))
 }
 
 #[cfg(test)]
@@ -279,4 +521,223 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn test_parse_query() {
+        let query = parse_query("status = 'active' ORDER BY person.age DESC, name LIMIT 10 OFFSET 20").unwrap();
+        assert_eq!(
+            query,
+            ast::Query {
+                predicate: parse_selection("status = 'active'").unwrap(),
+                order_by: vec![
+                    ast::OrderByItem {
+                        identifier: ast::Identifier::CollectionProperty("person".to_string(), "age".to_string()),
+                        direction: ast::OrderDirection::Desc,
+                    },
+                    ast::OrderByItem { identifier: ast::Identifier::Property("name".to_string()), direction: ast::OrderDirection::Asc },
+                ],
+                limit: Some(10),
+                offset: Some(20),
+            }
+        );
+
+        // The selection is optional, and OR isn't mistaken for the start of ORDER
+        assert_eq!(parse_query("LIMIT 5").unwrap(), ast::Query { limit: Some(5), ..ast::Query::from(ast::Predicate::True) });
+        let query = parse_query("a = 1 OR b = 2 ORDER BY a").unwrap();
+        assert!(matches!(query.predicate, ast::Predicate::Or(..)));
+        assert_eq!(query.order_by.len(), 1);
+
+        // Selections can't have clauses, or be empty
+        assert!(parse_selection("a = 1 LIMIT 5").is_err());
+        assert!(parse_selection("").is_err());
+        assert!(parse_query("a = 1 LIMIT -5").is_err());
+
+        // Clause keywords only start clauses where a clause can start, so they can be used as property names
+        let query = parse_query("order = 1 AND limit > 2 ORDER BY offset LIMIT 5").unwrap();
+        assert_eq!(query.predicate, parse_selection("order = 1 AND limit > 2").unwrap());
+        assert_eq!(query.order_by[0].identifier, ast::Identifier::Property("offset".to_string()));
+        assert_eq!(query.limit, Some(5));
+    }
+
+    #[test]
+    fn test_parse_aggregation() {
+        let property = |name: &str| ast::Identifier::Property(name.to_string());
+        let aggregation = parse_aggregation("SELECT artist, COUNT(*), sum(sales), MAX(year) WHERE year > 2000 GROUP BY artist").unwrap();
+        assert_eq!(
+            aggregation,
+            ast::Aggregation {
+                select: vec![
+                    ast::SelectItem::Property(property("artist")),
+                    ast::SelectItem::Aggregate { function: ast::AggregateFunction::Count, argument: None },
+                    ast::SelectItem::Aggregate { function: ast::AggregateFunction::Sum, argument: Some(property("sales")) },
+                    ast::SelectItem::Aggregate { function: ast::AggregateFunction::Max, argument: Some(property("year")) },
+                ],
+                predicate: parse_selection("year > 2000").unwrap(),
+                group_by: vec![property("artist")],
+            }
+        );
+
+        // The WHERE and GROUP BY clauses are optional, and a property named like a function is still a property
+        let aggregation = parse_aggregation("SELECT COUNT(counter), counter GROUP BY counter").unwrap();
+        assert_eq!(aggregation.predicate, ast::Predicate::True);
+        assert_eq!(aggregation.select[1], ast::SelectItem::Property(property("counter")));
+        // ...and so is one named SELECT
+        let aggregation = parse_aggregation("SELECT select, COUNT(*) WHERE select > 1 GROUP BY select").unwrap();
+        assert_eq!(aggregation.select[0], ast::SelectItem::Property(property("select")));
+
+        // Only COUNT can be applied to whole entities, and ungrouped properties can't be selected
+        assert!(parse_aggregation("SELECT SUM(*)").is_err());
+        assert!(parse_aggregation("SELECT artist, COUNT(*)").is_err());
+        assert!(parse_aggregation("COUNT(*)").is_err());
+    }
+
+    #[test]
+    fn test_parse_selection_arithmetic() {
+        let ident = |name: &str| Box::new(ast::Expr::Identifier(ast::Identifier::Property(name.to_string())));
+        let int = |value: i64| Box::new(ast::Expr::Literal(ast::Literal::Integer(value)));
+        let infix = |left, operator, right| Box::new(ast::Expr::InfixExpr { left, operator, right });
+
+        // Multiplication binds tighter than addition, and operators of the same precedence are left associative
+        let predicate = parse_selection("price - discount + price * quantity / 2 > 100").unwrap();
+        assert_eq!(
+            predicate,
+            ast::Predicate::Comparison {
+                left: infix(
+                    infix(ident("price"), ast::InfixOperator::Subtract, ident("discount")),
+                    ast::InfixOperator::Add,
+                    infix(infix(ident("price"), ast::InfixOperator::Multiply, ident("quantity")), ast::InfixOperator::Divide, int(2)),
+                ),
+                operator: ast::ComparisonOperator::GreaterThan,
+                right: int(100),
+            }
+        );
+
+        // Parentheses group arithmetic, and arithmetic can follow AND
+        let predicate = parse_selection("a = 1 AND (price + 1) * 2 <= 10").unwrap();
+        assert_eq!(
+            predicate,
+            ast::Predicate::And(
+                Box::new(ast::Predicate::Comparison { left: ident("a"), operator: ast::ComparisonOperator::Equal, right: int(1) }),
+                Box::new(ast::Predicate::Comparison {
+                    left: infix(infix(ident("price"), ast::InfixOperator::Add, int(1)), ast::InfixOperator::Multiply, int(2)),
+                    operator: ast::ComparisonOperator::LessThanOrEqual,
+                    right: int(10),
+                })
+            )
+        );
+
+        // Arithmetic on its own is not a predicate
+        assert!(parse_selection("price * 2").is_err());
+
+        // Nor can a predicate or a list be used as a value
+        assert!(matches!(parse_selection("(a = 1) + 2 > 3"), Err(ParseError::InvalidOperand(_))));
+        assert!(matches!(parse_selection("2 * (a = 1) > 3"), Err(ParseError::InvalidOperand(_))));
+        assert!(matches!(parse_selection("a = (b = 1)"), Err(ParseError::InvalidOperand(_))));
+        assert!(matches!(parse_selection("(a = 1) = true"), Err(ParseError::InvalidOperand(_))));
+    }
+
+    #[test]
+    fn test_parse_selection_in_and_between() {
+        let input = r#"status NOT IN ('open', 'pending') AND year BETWEEN 1990 AND 2000"#;
+        let predicate = parse_selection(input).unwrap();
+        assert_eq!(
+            predicate,
+            ast::Predicate::And(
+                Box::new(ast::Predicate::Not(Box::new(ast::Predicate::Comparison {
+                    left: Box::new(ast::Expr::Identifier(ast::Identifier::Property("status".to_string()))),
+                    operator: ast::ComparisonOperator::In,
+                    right: Box::new(ast::Expr::ExprList(vec![
+                        ast::Expr::Literal(ast::Literal::String("open".to_string())),
+                        ast::Expr::Literal(ast::Literal::String("pending".to_string())),
+                    ]))
+                }))),
+                Box::new(ast::Predicate::Comparison {
+                    left: Box::new(ast::Expr::Identifier(ast::Identifier::Property("year".to_string()))),
+                    operator: ast::ComparisonOperator::Between,
+                    right: Box::new(ast::Expr::ExprList(vec![
+                        ast::Expr::Literal(ast::Literal::Integer(1990)),
+                        ast::Expr::Literal(ast::Literal::Integer(2000)),
+                    ]))
+                })
+            )
+        );
+
+        // BETWEEN's AND binds to its bounds before the logical AND
+        let predicate = parse_selection("year BETWEEN 1990 AND 2000 AND status IN ('open')").unwrap();
+        assert!(matches!(predicate, ast::Predicate::And(..)));
+        assert!(parse_selection("year BETWEEN 1990").is_err());
+        assert!(parse_selection("status IN 'open'").is_err());
+    }
+
+    #[test]
+    fn test_parse_selection_patterns() {
+        let comparison = |name: &str, operator, value: &str| ast::Predicate::Comparison {
+            left: Box::new(ast::Expr::Identifier(ast::Identifier::Property(name.to_string()))),
+            operator,
+            right: Box::new(ast::Expr::Literal(ast::Literal::String(value.to_string()))),
+        };
+
+        let predicate = parse_selection("name NOT STARTS WITH 'The' AND title ilike '%live%'").unwrap();
+        assert_eq!(
+            predicate,
+            ast::Predicate::And(
+                Box::new(ast::Predicate::Not(Box::new(comparison("name", ast::ComparisonOperator::StartsWith, "The")))),
+                Box::new(comparison("title", ast::ComparisonOperator::ILike, "%live%")),
+            )
+        );
+        assert_eq!(parse_selection("title CONTAINS 'live'").unwrap(), comparison("title", ast::ComparisonOperator::Contains, "live"));
+        assert_eq!(parse_selection("title LIKE 'Live_'").unwrap(), comparison("title", ast::ComparisonOperator::Like, "Live_"));
+        assert_eq!(
+            parse_selection("notes MATCHES 'live album'").unwrap(),
+            comparison("notes", ast::ComparisonOperator::Matches, "live album")
+        );
+        assert!(parse_selection("name STARTS 'The'").is_err());
+    }
+
+    #[test]
+    fn test_parse_selection_with_params() {
+        let name = ast::Literal::String("O'Brien' OR 1 = 1 --".to_string());
+        let equals = |name: &str, value: ast::Literal| ast::Predicate::Comparison {
+            left: Box::new(ast::Expr::Identifier(ast::Identifier::Property(name.to_string()))),
+            operator: ast::ComparisonOperator::Equal,
+            right: Box::new(ast::Expr::Literal(value)),
+        };
+
+        // A bound value is only ever a literal, whatever it contains
+        let predicate = parse_selection_with_params("name = ? AND age > ?", &[name.clone(), ast::Literal::Integer(30)]).unwrap();
+        assert_eq!(
+            predicate,
+            ast::Predicate::And(
+                Box::new(equals("name", name.clone())),
+                Box::new(ast::Predicate::Comparison {
+                    left: Box::new(ast::Expr::Identifier(ast::Identifier::Property("age".to_string()))),
+                    operator: ast::ComparisonOperator::GreaterThan,
+                    right: Box::new(ast::Expr::Literal(ast::Literal::Integer(30))),
+                }),
+            )
+        );
+
+        // Numbered placeholders can be reused and given in any order
+        let predicate =
+            parse_selection_with_params("name = $2 OR nickname = $2 OR year IN ($1)", &[ast::Literal::Integer(1990), name.clone()]);
+        assert!(predicate.is_ok());
+        let query = parse_query_with_params("name = $1 ORDER BY name LIMIT 5", std::slice::from_ref(&name)).unwrap();
+        assert_eq!(query.predicate, equals("name", name.clone()));
+        assert_eq!(query.limit, Some(5));
+
+        // Quotes within a string literal are doubled
+        assert_eq!(parse_selection("name = 'O''Brien'").unwrap(), equals("name", ast::Literal::String("O'Brien".to_string())));
+        assert_eq!(parse_selection("name = ''").unwrap(), equals("name", ast::Literal::String("".to_string())));
+
+        let one = std::slice::from_ref(&name);
+        let error = |input: &str, params: &[ast::Literal]| {
+            matches!(parse_selection_with_params(input, params), Err(ParseError::InvalidParameters(_)))
+        };
+        assert!(error("name = ?", &[]));
+        assert!(error("name = ?", &[name.clone(), name.clone()]));
+        assert!(error("name = $0", one));
+        assert!(error("name = $2", one));
+        assert!(error("name = ? OR name = $1", one));
+        assert!(error("name = 'Alice'", one));
+    }
 }
@@ -1,2 +1,6 @@
+pub mod aggregate;
 pub mod filter;
+pub mod order;
+pub mod pattern;
+pub mod search;
 pub mod sql;
@@ -0,0 +1,142 @@
+//! Evaluate aggregations over items in memory. This is necessary for storage engines which scan over their data rather
+//! than being able to compute aggregates themselves.
+
+use std::cmp::Ordering;
+
+use crate::ast::{AggregateFunction, Aggregation, Identifier, InfixOperator, Literal, OrderByItem, OrderDirection, SelectItem};
+use crate::selection::filter::{evaluate_arithmetic, Error, Filterable};
+use crate::selection::order::{compare_keys, compare_literals, sort_key};
+
+/// A row of aggregation results, with a value for each item of the SELECT list in turn.
+/// As in SQL, a value is None when there is nothing to aggregate, such as the SUM of no values.
+pub type AggregateRow = Vec<Option<Literal>>;
+
+/// Aggregate items which match the aggregation's predicate, returning a row for each group in ascending order of the
+/// GROUP BY properties. Without a GROUP BY clause there is always exactly one row, even if there are no items.
+pub fn aggregate<'a, I: Filterable + 'a>(
+    items: impl IntoIterator<Item = &'a I>,
+    aggregation: &Aggregation,
+) -> Result<Vec<AggregateRow>, Error> {
+    // Grouping is a sort by the GROUP BY properties, after which each group is a run of items with equal keys
+    let group_by: Vec<OrderByItem> = aggregation
+        .group_by
+        .iter()
+        .map(|identifier| OrderByItem { identifier: identifier.clone(), direction: OrderDirection::Asc })
+        .collect();
+    let mut keyed: Vec<(Vec<Option<Literal>>, &I)> = items.into_iter().map(|item| (sort_key(item, &group_by), item)).collect();
+    keyed.sort_by(|(a, _), (b, _)| compare_keys(a, b, &group_by));
+
+    let mut groups: Vec<(Vec<Option<Literal>>, Vec<&I>)> = Vec::new();
+    for (key, item) in keyed {
+        match groups.last_mut() {
+            Some((group_key, members)) if compare_keys(group_key, &key, &group_by) == Ordering::Equal => members.push(item),
+            _ => groups.push((key, vec![item])),
+        }
+    }
+    if groups.is_empty() && group_by.is_empty() {
+        groups.push((Vec::new(), Vec::new()));
+    }
+
+    groups
+        .iter()
+        .map(|(key, members)| aggregation.select.iter().map(|item| select(item, &aggregation.group_by, key, members)).collect())
+        .collect()
+}
+
+/// The value of one item of the SELECT list for a group
+fn select<I: Filterable>(
+    item: &SelectItem,
+    group_by: &[Identifier],
+    key: &[Option<Literal>],
+    members: &[&I],
+) -> Result<Option<Literal>, Error> {
+    let (function, argument) = match item {
+        SelectItem::Property(identifier) => {
+            let position = group_by.iter().position(|group| group == identifier).ok_or_else(|| property_not_found(identifier))?;
+            return Ok(key[position].clone());
+        }
+        SelectItem::Aggregate { function, argument } => (function, argument),
+    };
+
+    let Some(argument) = argument else {
+        return Ok(Some(Literal::Integer(members.len() as i64)));
+    };
+    let name = match argument {
+        Identifier::Property(name) | Identifier::CollectionProperty(_, name) => name,
+    };
+    // Missing values are skipped, as NULLs are in SQL
+    let mut values = members.iter().filter_map(|member| member.value(name));
+
+    Ok(match function {
+        AggregateFunction::Count => Some(Literal::Integer(values.count() as i64)),
+        // Adding the first value to zero converts it to a number, or fails if it isn't one
+        AggregateFunction::Sum => match values.next() {
+            Some(first) => {
+                let first = evaluate_arithmetic(&Literal::Integer(0), &InfixOperator::Add, &first)?;
+                Some(values.try_fold(first, |sum, value| evaluate_arithmetic(&sum, &InfixOperator::Add, &value))?)
+            }
+            None => None,
+        },
+        AggregateFunction::Min => values.min_by(compare_literals),
+        AggregateFunction::Max => values.max_by(compare_literals),
+    })
+}
+
+fn property_not_found(identifier: &Identifier) -> Error {
+    match identifier {
+        Identifier::Property(name) | Identifier::CollectionProperty(_, name) => Error::PropertyNotFound(name.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_aggregation;
+
+    struct Sale {
+        artist: &'static str,
+        copies: Option<i64>,
+        price: f64,
+    }
+
+    impl Filterable for Sale {
+        fn collection(&self) -> &str { "sales" }
+
+        fn value(&self, name: &str) -> Option<Literal> {
+            match name {
+                "artist" => Some(Literal::String(self.artist.to_string())),
+                "copies" => self.copies.map(Literal::Integer),
+                "price" => Some(Literal::Float(self.price)),
+                _ => None,
+            }
+        }
+    }
+
+    fn rows(sales: &[Sale], aggregation: &str) -> Vec<AggregateRow> { aggregate(sales, &parse_aggregation(aggregation).unwrap()).unwrap() }
+
+    #[test]
+    fn test_aggregate() {
+        let sales = [
+            Sale { artist: "Muse", copies: Some(3), price: 9.5 },
+            Sale { artist: "Blur", copies: Some(2), price: 7.0 },
+            Sale { artist: "Muse", copies: None, price: 12.0 },
+            Sale { artist: "Muse", copies: Some(4), price: 8.0 },
+        ];
+        let int = |value: i64| Some(Literal::Integer(value));
+        let float = |value: f64| Some(Literal::Float(value));
+        let string = |value: &str| Some(Literal::String(value.to_string()));
+
+        assert_eq!(
+            rows(&sales, "SELECT artist, COUNT(*), COUNT(copies), SUM(copies), MAX(price) GROUP BY artist"),
+            [vec![string("Blur"), int(1), int(1), int(2), float(7.0)], vec![string("Muse"), int(3), int(2), int(7), float(12.0)]]
+        );
+        assert_eq!(rows(&sales, "SELECT SUM(price), MIN(copies)"), [vec![float(36.5), int(2)]]);
+
+        // Without a GROUP BY there is a row even when nothing is aggregated, but with one there are no groups
+        assert_eq!(rows(&[], "SELECT COUNT(*), SUM(copies)"), [vec![int(0), None]]);
+        assert!(rows(&[], "SELECT COUNT(*) GROUP BY artist").is_empty());
+
+        // Only numbers can be summed
+        assert!(aggregate(&sales, &parse_aggregation("SELECT SUM(artist)").unwrap()).is_err());
+    }
+}
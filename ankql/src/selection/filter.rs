@@ -1,9 +1,12 @@
 //! Filter items based on a predicate. This is necessary for cases where we are scanning over a set of data
 //! which has not been pre-filtered by an index search - or to supplement/validate an index search with additional filtering.
 
-use crate::ast::{ComparisonOperator, Expr, Identifier, Literal, Predicate};
+use std::cmp::Ordering;
+
+use crate::ast::{ComparisonOperator, Expr, Identifier, InfixOperator, Literal, Predicate};
+use crate::selection::pattern::like;
+use crate::selection::search;
 use thiserror::Error;
-use tracing::info;
 
 #[derive(Debug, Error, PartialEq)]
 pub enum Error {
@@ -11,24 +14,46 @@ pub enum Error {
     CollectionMismatch { expected: String, actual: String },
     #[error("property not found: {0}")]
     PropertyNotFound(String),
+    #[error("type mismatch: can't compare {left:?} with {right:?}")]
+    TypeMismatch { left: Literal, right: Literal },
+    #[error("{0:?} expects a list of {1} values")]
+    InvalidOperands(ComparisonOperator, &'static str),
+    #[error("not a number: {0:?}")]
+    NotANumber(Literal),
+    #[error("division by zero")]
+    DivisionByZero,
+    #[error("integer overflow in {0:?}")]
+    Overflow(InfixOperator),
+    #[error("only literal, identifier and arithmetic expressions can be used as values, got {0:?}")]
+    UnsupportedExpression(Expr),
 }
 
-// Just to make this fast, lets assume all values are strings.
 pub trait Filterable {
     fn collection(&self) -> &str;
-    // TODO figure out how to make this generic so we can perform typecast eligibity checking
-    // and perform the actual typecast for comparisions
-    fn value(&self, name: &str) -> Option<String>;
+    /// The typed value of a property, or None if the item doesn't have it
+    fn value(&self, name: &str) -> Option<Literal>;
+    /// The members of a set-valued property, or None if the property isn't a set.
+    /// Comparing a set for equality with a value tests whether the value is a member
+    fn members(&self, _name: &str) -> Option<Vec<String>> { None }
+}
+
+/// The members of the property named by `expr`, if it is a set-valued property
+fn evaluate_members<I: Filterable>(item: &I, expr: &Expr) -> Result<Option<Vec<String>>, Error> {
+    match expr {
+        Expr::Identifier(Identifier::Property(name)) => Ok(item.members(name)),
+        Expr::Identifier(Identifier::CollectionProperty(collection, name)) => {
+            if collection != item.collection() {
+                return Err(Error::CollectionMismatch { expected: collection.clone(), actual: item.collection().to_string() });
+            }
+            Ok(item.members(name))
+        }
+        _ => Ok(None),
+    }
 }
 
-fn evaluate_expr<I: Filterable>(item: &I, expr: &Expr) -> Result<String, Error> {
+fn evaluate_expr<I: Filterable>(item: &I, expr: &Expr) -> Result<Literal, Error> {
     match expr {
-        Expr::Literal(lit) => Ok(match lit {
-            Literal::String(s) => s.clone(),
-            Literal::Integer(i) => i.to_string(),
-            Literal::Float(f) => f.to_string(),
-            Literal::Boolean(b) => b.to_string(),
-        }),
+        Expr::Literal(lit) => Ok(lit.clone()),
         Expr::Identifier(id) => match id {
             Identifier::Property(name) => item.value(name).ok_or_else(|| Error::PropertyNotFound(name.clone())),
             Identifier::CollectionProperty(collection, name) => {
@@ -38,25 +63,181 @@ fn evaluate_expr<I: Filterable>(item: &I, expr: &Expr) -> Result<String, Error>
                 item.value(name).ok_or_else(|| Error::PropertyNotFound(name.clone()))
             }
         },
-        _ => unimplemented!("Only literal and identifier expressions are supported"),
+        Expr::InfixExpr { left, operator, right } => {
+            evaluate_arithmetic(&evaluate_expr(item, left)?, operator, &evaluate_expr(item, right)?)
+        }
+        Expr::Predicate(_) | Expr::ExprList(_) => Err(Error::UnsupportedExpression(expr.clone())),
+    }
+}
+
+/// Apply an arithmetic operator with the same semantics as SQL: integer arithmetic stays integral, and division
+/// truncates, unless either side is a float. Strings are used as numbers if they parse as one.
+pub fn evaluate_arithmetic(left: &Literal, operator: &InfixOperator, right: &Literal) -> Result<Literal, Error> {
+    match (as_number(left)?, as_number(right)?) {
+        (Literal::Integer(l), Literal::Integer(r)) => {
+            let result = match operator {
+                InfixOperator::Add => l.checked_add(r),
+                InfixOperator::Subtract => l.checked_sub(r),
+                InfixOperator::Multiply => l.checked_mul(r),
+                InfixOperator::Divide if r == 0 => return Err(Error::DivisionByZero),
+                InfixOperator::Divide => l.checked_div(r),
+            };
+            result.map(Literal::Integer).ok_or_else(|| Error::Overflow(operator.clone()))
+        }
+        (l, r) => {
+            let (l, r) = (as_float(&l), as_float(&r));
+            Ok(Literal::Float(match operator {
+                InfixOperator::Add => l + r,
+                InfixOperator::Subtract => l - r,
+                InfixOperator::Multiply => l * r,
+                InfixOperator::Divide if r == 0.0 => return Err(Error::DivisionByZero),
+                InfixOperator::Divide => l / r,
+            }))
+        }
+    }
+}
+
+/// The value as an integer or a float
+fn as_number(value: &Literal) -> Result<Literal, Error> {
+    match value {
+        Literal::Integer(_) | Literal::Float(_) => Ok(value.clone()),
+        Literal::String(string) => parse_as(string, &Literal::Integer(0)).ok_or_else(|| Error::NotANumber(value.clone())),
+        Literal::Boolean(_) => Err(Error::NotANumber(value.clone())),
+    }
+}
+
+fn as_float(number: &Literal) -> f64 {
+    match number {
+        Literal::Integer(integer) => *integer as f64,
+        Literal::Float(float) => *float,
+        _ => unreachable!("only called with numbers"),
+    }
+}
+
+/// Compare two values, coercing between types where there is an unambiguous conversion:
+/// - Integers and floats compare numerically
+/// - A string compares with a number or boolean if it parses as one, so string properties can be compared with numeric literals
+///
+/// Any other pair of types is a `TypeMismatch`. `None` means the values are unordered, which is only the case for NaN.
+pub fn compare_values(left: &Literal, right: &Literal) -> Result<Option<Ordering>, Error> {
+    let mismatch = || Error::TypeMismatch { left: left.clone(), right: right.clone() };
+    Ok(match (left, right) {
+        (Literal::String(l), Literal::String(r)) => Some(l.cmp(r)),
+        (Literal::Integer(l), Literal::Integer(r)) => Some(l.cmp(r)),
+        (Literal::Boolean(l), Literal::Boolean(r)) => Some(l.cmp(r)),
+        (Literal::Integer(l), Literal::Float(r)) => (*l as f64).partial_cmp(r),
+        (Literal::Float(l), Literal::Integer(r)) => l.partial_cmp(&(*r as f64)),
+        (Literal::Float(l), Literal::Float(r)) => l.partial_cmp(r),
+        (Literal::String(string), other) => compare_values(&parse_as(string, other).ok_or_else(mismatch)?, other)?,
+        (other, Literal::String(string)) => compare_values(other, &parse_as(string, other).ok_or_else(mismatch)?)?,
+        _ => return Err(mismatch()),
+    })
+}
+
+/// Parse a string as the same type as `like`
+fn parse_as(string: &str, like: &Literal) -> Option<Literal> {
+    let string = string.trim();
+    match like {
+        Literal::Integer(_) => string.parse().map(Literal::Integer).or_else(|_| string.parse().map(Literal::Float)).ok(),
+        Literal::Float(_) => string.parse().map(Literal::Float).ok(),
+        Literal::Boolean(_) => string.parse().map(Literal::Boolean).ok(),
+        Literal::String(_) => Some(Literal::String(string.to_owned())),
+    }
+}
+
+/// Match a string against a pattern. Patterns only apply to strings, so any other type on either side is a mismatch
+fn evaluate_pattern(value: &Literal, operator: &ComparisonOperator, pattern: &Literal) -> Result<bool, Error> {
+    let (Literal::String(value), Literal::String(pattern)) = (value, pattern) else {
+        return Err(Error::TypeMismatch { left: value.clone(), right: pattern.clone() });
+    };
+    Ok(match operator {
+        ComparisonOperator::Like => like(value, pattern),
+        ComparisonOperator::ILike => like(&value.to_lowercase(), &pattern.to_lowercase()),
+        ComparisonOperator::StartsWith => value.starts_with(pattern.as_str()),
+        ComparisonOperator::Contains => value.contains(pattern.as_str()),
+        ComparisonOperator::Matches => search::matches(value, pattern),
+        _ => unreachable!("only called with pattern operators"),
+    })
+}
+
+fn evaluate_comparison(left: &Literal, operator: &ComparisonOperator, right: &Literal) -> Result<bool, Error> {
+    if let ComparisonOperator::Like
+    | ComparisonOperator::ILike
+    | ComparisonOperator::StartsWith
+    | ComparisonOperator::Contains
+    | ComparisonOperator::Matches = operator
+    {
+        return evaluate_pattern(left, operator, right);
     }
+
+    // Values which can't be converted to one another are simply unequal
+    let ordering = match (operator, compare_values(left, right)) {
+        (ComparisonOperator::Equal, Err(Error::TypeMismatch { .. })) => return Ok(false),
+        (ComparisonOperator::NotEqual, Err(Error::TypeMismatch { .. })) => return Ok(true),
+        (_, result) => result?,
+    };
+
+    Ok(match operator {
+        ComparisonOperator::Equal => ordering == Some(Ordering::Equal),
+        ComparisonOperator::NotEqual => ordering != Some(Ordering::Equal),
+        ComparisonOperator::GreaterThan => ordering == Some(Ordering::Greater),
+        ComparisonOperator::GreaterThanOrEqual => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        ComparisonOperator::LessThan => ordering == Some(Ordering::Less),
+        ComparisonOperator::LessThanOrEqual => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        ComparisonOperator::In => return Err(Error::InvalidOperands(ComparisonOperator::In, "one or more")),
+        ComparisonOperator::Between => return Err(Error::InvalidOperands(ComparisonOperator::Between, "two")),
+        ComparisonOperator::Like
+        | ComparisonOperator::ILike
+        | ComparisonOperator::StartsWith
+        | ComparisonOperator::Contains
+        | ComparisonOperator::Matches => {
+            unreachable!("patterns are matched above")
+        }
+    })
 }
 
 pub fn evaluate_predicate<I: Filterable>(item: &I, predicate: &Predicate) -> Result<bool, Error> {
     match predicate {
+        Predicate::Comparison { left, operator: operator @ (ComparisonOperator::Equal | ComparisonOperator::NotEqual), right }
+            if evaluate_members(item, left)?.is_some() || evaluate_members(item, right)?.is_some() =>
+        {
+            let (members, value) = match evaluate_members(item, left)? {
+                Some(members) => (members, evaluate_expr(item, right)?),
+                None => (evaluate_members(item, right)?.unwrap_or_default(), evaluate_expr(item, left)?),
+            };
+            let is_member = members
+                .into_iter()
+                .any(|member| evaluate_comparison(&Literal::String(member), &ComparisonOperator::Equal, &value).unwrap_or(false));
+            Ok(if *operator == ComparisonOperator::Equal { is_member } else { !is_member })
+        }
+        Predicate::Comparison { left, operator: ComparisonOperator::In, right } => {
+            let Expr::ExprList(list) = &**right else { return Err(Error::InvalidOperands(ComparisonOperator::In, "one or more")) };
+            // A set-valued property is IN the list if any of its members are
+            let values = match evaluate_members(item, left)? {
+                Some(members) => members.into_iter().map(Literal::String).collect(),
+                None => vec![evaluate_expr(item, left)?],
+            };
+            for expr in list {
+                let candidate = evaluate_expr(item, expr)?;
+                for value in &values {
+                    if evaluate_comparison(value, &ComparisonOperator::Equal, &candidate)? {
+                        return Ok(true);
+                    }
+                }
+            }
+            Ok(false)
+        }
+        Predicate::Comparison { left, operator: ComparisonOperator::Between, right } => {
+            let Expr::ExprList(bounds) = &**right else { return Err(Error::InvalidOperands(ComparisonOperator::Between, "two")) };
+            let [low, high] = bounds.as_slice() else { return Err(Error::InvalidOperands(ComparisonOperator::Between, "two")) };
+            let value = evaluate_expr(item, left)?;
+            Ok(evaluate_comparison(&value, &ComparisonOperator::GreaterThanOrEqual, &evaluate_expr(item, low)?)?
+                && evaluate_comparison(&value, &ComparisonOperator::LessThanOrEqual, &evaluate_expr(item, high)?)?)
+        }
         Predicate::Comparison { left, operator, right } => {
             let left_val = evaluate_expr(item, left)?;
             let right_val = evaluate_expr(item, right)?;
-
-            Ok(match operator {
-                ComparisonOperator::Equal => left_val == right_val,
-                ComparisonOperator::NotEqual => left_val != right_val,
-                ComparisonOperator::GreaterThan => left_val > right_val,
-                ComparisonOperator::GreaterThanOrEqual => left_val >= right_val,
-                ComparisonOperator::LessThan => left_val < right_val,
-                ComparisonOperator::LessThanOrEqual => left_val <= right_val,
-                _ => unimplemented!("Only basic comparison operators are supported"),
-            })
+            evaluate_comparison(&left_val, operator, &right_val)
         }
         Predicate::And(left, right) => Ok(evaluate_predicate(item, left)? && evaluate_predicate(item, right)?),
         Predicate::Or(left, right) => Ok(evaluate_predicate(item, left)? || evaluate_predicate(item, right)?),
@@ -116,10 +297,10 @@ mod tests {
     impl Filterable for TestItem {
         fn collection(&self) -> &str { "users" }
 
-        fn value(&self, name: &str) -> Option<String> {
+        fn value(&self, name: &str) -> Option<Literal> {
             match name {
-                "name" => Some(self.name.clone()),
-                "age" => Some(self.age.clone()),
+                "name" => Some(Literal::String(self.name.clone())),
+                "age" => Some(Literal::String(self.age.clone())),
                 _ => None,
             }
         }
@@ -187,4 +368,173 @@ mod tests {
             ]
         );
     }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct TypedItem {
+        age: Literal,
+        active: bool,
+    }
+
+    impl Filterable for TypedItem {
+        fn collection(&self) -> &str { "users" }
+
+        fn value(&self, name: &str) -> Option<Literal> {
+            match name {
+                "age" => Some(self.age.clone()),
+                "active" => Some(Literal::Boolean(self.active)),
+                _ => None,
+            }
+        }
+    }
+
+    fn matches(item: &TypedItem, query: &str) -> Result<bool, Error> { evaluate_predicate(item, &parse_selection(query).unwrap()) }
+
+    #[test]
+    fn test_typed_comparison() {
+        let ten = TypedItem { age: Literal::Integer(10), active: true };
+        assert_eq!(matches(&ten, "age > 9"), Ok(true));
+        assert_eq!(matches(&ten, "age <= 9.5"), Ok(false));
+        assert_eq!(matches(&ten, "age = 10.0"), Ok(true));
+        assert_eq!(matches(&ten, "active = true AND age < 11"), Ok(true));
+
+        // Strings which parse as a number compare numerically against numbers, rather than lexically
+        let text = TypedItem { age: Literal::String("10".to_string()), active: false };
+        assert_eq!(matches(&text, "age > 9"), Ok(true));
+        assert_eq!(matches(&text, "age > '9'"), Ok(false));
+        assert_eq!(matches(&text, "active != true"), Ok(true));
+
+        let nan = TypedItem { age: Literal::Float(f64::NAN), active: false };
+        assert_eq!(matches(&nan, "age = 1 OR age < 1 OR age > 1"), Ok(false));
+    }
+
+    #[test]
+    fn test_type_mismatch() {
+        let item = TypedItem { age: Literal::Integer(10), active: true };
+
+        // Values of incompatible types are never equal, but can't be ordered
+        assert_eq!(matches(&item, "age = 'ten'"), Ok(false));
+        assert_eq!(matches(&item, "age != 'ten'"), Ok(true));
+        assert_eq!(matches(&item, "active = 1"), Ok(false));
+        assert_eq!(
+            matches(&item, "age > 'ten'"),
+            Err(Error::TypeMismatch { left: Literal::Integer(10), right: Literal::String("ten".to_string()) })
+        );
+        assert!(matches!(matches(&item, "active < 2"), Err(Error::TypeMismatch { .. })));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let ten = TypedItem { age: Literal::Integer(10), active: true };
+        assert_eq!(matches(&ten, "age * 2 > 19"), Ok(true));
+        assert_eq!(matches(&ten, "age + 2 * 3 = 16"), Ok(true));
+        assert_eq!(matches(&ten, "(age - 4) * 2 = 12"), Ok(true));
+        assert_eq!(matches(&ten, "age - 4 - 3 = 3"), Ok(true));
+        assert_eq!(matches(&ten, "age + 1 BETWEEN 11 AND 5 + 6"), Ok(true));
+
+        // Integer division truncates, as in SQL, unless either side is a float
+        assert_eq!(matches(&ten, "age / 4 = 2"), Ok(true));
+        assert_eq!(matches(&ten, "age / 4.0 = 2.5"), Ok(true));
+
+        // Strings which parse as numbers take part in arithmetic
+        let text = TypedItem { age: Literal::String("10".to_string()), active: false };
+        assert_eq!(matches(&text, "age * 2 = 20"), Ok(true));
+
+        assert_eq!(matches(&ten, "age / 0 = 1"), Err(Error::DivisionByZero));
+        assert_eq!(matches(&ten, "active + 1 = 2"), Err(Error::NotANumber(Literal::Boolean(true))));
+        assert_eq!(matches(&ten, "age * 9223372036854775807 > 0"), Err(Error::Overflow(InfixOperator::Multiply)));
+    }
+
+    #[test]
+    fn test_in_and_between() {
+        let ten = TypedItem { age: Literal::Integer(10), active: true };
+        assert_eq!(matches(&ten, "age IN (5, 10, 'ten')"), Ok(true));
+        assert_eq!(matches(&ten, "age IN (5, 11)"), Ok(false));
+        assert_eq!(matches(&ten, "age NOT IN (5, 11)"), Ok(true));
+
+        // BETWEEN includes both bounds
+        assert_eq!(matches(&ten, "age BETWEEN 10 AND 20"), Ok(true));
+        assert_eq!(matches(&ten, "age BETWEEN 1 AND 9.5"), Ok(false));
+        assert_eq!(matches(&ten, "active = true AND age BETWEEN 5 AND 10"), Ok(true));
+        assert_eq!(matches(&ten, "age NOT BETWEEN 5 AND 10"), Ok(false));
+    }
+
+    #[test]
+    fn test_unsupported_expression() {
+        // The parser rejects these, but a hand built predicate can still use a comparison as a value
+        let nested = Predicate::Comparison {
+            left: Box::new(Expr::Identifier(Identifier::Property("name".to_string()))),
+            operator: ComparisonOperator::Equal,
+            right: Box::new(Expr::Literal(Literal::String("Alice".to_string()))),
+        };
+        let predicate = Predicate::Comparison {
+            left: Box::new(Expr::Predicate(nested.clone())),
+            operator: ComparisonOperator::Equal,
+            right: Box::new(Expr::Literal(Literal::Boolean(true))),
+        };
+        assert_eq!(
+            evaluate_predicate(&TestItem::new("Alice", "30"), &predicate),
+            Err(Error::UnsupportedExpression(Expr::Predicate(nested)))
+        );
+    }
+
+    #[test]
+    fn test_patterns() {
+        let item = TestItem::new("Origin of Symmetry", "25");
+        let matches = |selection: &str| evaluate_predicate(&item, &parse_selection(selection).unwrap());
+        assert_eq!(matches("name LIKE 'Origin%'"), Ok(true));
+        assert_eq!(matches("name LIKE 'origin%'"), Ok(false));
+        assert_eq!(matches("name ILIKE 'origin%SYMMETRY'"), Ok(true));
+        assert_eq!(matches("name NOT LIKE '%of%'"), Ok(false));
+        assert_eq!(matches("name STARTS WITH 'Origin'"), Ok(true));
+        assert_eq!(matches("name starts with 'Symmetry'"), Ok(false));
+        assert_eq!(matches("name CONTAINS 'of Sym' AND age LIKE '2_'"), Ok(true));
+        assert_eq!(matches("name NOT CONTAINS 'Drones'"), Ok(true));
+        assert_eq!(matches("name MATCHES 'symmetry ORIGIN'"), Ok(true));
+        assert_eq!(matches("name MATCHES 'origin drones'"), Ok(false));
+        // Patterns only match strings
+        assert!(matches("name LIKE 5").is_err());
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct TaggedItem {
+        name: String,
+        tags: Vec<String>,
+    }
+
+    impl Filterable for TaggedItem {
+        fn collection(&self) -> &str { "albums" }
+
+        fn value(&self, name: &str) -> Option<Literal> {
+            match name {
+                "name" => Some(Literal::String(self.name.clone())),
+                _ => None,
+            }
+        }
+
+        fn members(&self, name: &str) -> Option<Vec<String>> {
+            match name {
+                "tags" => Some(self.tags.clone()),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_membership() {
+        let rock = TaggedItem { name: "Showbiz".to_string(), tags: vec!["rock".to_string(), "debut".to_string()] };
+        let pop = TaggedItem { name: "Drones".to_string(), tags: vec!["pop".to_string()] };
+        let empty = TaggedItem { name: "Demo".to_string(), tags: vec![] };
+
+        let predicate = parse_selection("tags = 'rock'").unwrap();
+        let results: Vec<_> = FilterIterator::new(vec![rock.clone(), pop.clone(), empty.clone()].into_iter(), predicate).collect();
+        assert_eq!(results, vec![FilterResult::Pass(rock.clone()), FilterResult::Skip(pop.clone()), FilterResult::Skip(empty.clone())]);
+
+        let predicate = parse_selection("'rock' != tags AND name <> 'Demo'").unwrap();
+        let results: Vec<_> = FilterIterator::new(vec![rock.clone(), pop.clone(), empty.clone()].into_iter(), predicate).collect();
+        assert_eq!(results, vec![FilterResult::Skip(rock.clone()), FilterResult::Pass(pop.clone()), FilterResult::Skip(empty.clone())]);
+
+        let predicate = parse_selection("tags IN ('pop', 'debut')").unwrap();
+        let results: Vec<_> = FilterIterator::new(vec![rock.clone(), pop.clone(), empty.clone()].into_iter(), predicate).collect();
+        assert_eq!(results, vec![FilterResult::Pass(rock), FilterResult::Pass(pop), FilterResult::Skip(empty)]);
+    }
 }
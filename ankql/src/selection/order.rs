@@ -0,0 +1,156 @@
+//! Order and window items according to the ORDER BY, LIMIT and OFFSET clauses of a query. This is necessary for storage
+//! engines which scan over their data rather than having an index or query planner to do it for them.
+
+use std::cmp::Ordering;
+
+use crate::ast::{Identifier, Literal, OrderByItem, OrderDirection, Query};
+use crate::selection::filter::Filterable;
+use crate::selection::search::{self, relevance};
+
+/// Compare two items by each of the properties in an ORDER BY clause in turn.
+/// Missing values sort after all others, as NULLs do in SQL, so they come first when descending.
+pub fn compare_items<I: Filterable>(a: &I, b: &I, order_by: &[OrderByItem]) -> Ordering {
+    compare_keys(&sort_key(a, order_by), &sort_key(b, order_by), order_by)
+}
+
+/// The values of an item's properties named in an ORDER BY clause. Together with the item's ID, this identifies its
+/// position in the ordered results, which is what a pagination cursor records.
+pub fn sort_key<I: Filterable>(item: &I, order_by: &[OrderByItem]) -> Vec<Option<Literal>> {
+    order_by
+        .iter()
+        .map(|item_order| match &item_order.identifier {
+            Identifier::Property(name) | Identifier::CollectionProperty(_, name) => item.value(name),
+        })
+        .collect()
+}
+
+/// Compare two sort keys, as produced by [`sort_key`], with the same rules as [`compare_items`].
+pub fn compare_keys(a: &[Option<Literal>], b: &[Option<Literal>], order_by: &[OrderByItem]) -> Ordering {
+    for ((a, b), item) in a.iter().zip(b).zip(order_by) {
+        let ordering = match (a, b) {
+            (Some(a), Some(b)) => compare_literals(a, b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        let ordering = match item.direction {
+            OrderDirection::Asc => ordering,
+            OrderDirection::Desc => ordering.reverse(),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// A total order over literals, so that properties holding values of different types still sort consistently.
+/// Unlike predicates, strings are not coerced to numbers: booleans sort before numbers, and numbers before strings.
+pub fn compare_literals(a: &Literal, b: &Literal) -> Ordering {
+    fn rank(literal: &Literal) -> u8 {
+        match literal {
+            Literal::Boolean(_) => 0,
+            Literal::Integer(_) | Literal::Float(_) => 1,
+            Literal::String(_) => 2,
+        }
+    }
+
+    match (a, b) {
+        (Literal::Boolean(a), Literal::Boolean(b)) => a.cmp(b),
+        (Literal::Integer(a), Literal::Integer(b)) => a.cmp(b),
+        (Literal::Integer(a), Literal::Float(b)) => (*a as f64).total_cmp(b),
+        (Literal::Float(a), Literal::Integer(b)) => a.total_cmp(&(*b as f64)),
+        (Literal::Float(a), Literal::Float(b)) => a.total_cmp(b),
+        (Literal::String(a), Literal::String(b)) => a.cmp(b),
+        (a, b) => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// Sort items by the query's ORDER BY clause, and then skip its OFFSET and take its LIMIT. Without an ORDER BY, the results
+/// of a full-text search are ranked from most to least relevant.
+/// The sort is stable, so items which compare equal keep the order they were given in.
+pub fn order_and_window<T, I: Filterable>(mut items: Vec<T>, query: &Query, item: impl Fn(&T) -> &I) -> Vec<T> {
+    if !query.order_by.is_empty() {
+        items.sort_by(|a, b| compare_items(item(a), item(b), &query.order_by));
+    } else if !search::searches(&query.predicate).is_empty() {
+        let mut ranked: Vec<(f64, T)> = items.into_iter().map(|i| (relevance(item(&i), &query.predicate).unwrap_or(0.0), i)).collect();
+        ranked.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        items = ranked.into_iter().map(|(_, i)| i).collect();
+    }
+    let offset = query.offset.unwrap_or(0) as usize;
+    let limit = query.limit.map_or(usize::MAX, |limit| limit as usize);
+    items.into_iter().skip(offset).take(limit).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_query;
+    use crate::selection::filter::evaluate_predicate;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Album {
+        name: &'static str,
+        year: Option<i64>,
+    }
+
+    impl Filterable for Album {
+        fn collection(&self) -> &str { "albums" }
+
+        fn value(&self, name: &str) -> Option<Literal> {
+            match name {
+                "name" => Some(Literal::String(self.name.to_string())),
+                "year" => self.year.map(Literal::Integer),
+                _ => None,
+            }
+        }
+    }
+
+    fn names(albums: &[Album], query: &str) -> Vec<&'static str> {
+        let query = parse_query(query).unwrap();
+        let matching = albums.iter().filter(|album| evaluate_predicate(*album, &query.predicate).unwrap_or(false)).collect();
+        order_and_window(matching, &query, |album| *album).iter().map(|album| album.name).collect()
+    }
+
+    #[test]
+    fn test_order_and_window() {
+        let albums = [
+            Album { name: "Drones", year: Some(2015) },
+            Album { name: "Showbiz", year: Some(1999) },
+            Album { name: "Demos", year: None },
+            Album { name: "Absolution", year: Some(2003) },
+            Album { name: "Origin of Symmetry", year: Some(2001) },
+        ];
+
+        assert_eq!(names(&albums, "ORDER BY year"), ["Showbiz", "Origin of Symmetry", "Absolution", "Drones", "Demos"]);
+        assert_eq!(names(&albums, "ORDER BY year DESC"), ["Demos", "Drones", "Absolution", "Origin of Symmetry", "Showbiz"]);
+        assert_eq!(names(&albums, "year > 2000 ORDER BY name LIMIT 2"), ["Absolution", "Drones"]);
+        assert_eq!(names(&albums, "ORDER BY year LIMIT 2 OFFSET 1"), ["Origin of Symmetry", "Absolution"]);
+        // Without an ORDER BY, the given order is kept
+        assert_eq!(names(&albums, "LIMIT 2"), ["Drones", "Showbiz"]);
+        assert_eq!(names(&albums, "OFFSET 4"), ["Origin of Symmetry"]);
+        // Unless it's a search, which is ranked by relevance
+        assert_eq!(names(&albums, "name MATCHES 'origin symmetry' OR name MATCHES 'drones'"), ["Origin of Symmetry", "Drones"]);
+        assert_eq!(names(&albums, "name MATCHES 'symmetry' OR name MATCHES 'drones' LIMIT 1"), ["Drones"]);
+    }
+
+    #[test]
+    fn test_compare_keys() {
+        let query = parse_query("ORDER BY year DESC, name").unwrap();
+        let demos = sort_key(&Album { name: "Demos", year: None }, &query.order_by);
+        let drones = sort_key(&Album { name: "Drones", year: Some(2015) }, &query.order_by);
+        assert_eq!(drones, [Some(Literal::Integer(2015)), Some(Literal::String("Drones".to_string()))]);
+        // Missing values come first when descending
+        assert_eq!(compare_keys(&demos, &drones, &query.order_by), Ordering::Less);
+        assert_eq!(compare_keys(&drones, &drones, &query.order_by), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_compare_literals() {
+        assert_eq!(compare_literals(&Literal::Integer(2), &Literal::Float(1.5)), Ordering::Greater);
+        assert_eq!(compare_literals(&Literal::Boolean(true), &Literal::Integer(0)), Ordering::Less);
+        // Strings sort as strings, even if they look like numbers
+        assert_eq!(compare_literals(&Literal::String("10".to_string()), &Literal::String("9".to_string())), Ordering::Less);
+        assert_eq!(compare_literals(&Literal::String("1".to_string()), &Literal::Integer(2)), Ordering::Greater);
+    }
+}
@@ -0,0 +1,105 @@
+//! Matching of LIKE patterns, in which `%` matches any sequence of characters, `_` matches any single character, and a
+//! backslash matches the character after it literally, as in Postgres.
+
+enum Token {
+    Any,
+    One,
+    Char(char),
+}
+
+fn tokenize(pattern: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '%' => Token::Any,
+            '_' => Token::One,
+            // A trailing backslash has nothing to escape, so it matches itself
+            '\\' => Token::Char(chars.next().unwrap_or('\\')),
+            c => Token::Char(c),
+        });
+    }
+    tokens
+}
+
+/// Whether the whole of a string matches a LIKE pattern
+pub fn like(value: &str, pattern: &str) -> bool {
+    let tokens = tokenize(pattern);
+    let value: Vec<char> = value.chars().collect();
+
+    // Match greedily, and on a mismatch retry from the most recent % with it consuming one more character
+    let (mut v, mut t) = (0, 0);
+    let mut retry: Option<(usize, usize)> = None;
+    while v < value.len() {
+        match tokens.get(t) {
+            Some(Token::Any) => {
+                retry = Some((t, v));
+                t += 1;
+            }
+            Some(Token::One) => {
+                v += 1;
+                t += 1;
+            }
+            Some(Token::Char(c)) if *c == value[v] => {
+                v += 1;
+                t += 1;
+            }
+            _ => match retry {
+                Some((any, consumed)) => {
+                    retry = Some((any, consumed + 1));
+                    t = any + 1;
+                    v = consumed + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    tokens[t..].iter().all(|token| matches!(token, Token::Any))
+}
+
+/// The literal text at the start of a LIKE pattern, before its first wildcard. Every string matching the pattern starts
+/// with it, which lets a pattern be narrowed down to a range of strings.
+pub fn literal_prefix(pattern: &str) -> String {
+    let mut prefix = String::new();
+    for token in tokenize(pattern) {
+        match token {
+            Token::Char(c) => prefix.push(c),
+            Token::Any | Token::One => break,
+        }
+    }
+    prefix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_like() {
+        assert!(like("Origin of Symmetry", "Origin%"));
+        assert!(like("Origin of Symmetry", "%of%"));
+        assert!(like("Origin of Symmetry", "%Symmetry"));
+        assert!(like("Drones", "Dr_nes"));
+        assert!(like("", "%"));
+        assert!(!like("Drones", "Dr_es"));
+        assert!(!like("Drones", "drones"));
+        // % backtracks to find a later match
+        assert!(like("abcabcd", "%abcd"));
+        assert!(like("aXbXc", "a%b%c"));
+        assert!(!like("aXbX", "a%b%c"));
+        // Escaped wildcards match themselves
+        assert!(like("100%", "100\\%"));
+        assert!(!like("1000", "100\\%"));
+        assert!(like("a_b", "a\\_b"));
+        assert!(!like("axb", "a\\_b"));
+    }
+
+    #[test]
+    fn test_literal_prefix() {
+        assert_eq!(literal_prefix("Origin%"), "Origin");
+        assert_eq!(literal_prefix("Dr_nes"), "Dr");
+        assert_eq!(literal_prefix("%of%"), "");
+        assert_eq!(literal_prefix("100\\%%"), "100%");
+        assert_eq!(literal_prefix("Showbiz"), "Showbiz");
+    }
+}
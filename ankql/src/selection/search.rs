@@ -0,0 +1,129 @@
+//! Full-text search for the MATCHES operator. Text is split into lowercase terms at anything which isn't a letter or digit,
+//! and a value matches a search if it contains every one of the search's terms, in any order. Matching values are ranked by
+//! how often the terms occur in them relative to their length.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::ast::{ComparisonOperator, Expr, Identifier, Literal, Predicate};
+use crate::selection::filter::Filterable;
+
+/// Split text into the lowercase terms which are indexed and searched for
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|term| !term.is_empty()).map(|term| term.to_lowercase()).collect()
+}
+
+/// The relevance of a value to a search, or None if it doesn't contain all of the search's terms.
+/// Each term scores more the more often it occurs, with diminishing returns, and the total is scaled down for longer values.
+pub fn score(text: &str, search: &str) -> Option<f64> {
+    let terms: BTreeSet<String> = tokenize(search).into_iter().collect();
+    // A search without any terms would match everything, which is never what was meant
+    if terms.is_empty() {
+        return None;
+    }
+
+    let tokens = tokenize(text);
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for token in &tokens {
+        *counts.entry(token.as_str()).or_default() += 1;
+    }
+
+    let mut score = 0.0;
+    for term in &terms {
+        let count = *counts.get(term.as_str())?;
+        score += 1.0 + (count as f64).ln();
+    }
+    Some(score / (tokens.len() as f64).sqrt())
+}
+
+/// Whether a value contains all of a search's terms
+pub fn matches(text: &str, search: &str) -> bool { score(text, search).is_some() }
+
+/// The property and search of each MATCHES comparison in a predicate which isn't negated
+pub fn searches(predicate: &Predicate) -> Vec<(&str, &str)> {
+    let mut searches = Vec::new();
+    collect_searches(predicate, false, &mut searches);
+    searches
+}
+
+/// The property and search of each MATCHES comparison which every item matching the predicate must satisfy, so that an index
+/// of terms can narrow down the items to evaluate the predicate against
+pub fn required_searches(predicate: &Predicate) -> Vec<(&str, &str)> {
+    let mut searches = Vec::new();
+    collect_searches(predicate, true, &mut searches);
+    searches
+}
+
+fn collect_searches<'a>(predicate: &'a Predicate, required: bool, searches: &mut Vec<(&'a str, &'a str)>) {
+    match predicate {
+        Predicate::Comparison { left, operator: ComparisonOperator::Matches, right } => {
+            if let (
+                Expr::Identifier(Identifier::Property(name) | Identifier::CollectionProperty(_, name)),
+                Expr::Literal(Literal::String(search)),
+            ) = (&**left, &**right)
+            {
+                searches.push((name.as_str(), search.as_str()));
+            }
+        }
+        Predicate::And(left, right) => {
+            collect_searches(left, required, searches);
+            collect_searches(right, required, searches);
+        }
+        Predicate::Or(left, right) if !required => {
+            collect_searches(left, required, searches);
+            collect_searches(right, required, searches);
+        }
+        _ => {}
+    }
+}
+
+/// The relevance of an item to the searches in a predicate, which is the sum of its scores for those it matches.
+/// This is None if the predicate doesn't search for anything.
+pub fn relevance<I: Filterable>(item: &I, predicate: &Predicate) -> Option<f64> {
+    let searches = searches(predicate);
+    if searches.is_empty() {
+        return None;
+    }
+    Some(
+        searches
+            .into_iter()
+            .filter_map(|(name, search)| match item.value(name) {
+                Some(Literal::String(text)) => score(&text, search),
+                _ => None,
+            })
+            .sum(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_selection;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(tokenize("Hello, World! It's 2024"), ["hello", "world", "it", "s", "2024"]);
+        assert!(tokenize(" -- ").is_empty());
+    }
+
+    #[test]
+    fn test_score() {
+        assert!(matches("Notes on the Rust borrow checker", "rust NOTES"));
+        assert!(!matches("Notes on the Rust borrow checker", "rust async"));
+        assert!(!matches("Anything at all", "  "));
+
+        // More occurrences rank higher, and so do shorter values
+        let once = score("rust is fast", "rust").unwrap();
+        let twice = score("rust is rust", "rust").unwrap();
+        let longer = score("rust is fast and safe", "rust").unwrap();
+        assert!(twice > once);
+        assert!(once > longer);
+    }
+
+    #[test]
+    fn test_searches() {
+        let predicate = parse_selection("title MATCHES 'rust' AND (body MATCHES 'async' OR body NOT MATCHES 'sync')").unwrap();
+        assert_eq!(searches(&predicate), [("title", "rust"), ("body", "async")]);
+        assert_eq!(required_searches(&predicate), [("title", "rust")]);
+        assert!(searches(&parse_selection("title = 'rust'").unwrap()).is_empty());
+    }
+}
@@ -1,9 +1,13 @@
-use crate::ast::{ComparisonOperator, Expr, Identifier, Literal, Predicate};
+use crate::ast::{
+    AggregateFunction, Aggregation, ComparisonOperator, Expr, Identifier, InfixOperator, Literal, OrderDirection, Predicate, Query,
+    SelectItem,
+};
 
 fn generate_expr_sql(expr: &Expr) -> String {
     match expr {
         Expr::Literal(lit) => match lit {
-            Literal::String(s) => format!("'{}'", s),
+            // Quotes within the string are doubled so that it can't end the literal early
+            Literal::String(s) => format!("'{}'", s.replace('\'', "''")),
             Literal::Integer(i) => i.to_string(),
             Literal::Float(f) => f.to_string(),
             Literal::Boolean(b) => b.to_string(),
@@ -14,7 +18,21 @@ fn generate_expr_sql(expr: &Expr) -> String {
                 format!(r#""{}"."{}""#, collection, name)
             }
         },
-        _ => unimplemented!("Only literal and identifier expressions are supported"),
+        Expr::ExprList(list) => format!("({})", list.iter().map(generate_expr_sql).collect::<Vec<_>>().join(", ")),
+        Expr::InfixExpr { left, operator, right } => {
+            format!("({} {} {})", generate_expr_sql(left), infix_op_to_sql(operator), generate_expr_sql(right))
+        }
+        // The parser only accepts values here, but SQL can compare a predicate as a boolean too
+        Expr::Predicate(predicate) => format!("({})", generate_selection_sql(predicate)),
+    }
+}
+
+fn infix_op_to_sql(op: &InfixOperator) -> &'static str {
+    match op {
+        InfixOperator::Add => "+",
+        InfixOperator::Subtract => "-",
+        InfixOperator::Multiply => "*",
+        InfixOperator::Divide => "/",
     }
 }
 
@@ -26,12 +44,24 @@ fn comparison_op_to_sql(op: &ComparisonOperator) -> &'static str {
         ComparisonOperator::GreaterThanOrEqual => ">=",
         ComparisonOperator::LessThan => "<",
         ComparisonOperator::LessThanOrEqual => "<=",
-        _ => unimplemented!("Only basic comparison operators are supported"),
+        ComparisonOperator::In => "IN",
+        ComparisonOperator::Between => "BETWEEN",
+        ComparisonOperator::Like => "LIKE",
+        ComparisonOperator::ILike => "ILIKE",
+        ComparisonOperator::StartsWith => "STARTS WITH",
+        ComparisonOperator::Contains => "CONTAINS",
+        ComparisonOperator::Matches => "MATCHES",
     }
 }
 
 pub fn generate_selection_sql(predicate: &Predicate) -> String {
     match predicate {
+        Predicate::Comparison { left, operator: ComparisonOperator::Between, right } => match &**right {
+            Expr::ExprList(bounds) if bounds.len() == 2 => {
+                format!("{} BETWEEN {} AND {}", generate_expr_sql(left), generate_expr_sql(&bounds[0]), generate_expr_sql(&bounds[1]))
+            }
+            _ => unimplemented!("BETWEEN requires a lower and an upper bound"),
+        },
         Predicate::Comparison { left, operator, right } => {
             format!("{} {} {}", generate_expr_sql(left), comparison_op_to_sql(operator), generate_expr_sql(right))
         }
@@ -47,10 +77,65 @@ pub fn generate_selection_sql(predicate: &Predicate) -> String {
     }
 }
 
+/// Generate the selection followed by any ORDER BY, LIMIT and OFFSET clauses
+pub fn generate_query_sql(query: &Query) -> String {
+    let mut parts = vec![generate_selection_sql(&query.predicate)];
+    if !query.order_by.is_empty() {
+        let items = query.order_by.iter().map(|item| {
+            let direction = match item.direction {
+                OrderDirection::Asc => "ASC",
+                OrderDirection::Desc => "DESC",
+            };
+            format!("{} {}", generate_expr_sql(&Expr::Identifier(item.identifier.clone())), direction)
+        });
+        parts.push(format!("ORDER BY {}", items.collect::<Vec<_>>().join(", ")));
+    }
+    if let Some(limit) = query.limit {
+        parts.push(format!("LIMIT {}", limit));
+    }
+    if let Some(offset) = query.offset {
+        parts.push(format!("OFFSET {}", offset));
+    }
+    parts.retain(|part| !part.is_empty());
+    parts.join(" ")
+}
+
+fn aggregate_function_to_sql(function: &AggregateFunction) -> &'static str {
+    match function {
+        AggregateFunction::Count => "COUNT",
+        AggregateFunction::Sum => "SUM",
+        AggregateFunction::Min => "MIN",
+        AggregateFunction::Max => "MAX",
+    }
+}
+
+fn generate_select_item_sql(item: &SelectItem) -> String {
+    match item {
+        SelectItem::Property(identifier) => generate_expr_sql(&Expr::Identifier(identifier.clone())),
+        SelectItem::Aggregate { function, argument: None } => format!("{}(*)", aggregate_function_to_sql(function)),
+        SelectItem::Aggregate { function, argument: Some(identifier) } => {
+            format!("{}({})", aggregate_function_to_sql(function), generate_expr_sql(&Expr::Identifier(identifier.clone())))
+        }
+    }
+}
+
+/// Generate the SELECT list followed by any WHERE and GROUP BY clauses
+pub fn generate_aggregation_sql(aggregation: &Aggregation) -> String {
+    let mut parts = vec![format!("SELECT {}", aggregation.select.iter().map(generate_select_item_sql).collect::<Vec<_>>().join(", "))];
+    if aggregation.predicate != Predicate::True {
+        parts.push(format!("WHERE {}", generate_selection_sql(&aggregation.predicate)));
+    }
+    if !aggregation.group_by.is_empty() {
+        let properties = aggregation.group_by.iter().map(|identifier| generate_expr_sql(&Expr::Identifier(identifier.clone())));
+        parts.push(format!("GROUP BY {}", properties.collect::<Vec<_>>().join(", ")));
+    }
+    parts.join(" ")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::parser::parse_selection;
+    use crate::parser::{parse_aggregation, parse_query, parse_selection};
 
     #[test]
     fn test_simple_equality() {
@@ -79,4 +164,53 @@ mod tests {
         let sql = generate_selection_sql(&predicate);
         assert_eq!(sql, r#""person"."name" = 'Alice'"#);
     }
+
+    #[test]
+    fn test_query_clauses() {
+        let query = parse_query("name = 'Alice' ORDER BY age DESC, name LIMIT 10 OFFSET 20").unwrap();
+        assert_eq!(generate_query_sql(&query), r#""name" = 'Alice' ORDER BY "age" DESC, "name" ASC LIMIT 10 OFFSET 20"#);
+
+        let query = parse_query("ORDER BY age LIMIT 5").unwrap();
+        assert_eq!(generate_query_sql(&query), r#"ORDER BY "age" ASC LIMIT 5"#);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let predicate = parse_selection("price * quantity + 5 > 100 AND (price + 1) / 2 <= 10").unwrap();
+        let sql = generate_selection_sql(&predicate);
+        assert_eq!(sql, r#"(("price" * "quantity") + 5) > 100 AND (("price" + 1) / 2) <= 10"#);
+    }
+
+    #[test]
+    fn test_in_and_between() {
+        let predicate = parse_selection("status IN ('open', 'pending') AND year NOT BETWEEN 1990 AND 2000").unwrap();
+        let sql = generate_selection_sql(&predicate);
+        assert_eq!(sql, r#""status" IN ('open', 'pending') AND NOT ("year" BETWEEN 1990 AND 2000)"#);
+    }
+
+    #[test]
+    fn test_patterns() {
+        let predicate = parse_selection("name NOT LIKE 'A%' AND name ILIKE '%b_' AND name STARTS WITH 'C' AND name CONTAINS 'd'").unwrap();
+        let sql = generate_selection_sql(&predicate);
+        assert_eq!(sql, r#"NOT ("name" LIKE 'A%') AND "name" ILIKE '%b_' AND "name" STARTS WITH 'C' AND "name" CONTAINS 'd'"#);
+    }
+
+    #[test]
+    fn test_quoted_string() {
+        let predicate = parse_selection("name = 'O''Brien' OR name = 'Alice'").unwrap();
+        let sql = generate_selection_sql(&predicate);
+        assert_eq!(sql, r#"("name" = 'O''Brien' OR "name" = 'Alice')"#);
+    }
+
+    #[test]
+    fn test_aggregation() {
+        let aggregation = parse_aggregation("SELECT artist, COUNT(*), SUM(sales) WHERE year > 2000 GROUP BY artist").unwrap();
+        assert_eq!(
+            generate_aggregation_sql(&aggregation),
+            r#"SELECT "artist", COUNT(*), SUM("sales") WHERE "year" > 2000 GROUP BY "artist""#
+        );
+
+        let aggregation = parse_aggregation("SELECT MIN(year)").unwrap();
+        assert_eq!(generate_aggregation_sql(&aggregation), r#"SELECT MIN("year")"#);
+    }
 }
@@ -96,7 +96,7 @@ pub use ankurah_core as core;
 pub use ankurah_derive as derive;
 pub use ankurah_proto as proto;
 
-pub use proto::ID;
+pub use proto::{Cursor, ID};
 // Re-export commonly used types
 pub use ankurah_core::{
     changes, error,
@@ -106,10 +106,10 @@ pub use ankurah_core::{
     model::View,
     node::{FetchArgs, Node},
     property,
-    resultset::ResultSet,
+    resultset::{Page, ResultSet},
     storage,
     subscription::SubscriptionHandle,
-    transaction, Model,
+    transaction, value, Model,
 };
 
 // TODO move this somewhere else - it's a dependency of the signal derive macro
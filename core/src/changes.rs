@@ -1,6 +1,6 @@
 use crate::{
     model::{Entity, View},
-    resultset::ResultSet,
+    property::PropertyName,
 };
 use ankurah_proto::Event;
 use std::sync::Arc;
@@ -42,6 +42,11 @@ impl<I> ItemChange<I> {
     pub fn kind(&self) -> ChangeKind { ChangeKind::from(self) }
 }
 
+impl<I: View> ItemChange<I> {
+    /// The properties of the item which hold conflicting concurrent values
+    pub fn conflicts(&self) -> Vec<PropertyName> { self.entity().backends().conflicts() }
+}
+
 impl<I> std::fmt::Display for ItemChange<I>
 where I: View
 {
@@ -89,10 +94,7 @@ impl<I> From<ChangeSet<Arc<Entity>>> for ChangeSet<I>
 where I: View
 {
     fn from(val: ChangeSet<Arc<Entity>>) -> Self {
-        ChangeSet {
-            resultset: ResultSet { items: val.resultset.iter().map(|item| I::from_entity(item.clone())).collect() },
-            changes: val.changes.into_iter().map(|change| change.into()).collect(),
-        }
+        ChangeSet { resultset: val.resultset.map(I::from_entity), changes: val.changes.into_iter().map(|change| change.into()).collect() }
     }
 }
 
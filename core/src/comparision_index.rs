@@ -4,6 +4,9 @@ use std::collections::{BTreeMap, BTreeSet, HashMap};
 use crate::collation::Collatable;
 use ankql::ast;
 
+/// The inclusive lower and optional exclusive upper bound of a range of values
+type Bounds = (Vec<u8>, Option<Vec<u8>>);
+
 /// An index for a specific field and comparison operator
 /// Used for storage engines that don't offer watchable indexes
 /// This is a naive implementation that uses a BTreeMap for each operator
@@ -15,11 +18,14 @@ pub(crate) struct ComparisonIndex {
     pub(crate) eq: HashMap<Vec<u8>, Vec<proto::SubscriptionId>>,
     pub(crate) gt: BTreeMap<Vec<u8>, Vec<proto::SubscriptionId>>,
     pub(crate) lt: BTreeMap<Vec<u8>, Vec<proto::SubscriptionId>>,
+    /// Prefix matches, keyed by the inclusive lower and exclusive upper bound of the values they match.
+    /// There is no upper bound when every value from the lower bound up is a match.
+    pub(crate) range: BTreeMap<Bounds, Vec<proto::SubscriptionId>>,
 }
 
 impl ComparisonIndex {
     #[allow(unused)]
-    pub fn new() -> Self { Self { eq: HashMap::new(), gt: BTreeMap::new(), lt: BTreeMap::new() } }
+    pub fn new() -> Self { Self { eq: HashMap::new(), gt: BTreeMap::new(), lt: BTreeMap::new(), range: BTreeMap::new() } }
 
     fn for_entry<F, V>(&mut self, value: V, op: ast::ComparisonOperator, f: F)
     where
@@ -57,6 +63,13 @@ impl ComparisonIndex {
                     f(entry);
                 }
             }
+            ast::ComparisonOperator::StartsWith => {
+                // x STARTS WITH 'ab' is equivalent to x >= 'ab' AND x < 'ac'
+                let lower = value.to_bytes();
+                let upper = prefix_successor(&lower);
+                let entry = self.range.entry((lower, upper)).or_default();
+                f(entry);
+            }
             _ => panic!("Unsupported operator: {:?}", op),
         }
     }
@@ -93,11 +106,30 @@ impl ComparisonIndex {
             }
         }
 
+        // Check prefix matches (lower <= x < upper)
+        for ((_lower, upper), subs) in self.range.iter().take_while(|((lower, _), _)| *lower <= bytes) {
+            if upper.as_ref().is_none_or(|upper| bytes < *upper) {
+                result.extend(subs.iter().cloned());
+            }
+        }
+
         // Should just return the BTreeSet but this sucks for test cases
         result.into_iter().collect()
     }
 }
 
+/// The smallest byte string greater than every string starting with the given prefix, if there is one.
+fn prefix_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut upper = prefix.to_vec();
+    while let Some(last) = upper.pop() {
+        if last < u8::MAX {
+            upper.push(last + 1);
+            return Some(upper);
+        }
+    }
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::ComparisonIndex;
@@ -148,4 +180,26 @@ mod tests {
         // 26 should match sub0 and sub1 because > 20 and !< 25
         assert_eq!(index.find_matching(Value::Integer(26)), vec![sub1]);
     }
+
+    #[test]
+    fn test_prefix_index() {
+        let mut index = ComparisonIndex::new();
+        let sub0 = proto::SubscriptionId::test(0);
+        let sub1 = proto::SubscriptionId::test(1);
+        index.add(ast::Literal::String("Wal".to_string()), ast::ComparisonOperator::StartsWith, sub0);
+        index.add(ast::Literal::String("Walk".to_string()), ast::ComparisonOperator::StartsWith, sub1);
+
+        assert_eq!(index.find_matching(Value::String("Walking on a Dream".to_string())), vec![sub0, sub1]);
+        assert_eq!(index.find_matching(Value::String("Wallflower".to_string())), vec![sub0]);
+        assert_eq!(index.find_matching(Value::String("Wal".to_string())), vec![sub0]);
+        assert!(index.find_matching(Value::String("Wam".to_string())).is_empty());
+        assert!(index.find_matching(Value::String("Wa".to_string())).is_empty());
+
+        index.remove(ast::Literal::String("Wal".to_string()), ast::ComparisonOperator::StartsWith, sub0);
+        assert!(index.find_matching(Value::String("Wallflower".to_string())).is_empty());
+
+        // A prefix of only 0xFF bytes has no upper bound
+        assert_eq!(super::prefix_successor(&[0x61, 0xFF]), Some(vec![0x62]));
+        assert_eq!(super::prefix_successor(&[0xFF, 0xFF]), None);
+    }
 }
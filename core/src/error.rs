@@ -31,6 +31,14 @@ pub enum RetrievalError {
     Anyhow(anyhow::Error),
     #[error("Decode error: {0}")]
     DecodeError(DecodeError),
+    #[error("Event {0} is missing from the entity history")]
+    MissingEvent(ID),
+    #[error("Unknown property backend: {0}")]
+    UnknownBackend(String),
+    #[error("Property backend {0} is registered as a different type")]
+    BackendTypeMismatch(String),
+    #[error("Property backend {0} is built in and can't be replaced")]
+    BuiltinBackend(String),
 }
 
 impl RetrievalError {
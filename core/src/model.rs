@@ -1,15 +1,15 @@
-use ankurah_proto::{Clock, CollectionId, Event, State, ID};
+use ankurah_proto::{Clock, ClockOrdering, CollectionId, Event, NodeId, State, ID};
 use tracing::info;
 // use futures_signals::signal::Signal;
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
-use std::sync::Mutex;
 
-use crate::{error::RetrievalError, property::Backends};
+use crate::{error::RetrievalError, property::Backends, value::Value};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
-use ankql::selection::filter::Filterable;
+use ankql::{ast::Literal, selection::filter::Filterable};
 
 /// A model is a struct that represents the present values for a given entity
 /// Schema is defined primarily by the Model object, and the View is derived from that via macro.
@@ -34,7 +34,7 @@ pub trait View {
 
 impl std::fmt::Display for Entity {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Entity({}/{}) = {}", self.collection, self.id, self.head.lock().unwrap())
+        write!(f, "Entity({}/{}) = {}", self.collection, self.id, self.head())
     }
 }
 
@@ -44,7 +44,6 @@ pub struct Entity {
     pub id: ID,
     pub collection: CollectionId,
     backends: Backends,
-    head: Arc<Mutex<Clock>>,
     pub upstream: Option<Arc<Entity>>,
 }
 
@@ -53,35 +52,81 @@ impl Entity {
 
     pub fn to_state(&self) -> Result<State> { self.backends.to_state_buffers() }
 
+    /// The scalar value of a property, from whichever backend holds it
+    pub fn property_value(&self, name: &str) -> Option<Value> { self.backends.property_value(name) }
+
+    /// The set of most recent events which have been applied to this entity
+    pub fn head(&self) -> Clock { self.backends.head.lock().unwrap().clone() }
+
+    /// Whether this entity has been deleted. Deleted entities no longer match any query
+    pub fn is_deleted(&self) -> bool { self.backends.is_tombstoned() }
+
+    /// Mark this entity for deletion. The tombstone is written when the entity is committed
+    pub(crate) fn delete(&self) { self.backends.set_tombstone(); }
+
     // used by the Model macro
-    pub fn create(id: ID, collection: CollectionId, backends: Backends) -> Self {
-        Self { id, collection, backends, head: Arc::new(Mutex::new(Clock::default())), upstream: None }
-    }
+    pub fn create(id: ID, collection: CollectionId, backends: Backends) -> Self { Self { id, collection, backends, upstream: None } }
     pub fn from_state(id: ID, collection: CollectionId, state: &State) -> Result<Self, RetrievalError> {
         let backends = Backends::from_state_buffers(state)?;
 
-        Ok(Self { id, collection, backends, head: Arc::new(Mutex::new(state.head.clone())), upstream: None })
+        Ok(Self { id, collection, backends, upstream: None })
+    }
+
+    /// Reconstruct an entity as it was at the given clock by replaying its events on top of the checkpoint,
+    /// or from the beginning if the clock doesn't descend from the checkpoint.
+    /// `history` must contain every event which the clock descends from that the checkpoint doesn't cover.
+    pub fn from_events(
+        id: ID,
+        collection: CollectionId,
+        checkpoint: Option<&State>,
+        history: &[Event],
+        clock: &Clock,
+    ) -> Result<Self, RetrievalError> {
+        let lineage: BTreeMap<ID, &Event> = history.iter().map(|e| (e.id, e)).collect();
+        let parent_of = |id: &ID| lineage.get(id).map(|e| &e.parent);
+
+        let (entity, covered) = match checkpoint {
+            Some(checkpoint) if matches!(clock.compare(&checkpoint.head, parent_of), ClockOrdering::Equal | ClockOrdering::Descends) => {
+                (Self::from_state(id, collection, checkpoint)?, checkpoint.head.ancestry(parent_of))
+            }
+            _ => (Self::create(id, collection, Backends::new()), BTreeSet::new()),
+        };
+
+        let mut pending = Vec::new();
+        for event_id in clock.ancestry(parent_of).difference(&covered) {
+            pending.push(*lineage.get(event_id).ok_or(RetrievalError::MissingEvent(*event_id))?);
+        }
+
+        for event in Self::causal_order(pending)? {
+            entity.apply_operations(event)?;
+        }
+        *entity.backends.head.lock().unwrap() = clock.clone();
+        Ok(entity)
     }
 
     /// Collect an event which contains all operations for all backends since the last time they were collected
-    /// Used for transaction commit.
+    /// Used for transaction commit, by the node `node_id`.
     /// TODO: We need to think about rollbacks
-    pub fn commit(&self) -> Result<Option<Event>> {
-        let operations = self.backends.to_operations()?;
-        if operations.is_empty() {
+    pub fn commit(&self, node_id: &NodeId) -> Result<Option<Event>> {
+        let id = ID::new();
+        let operations = self.backends.to_operations(id, ID::from_ulid(node_id.clone().into()))?;
+        // Only the deletion itself carries a tombstone, not every later edit of a deleted entity
+        let tombstone = self.is_deleted() && !self.upstream.as_ref().is_some_and(|upstream| upstream.is_deleted());
+        if operations.is_empty() && !tombstone {
             Ok(None)
         } else {
             let event = {
                 let event = Event {
-                    id: ID::new(),
+                    id,
                     entity_id: self.id.clone(),
                     collection: self.collection.clone(),
                     operations,
-                    parent: self.head.lock().unwrap().clone(),
+                    parent: self.head(),
+                    tombstone,
                 };
 
                 // Set the head to the event's ID
-                *self.head.lock().unwrap() = Clock::new([event.id]);
+                *self.backends.head.lock().unwrap() = Clock::new([event.id]);
                 event
             };
 
@@ -90,33 +135,106 @@ impl Entity {
         }
     }
 
-    pub fn apply_event(&self, event: &Event) -> Result<()> {
-        let head = Clock::new([event.id]);
-        for (backend_name, operations) in &event.operations {
-            // TODO - backends and Entity should not have two copies of the head. Figure out how to unify them
-            self.backends.apply_operations((*backend_name).to_owned(), operations, &head)?;
+    /// Apply an event to this entity, merging it with any concurrent events which have already been applied.
+    /// `history` holds previously recorded events for this entity. It is only consulted when the event
+    /// does not descend directly from our head, and may be empty if no such events are known.
+    pub fn apply_event(&self, event: &Event, history: &[Event]) -> Result<()> {
+        let head = self.head();
+        if head.contains(&event.id) {
+            return Ok(());
+        }
+
+        // Fast path: the event builds directly on top of what we have
+        if event.parent == head {
+            info!("Apply event {}", event);
+            self.apply_operations(event)?;
+            *self.backends.head.lock().unwrap() = Clock::new([event.id]);
+            return Ok(());
+        }
+
+        let lineage: BTreeMap<ID, &Event> = history.iter().chain(std::iter::once(event)).map(|e| (e.id, e)).collect();
+        let parent_of = |id: &ID| lineage.get(id).map(|e| &e.parent);
+
+        let applied = head.ancestry(parent_of);
+        if applied.contains(&event.id) {
+            return Ok(());
         }
-        // TODO figure out how to test this
-        info!("Apply event {}", event);
-        *self.head.lock().unwrap() = head;
-        info!("Apply event MARK 2 new head {}", self.head.lock().unwrap());
+
+        // Replay everything since the common ancestor which we haven't applied yet, rather than stomping on our state
+        let ancestry = Clock::new([event.id]).ancestry(parent_of);
+        let pending: Vec<&Event> = ancestry.difference(&applied).filter_map(|id| lineage.get(id).copied()).collect();
+        for pending_event in Self::causal_order(pending)? {
+            info!("Apply event {} (merge)", pending_event);
+            self.apply_operations(pending_event)?;
+        }
+
+        // Any of our heads which the event doesn't descend from remain concurrent with it
+        let mut new_head = Clock::new(head.iter().filter(|id| !ancestry.contains(id)).cloned().collect::<BTreeSet<_>>());
+        new_head.insert(event.id);
+        info!("Apply event {} new head {}", event, new_head);
+        *self.backends.head.lock().unwrap() = new_head;
 
         Ok(())
     }
 
-    /// HACK - we probably shouldn't be stomping on the backends like this
-    pub fn apply_state(&self, state: &State) -> Result<(), RetrievalError> {
-        self.backends.apply_state(state)?;
+    fn apply_operations(&self, event: &Event) -> Result<()> {
+        for (backend_name, operations) in &event.operations {
+            self.backends.apply_operations((*backend_name).to_owned(), event.id, operations)?;
+        }
+        if event.tombstone {
+            self.backends.set_tombstone();
+        }
         Ok(())
     }
 
+    /// Order events so that each one follows all of its parents, breaking ties by event id
+    fn causal_order(mut pending: Vec<&Event>) -> Result<Vec<&Event>> {
+        pending.sort_by_key(|e| e.id);
+        let mut ordered = Vec::with_capacity(pending.len());
+        while !pending.is_empty() {
+            let next = pending
+                .iter()
+                .position(|e| !e.parent.iter().any(|parent| pending.iter().any(|p| p.id == *parent)))
+                .ok_or_else(|| anyhow!("Cycle detected in event lineage"))?;
+            ordered.push(pending.remove(next));
+        }
+        Ok(ordered)
+    }
+
+    /// Merge the state of another replica of this entity into ours, based on how its head relates to our own.
+    /// `history` holds previously recorded events for this entity, which are used to compare the heads.
+    /// Returns true if our state changed.
+    pub fn merge_state(&self, state: &State, history: &[Event]) -> Result<bool, RetrievalError> {
+        let head = self.head();
+        let lineage: BTreeMap<ID, &Clock> = history.iter().map(|e| (e.id, &e.parent)).collect();
+        let parent_of = |id: &ID| lineage.get(id).copied();
+
+        let ordering = state.head.compare(&head, parent_of);
+        let new_head = match ordering {
+            // We've already seen everything in the incoming state
+            ClockOrdering::Equal | ClockOrdering::Ancestor => return Ok(false),
+            ClockOrdering::Descends => state.head.clone(),
+            ClockOrdering::Concurrent => {
+                // Retain the heads from either side which aren't an ancestor of another head
+                let candidates: BTreeSet<ID> = head.iter().chain(state.head.iter()).cloned().collect();
+                let parents: BTreeSet<ID> = candidates.iter().filter_map(parent_of).flat_map(|p| p.iter().cloned()).collect();
+                let superseded = Clock::new(parents).ancestry(parent_of);
+                Clock::new(candidates.difference(&superseded).cloned().collect::<BTreeSet<_>>())
+            }
+        };
+
+        info!("Merge state {} into {} ({:?})", state.head, self, ordering);
+        self.backends.merge_state(state, ordering)?;
+        *self.backends.head.lock().unwrap() = new_head;
+        Ok(true)
+    }
+
     /// Create a snapshot of the Entity which is detached from this one, and will not receive the updates this one does
     pub fn snapshot(self: &Arc<Self>) -> Arc<Self> {
         Arc::new(Self {
             id: self.id.clone(),
             collection: self.collection.clone(),
             backends: self.backends.fork(),
-            head: Arc::new(Mutex::new(self.head.lock().unwrap().clone())),
             upstream: Some(self.clone()),
         })
     }
@@ -125,12 +243,10 @@ impl Entity {
 impl Filterable for Entity {
     fn collection(&self) -> &str { self.collection.as_str() }
 
-    /// TODO Implement this as a typecasted value. eg value<T> -> Option<Result<T>>
-    /// where None is returned if the property is not found, and Err is returned if the property is found but is not able to be typecasted
-    /// to the requested type. (need to think about the rust type system here more)
-    fn value(&self, name: &str) -> Option<String> {
-        // Iterate through backends to find one that has this property
-        self.backends.backends.lock().unwrap().values().find_map(|backend| backend.get_property_value_string(name))
+    fn value(&self, name: &str) -> Option<Literal> { self.property_value(name)?.to_literal() }
+
+    fn members(&self, name: &str) -> Option<Vec<String>> {
+        self.backends.backends.lock().unwrap().values().find_map(|backend| backend.get_property_members(name))
     }
 }
 
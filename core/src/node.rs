@@ -1,3 +1,4 @@
+use ankql::selection::aggregate::AggregateRow;
 use ankurah_proto::{self as proto, CollectionId};
 use anyhow::anyhow;
 use dashmap::{DashMap, DashSet};
@@ -14,7 +15,7 @@ use crate::{
     error::{RequestError, RetrievalError},
     model::{Entity, View},
     reactor::Reactor,
-    resultset::ResultSet,
+    resultset::{Page, ResultSet},
     storage::{StorageCollectionWrapper, StorageEngine},
     subscription::SubscriptionHandle,
     transaction::Transaction,
@@ -28,19 +29,28 @@ pub struct PeerState {
 }
 
 pub struct FetchArgs {
-    pub predicate: ankql::ast::Predicate,
+    pub query: ankql::ast::Query,
     pub cached: bool,
 }
 
 impl TryInto<FetchArgs> for &str {
+    type Error = ankql::error::ParseError;
+    fn try_into(self) -> Result<FetchArgs, Self::Error> { Ok(FetchArgs { query: ankql::parser::parse_query(self)?, cached: false }) }
+}
+
+impl<P: AsRef<[ankql::ast::Literal]>> TryInto<FetchArgs> for (&str, P) {
     type Error = ankql::error::ParseError;
     fn try_into(self) -> Result<FetchArgs, Self::Error> {
-        Ok(FetchArgs { predicate: ankql::parser::parse_selection(self)?, cached: false })
+        Ok(FetchArgs { query: ankql::parser::parse_query_with_params(self.0, self.1.as_ref())?, cached: false })
     }
 }
 
 impl Into<FetchArgs> for ankql::ast::Predicate {
-    fn into(self) -> FetchArgs { FetchArgs { predicate: self, cached: false } }
+    fn into(self) -> FetchArgs { FetchArgs { query: self.into(), cached: false } }
+}
+
+impl From<ankql::ast::Query> for FetchArgs {
+    fn from(query: ankql::ast::Query) -> Self { FetchArgs { query, cached: false } }
 }
 
 impl From<ankql::error::ParseError> for RetrievalError {
@@ -187,17 +197,36 @@ impl Node {
                 // With moderate potential for duplication, while not creating message loops
                 // Doing so would be a secondary/tertiary/etc hop for this message
                 match self.commit_events_local(&events).await {
-                    Ok(_) => Ok(proto::NodeResponseBody::CommitComplete),
+                    Ok(_) => {
+                        // A durable peer retains whatever it sends us, so we needn't keep those events once they're checkpointed
+                        if self.durable_peers.contains(&request.from) {
+                            self.acknowledge_events(&request.from, &events).await?;
+                        }
+                        Ok(proto::NodeResponseBody::CommitComplete)
+                    }
                     Err(e) => Ok(proto::NodeResponseBody::Error(e.to_string())),
                 }
             }
-            proto::NodeRequestBody::Fetch { collection, predicate } => {
-                let states: Vec<_> = self.storage_engine.fetch_states(collection, &predicate).await?.into_iter().collect();
+            proto::NodeRequestBody::Fetch { collection, query } => {
+                let states: Vec<_> = self.storage_engine.fetch_states(collection, &query).await?.into_iter().collect();
                 Ok(proto::NodeResponseBody::Fetch(states))
             }
+            proto::NodeRequestBody::FetchPage { collection, query, after, limit } => {
+                let (states, next) = self.fetch_page_local(&collection, &query, after.as_ref(), limit).await?;
+                Ok(proto::NodeResponseBody::FetchPage { states, next })
+            }
             proto::NodeRequestBody::Subscribe { collection, predicate } => {
                 self.handle_subscribe_request(request.from, collection, predicate).await
             }
+            proto::NodeRequestBody::Aggregate { collection, aggregation } => {
+                Ok(proto::NodeResponseBody::Aggregate(self.storage_engine.aggregate(collection, &aggregation).await?))
+            }
+            proto::NodeRequestBody::FetchEvents { collection, entity_id, since } => {
+                let history = self.collection(&collection).await.get_events(entity_id).await?;
+                let lineage: BTreeMap<proto::ID, &proto::Clock> = history.iter().map(|e| (e.id, &e.parent)).collect();
+                let known = since.ancestry(|id: &proto::ID| lineage.get(id).copied());
+                Ok(proto::NodeResponseBody::Events(history.iter().filter(|e| !known.contains(&e.id)).cloned().collect()))
+            }
             proto::NodeRequestBody::Unsubscribe { subscription_id } => {
                 // Remove and drop the subscription handle
                 if let Some(mut peer_state) = self.peer_connections.get_mut(&request.from) {
@@ -215,7 +244,7 @@ impl Node {
         predicate: ankql::ast::Predicate,
     ) -> anyhow::Result<proto::NodeResponseBody> {
         // First fetch initial state
-        let states = self.storage_engine.fetch_states(collection_id.clone(), &predicate).await?;
+        let states = self.storage_engine.fetch_states(collection_id.clone(), &predicate.clone().into()).await?;
 
         // Set up subscription that forwards changes to the peer
         let node = self.clone();
@@ -298,12 +327,20 @@ impl Node {
         for event in events {
             // Apply Events to the Node's registered Entities first.
             let entity = self.fetch_entity(event.entity_id, &event.collection).await?;
+            let collection = self.collection(&event.collection).await;
 
-            entity.apply_event(event)?;
+            // Events which don't build directly on our head are merged using the entity's recorded history
+            let head = entity.head();
+            let history =
+                if event.parent == head || head.contains(&event.id) { Vec::new() } else { collection.get_events(event.entity_id).await? };
+            entity.apply_event(event, &history)?;
+
+            // Retain the event in the log before we persist the resulting state
+            collection.add_event(event).await?;
 
             let state = entity.to_state()?;
             // Push the state buffers to storage.
-            let changed = self.collection(&event.collection).await.set_state(event.entity_id, &state).await?;
+            let changed = collection.set_state(event.entity_id, &state).await?;
 
             if changed {
                 changes.push(EntityChange { entity: entity.clone(), events: vec![event.clone()] });
@@ -321,21 +358,42 @@ impl Node {
         // Then propagate to all peers
         let peer_ids: Vec<_> = self.peer_connections.iter().map(|i| i.key().clone()).collect();
 
-        futures::future::join_all(peer_ids.iter().map(|peer_id| {
+        let confirmations = futures::future::join_all(peer_ids.iter().map(|peer_id| {
             let events = events.clone();
             async move {
                 match self.request(peer_id.clone(), proto::NodeRequestBody::CommitEvents(events)).await {
                     Ok(proto::NodeResponseBody::CommitComplete) => {
-                        info!("Peer {} confirmed commit", peer_id)
+                        info!("Peer {} confirmed commit", peer_id);
+                        return true;
                     }
                     Ok(proto::NodeResponseBody::Error(e)) => warn!("Peer {} error: {}", peer_id, e),
                     Ok(_) => warn!("Peer {} unexpected response type", peer_id),
                     Err(_) => warn!("Peer {} internal channel closed", peer_id),
                 }
+                false
             }
         }))
         .await;
 
+        // Events have to be retained until the durable peers confirm them, so we record which ones did
+        for (peer_id, confirmed) in peer_ids.iter().zip(confirmations) {
+            if confirmed && self.durable_peers.contains(peer_id) {
+                self.acknowledge_events(peer_id, events).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Durably record that a durable peer has acknowledged the events, so they may be compacted as far as that peer is concerned
+    async fn acknowledge_events(&self, peer_id: &proto::NodeId, events: &[proto::Event]) -> anyhow::Result<()> {
+        let mut event_ids: BTreeMap<&CollectionId, Vec<proto::ID>> = BTreeMap::new();
+        for event in events {
+            event_ids.entry(&event.collection).or_default().push(event.id);
+        }
+        for (collection_id, event_ids) in event_ids {
+            self.collection(collection_id).await.acknowledge_events(peer_id, &event_ids).await?;
+        }
         Ok(())
     }
 
@@ -369,7 +427,12 @@ impl Node {
         match entities.entry((id, collection_id.clone())) {
             Entry::Occupied(mut entry) => {
                 if let Some(entity) = entry.get().upgrade() {
-                    entity.apply_state(state)?;
+                    // The resident entity is normally at least as new as what's in storage, in which case there is nothing to merge.
+                    // Otherwise the heads are compared through our recorded events, so that a newer stored state isn't taken as concurrent
+                    if entity.head() != state.head {
+                        let history = self.collection(collection_id).await.get_events(id).await?;
+                        entity.merge_state(state, &history)?;
+                    }
                     Ok(entity)
                 } else {
                     let entity = Arc::new(Entity::from_state(id, collection_id.clone(), state)?);
@@ -449,21 +512,18 @@ impl Node {
     async fn fetch_from_peer(
         self: &Arc<Self>,
         collection_id: &CollectionId,
-        predicate: &ankql::ast::Predicate,
+        query: &ankql::ast::Query,
     ) -> anyhow::Result<(), RetrievalError> {
         let peer_id = self.get_durable_peer_random().ok_or(RetrievalError::NoDurablePeers)?;
 
         match self
-            .request(peer_id.clone(), proto::NodeRequestBody::Fetch { collection: collection_id.clone(), predicate: predicate.clone() })
+            .request(peer_id.clone(), proto::NodeRequestBody::Fetch { collection: collection_id.clone(), query: query.clone() })
             .await
             .map_err(|e| RetrievalError::Other(format!("{:?}", e)))?
         {
             proto::NodeResponseBody::Fetch(states) => {
-                let raw_bucket = self.collection(collection_id).await;
-                // do we have the ability to merge states?
-                // because that's what we have to do I think
                 for (id, state) in states {
-                    raw_bucket.0.set_state(id, &state).await.map_err(|e| RetrievalError::Other(format!("{:?}", e)))?;
+                    self.merge_remote_state(&peer_id, collection_id, id, &state).await?;
                 }
                 Ok(())
             }
@@ -478,6 +538,177 @@ impl Node {
         }
     }
 
+    /// Fetch one page of entities from the first available durable peer, returning their IDs in page order and the next cursor.
+    async fn fetch_page_from_peer(
+        self: &Arc<Self>,
+        collection_id: &CollectionId,
+        query: &ankql::ast::Query,
+        after: Option<&proto::Cursor>,
+        limit: u64,
+    ) -> Result<(Vec<proto::ID>, Option<proto::Cursor>), RetrievalError> {
+        let peer_id = self.get_durable_peer_random().ok_or(RetrievalError::NoDurablePeers)?;
+
+        match self
+            .request(
+                peer_id.clone(),
+                proto::NodeRequestBody::FetchPage { collection: collection_id.clone(), query: query.clone(), after: after.cloned(), limit },
+            )
+            .await
+            .map_err(|e| RetrievalError::Other(format!("{:?}", e)))?
+        {
+            proto::NodeResponseBody::FetchPage { states, next } => {
+                let mut ids = Vec::new();
+                for (id, state) in states {
+                    self.merge_remote_state(&peer_id, collection_id, id, &state).await?;
+                    ids.push(id);
+                }
+                Ok((ids, next))
+            }
+            proto::NodeResponseBody::Error(e) => {
+                debug!("Error from peer fetch page: {}", e);
+                Err(RetrievalError::Other(format!("{:?}", e)))
+            }
+            _ => {
+                debug!("Unexpected response type from peer fetch page");
+                Err(RetrievalError::Other("Unexpected response type".to_string()))
+            }
+        }
+    }
+
+    /// Fetch one page of states from local storage, along with the cursor for the following page if there is one.
+    async fn fetch_page_local(
+        &self,
+        collection_id: &CollectionId,
+        query: &ankql::ast::Query,
+        after: Option<&proto::Cursor>,
+        limit: u64,
+    ) -> Result<(Vec<(proto::ID, proto::State)>, Option<proto::Cursor>), RetrievalError> {
+        if limit == 0 {
+            return Err(RetrievalError::Other("Page limit must be at least one".to_string()));
+        }
+        if after.is_some_and(|after| after.key.len() != query.order_by.len()) {
+            return Err(RetrievalError::Other("Cursor does not match the query's ORDER BY clause".to_string()));
+        }
+
+        // Ask for one more state than the page holds, to find out whether there is a following page
+        let mut states = self.storage_engine.fetch_page(collection_id.clone(), query, after, limit.saturating_add(1)).await?;
+        if states.len() as u64 <= limit {
+            return Ok((states, None));
+        }
+        states.truncate(limit as usize);
+
+        let (id, state) = states.last().expect("page is not empty");
+        let entity = Entity::from_state(*id, collection_id.clone(), state)?;
+        let next = proto::Cursor::new(ankql::selection::order::sort_key(&entity, &query.order_by), *id);
+        Ok((states, Some(next)))
+    }
+
+    /// Merge an entity state received from a peer with our local state, and persist the result.
+    /// Local edits which the peer hasn't seen yet are retained.
+    async fn merge_remote_state(
+        &self,
+        peer_id: &proto::NodeId,
+        collection_id: &CollectionId,
+        id: proto::ID,
+        state: &proto::State,
+    ) -> Result<(), RetrievalError> {
+        let entity = self.fetch_entity(id, collection_id).await?;
+        let collection = self.collection(collection_id).await;
+        let mut history = collection.get_events(id).await?;
+
+        // The peer's head may descend from ours through events we've never received. Without those the heads would look
+        // concurrent, so we record the peer's events since our head before comparing them
+        let head = entity.head();
+        if !head.is_empty() && state.head != head && !state.head.iter().all(|event_id| history.iter().any(|e| e.id == *event_id)) {
+            let events = self.fetch_events_from_peer(peer_id, collection_id, id, &head).await?;
+            self.acknowledge_events(peer_id, &events).await?;
+            for event in events {
+                if collection.add_event(&event).await? {
+                    history.push(event);
+                }
+            }
+        }
+
+        if entity.merge_state(state, &history)? {
+            collection.set_state(id, &entity.to_state()?).await?;
+        }
+        Ok(())
+    }
+
+    /// Fetch an entity's events which aren't in the ancestry of `since` from a peer
+    async fn fetch_events_from_peer(
+        &self,
+        peer_id: &proto::NodeId,
+        collection_id: &CollectionId,
+        id: proto::ID,
+        since: &proto::Clock,
+    ) -> Result<Vec<proto::Event>, RetrievalError> {
+        match self
+            .request(
+                peer_id.clone(),
+                proto::NodeRequestBody::FetchEvents { collection: collection_id.clone(), entity_id: id, since: since.clone() },
+            )
+            .await
+            .map_err(|e| RetrievalError::Other(format!("{:?}", e)))?
+        {
+            proto::NodeResponseBody::Events(events) => Ok(events),
+            proto::NodeResponseBody::Error(e) => {
+                debug!("Error from peer fetch events: {}", e);
+                Err(RetrievalError::Other(format!("{:?}", e)))
+            }
+            _ => {
+                debug!("Unexpected response type from peer fetch events");
+                Err(RetrievalError::Other("Unexpected response type".to_string()))
+            }
+        }
+    }
+
+    /// Retrieve an entity as it was at the given clock, by replaying its recorded events.
+    /// The result is detached from the node, and will not receive any further updates.
+    pub async fn get_at<R: View>(&self, id: proto::ID, clock: &proto::Clock) -> Result<R, RetrievalError> {
+        let collection_id = R::collection();
+        let collection = self.collection(&collection_id).await;
+        let checkpoint = collection.get_checkpoint(id).await?;
+        let history = collection.get_events(id).await?;
+        let entity = Entity::from_events(id, collection_id, checkpoint.as_ref(), &history, clock)?;
+        Ok(R::from_entity(Arc::new(entity)))
+    }
+
+    /// Store a checkpoint of the entity's current state, returning the clock it was taken at
+    pub async fn checkpoint(&self, collection_id: &CollectionId, id: proto::ID) -> Result<proto::Clock, RetrievalError> {
+        let entity = self.fetch_entity(id, collection_id).await?;
+        let state = entity.to_state()?;
+        self.collection(collection_id).await.checkpoint(id, &state).await?;
+        Ok(state.head)
+    }
+
+    /// Compact the events covered by the entity's newest checkpoint, returning how many were removed.
+    /// Nothing is compacted while any of those events is unacknowledged by one of our durable peers. A node which isn't
+    /// durable itself also needs at least one durable peer to have acknowledged each of them, even while it's disconnected.
+    pub async fn compact(&self, collection_id: &CollectionId, id: proto::ID) -> Result<usize, RetrievalError> {
+        let collection = self.collection(collection_id).await;
+        let Some(checkpoint) = collection.get_checkpoint(id).await? else {
+            return Ok(0);
+        };
+
+        let history = collection.get_events(id).await?;
+        let lineage: BTreeMap<proto::ID, &proto::Clock> = history.iter().map(|e| (e.id, &e.parent)).collect();
+        let covered: Vec<proto::ID> =
+            checkpoint.head.ancestry(|id: &proto::ID| lineage.get(id).copied()).into_iter().filter(|id| lineage.contains_key(id)).collect();
+
+        let acknowledgements = collection.get_acknowledgements(&covered).await?;
+        let durable_peers = self.get_durable_peers();
+        for event_id in &covered {
+            let acknowledged_by = acknowledgements.get(event_id);
+            let retained = self.durable || acknowledged_by.is_some_and(|peers| !peers.is_empty());
+            if !retained || !durable_peers.iter().all(|peer_id| acknowledged_by.is_some_and(|peers| peers.contains(peer_id))) {
+                return Ok(0);
+            }
+        }
+
+        Ok(collection.compact_before(id, &checkpoint.head).await?)
+    }
+
     pub async fn get<R: View>(self: &Arc<Self>, id: proto::ID) -> Result<R, RetrievalError> {
         let entity = self.fetch_entity(id, &R::collection()).await?;
         Ok(R::from_entity(entity))
@@ -489,14 +720,14 @@ impl Node {
     ) -> Result<ResultSet<R>, RetrievalError> {
         let args: FetchArgs = args.try_into().map_err(|e| e.into())?;
 
-        let predicate = args.predicate;
+        let query = args.query;
 
         use crate::model::Model;
         let collection_id = R::Model::collection();
 
         if !self.durable {
             // Fetch from peers and commit first response
-            match self.fetch_from_peer(&collection_id, &predicate).await {
+            match self.fetch_from_peer(&collection_id, &query).await {
                 Ok(_) => (),
                 Err(RetrievalError::NoDurablePeers) if args.cached => (),
                 Err(e) => {
@@ -506,16 +737,92 @@ impl Node {
         }
 
         // Fetch raw states from storage
-        let states = self.storage_engine.fetch_states(collection_id.clone(), &predicate).await?;
+        let states = self.storage_engine.fetch_states(collection_id.clone(), &query).await?;
 
         // Convert states to entities
         let mut entities = Vec::new();
         for (id, state) in states {
-            let entity = self.assert_entity(&collection_id, id, &state).await?;
-            entities.push(R::from_entity(entity));
+            entities.push(self.assert_entity(&collection_id, id, &state).await?);
+        }
+
+        Ok(ResultSet::matching(entities, &query.predicate).map(R::from_entity))
+    }
+
+    /// Fetch one page of entities matching a query, in the order of its ORDER BY clause with ties broken by ID.
+    /// The page starts after the cursor returned with the previous page, or at the beginning if there is none, and holds at most
+    /// `limit` entities. The query's own LIMIT and OFFSET are ignored. Because the cursor records the position of the last
+    /// entity rather than a count, entities inserted or deleted between pages don't cause any others to be skipped or repeated.
+    pub async fn fetch_page<R: View>(
+        self: &Arc<Self>,
+        args: impl TryInto<FetchArgs, Error = impl Into<RetrievalError>>,
+        after: Option<proto::Cursor>,
+        limit: u64,
+    ) -> Result<Page<R>, RetrievalError> {
+        let args: FetchArgs = args.try_into().map_err(|e| e.into())?;
+
+        let query = args.query;
+
+        use crate::model::Model;
+        let collection_id = R::Model::collection();
+
+        if !self.durable {
+            // Only a durable peer knows the whole collection, so it decides what's on the page
+            match self.fetch_page_from_peer(&collection_id, &query, after.as_ref(), limit).await {
+                Ok((ids, next)) => {
+                    let mut entities = Vec::new();
+                    for id in ids {
+                        entities.push(self.fetch_entity(id, &collection_id).await?);
+                    }
+                    return Ok(Page { resultset: ResultSet::matching(entities, &query.predicate).map(R::from_entity), next });
+                }
+                Err(RetrievalError::NoDurablePeers) if args.cached => (),
+                Err(e) => {
+                    return Err(e);
+                }
+            }
+        }
+
+        let (states, next) = self.fetch_page_local(&collection_id, &query, after.as_ref(), limit).await?;
+
+        let mut entities = Vec::new();
+        for (id, state) in states {
+            entities.push(self.assert_entity(&collection_id, id, &state).await?);
         }
 
-        Ok(ResultSet { items: entities })
+        Ok(Page { resultset: ResultSet::matching(entities, &query.predicate).map(R::from_entity), next })
+    }
+
+    /// Compute aggregates over the entities matching a query such as `SELECT artist, COUNT(*) WHERE year > 2000 GROUP BY artist`,
+    /// returning a row for each group. Only durable nodes have the whole collection to aggregate, so others ask a durable peer.
+    pub async fn aggregate<R: View>(
+        self: &Arc<Self>,
+        aggregation: impl TryInto<ankql::ast::Aggregation, Error = impl Into<RetrievalError>>,
+    ) -> Result<Vec<AggregateRow>, RetrievalError> {
+        let aggregation = aggregation.try_into().map_err(|e| e.into())?;
+
+        use crate::model::Model;
+        let collection_id = R::Model::collection();
+
+        if self.durable {
+            return self.storage_engine.aggregate(collection_id, &aggregation).await;
+        }
+
+        let peer_id = self.get_durable_peer_random().ok_or(RetrievalError::NoDurablePeers)?;
+        match self
+            .request(peer_id, proto::NodeRequestBody::Aggregate { collection: collection_id, aggregation })
+            .await
+            .map_err(|e| RetrievalError::Other(format!("{:?}", e)))?
+        {
+            proto::NodeResponseBody::Aggregate(rows) => Ok(rows),
+            proto::NodeResponseBody::Error(e) => {
+                debug!("Error from peer aggregate: {}", e);
+                Err(RetrievalError::Other(format!("{:?}", e)))
+            }
+            _ => {
+                debug!("Unexpected response type from peer aggregate");
+                Err(RetrievalError::Other("Unexpected response type".to_string()))
+            }
+        }
     }
 
     /// Subscribe to changes in entities matching a predicate
@@ -536,14 +843,18 @@ impl Node {
         // If we have a durable node, send a subscription request to it
         if let Some(peer_id) = durable_peer_id {
             match self
-                .request(peer_id, proto::NodeRequestBody::Subscribe { collection: collection_id.clone(), predicate: predicate.clone() })
+                .request(
+                    peer_id.clone(),
+                    proto::NodeRequestBody::Subscribe { collection: collection_id.clone(), predicate: predicate.clone() },
+                )
                 .await?
             {
                 proto::NodeResponseBody::Subscribe { initial, subscription_id: _ } => {
-                    // Apply initial states to our storage
-                    let raw_bucket = self.collection(&collection_id).await;
+                    // Merge initial states into our storage
                     for (id, state) in initial {
-                        raw_bucket.set_state(id, &state).await.map_err(|e| anyhow!("Failed to set entity: {:?}", e))?;
+                        self.merge_remote_state(&peer_id, &collection_id, id, &state)
+                            .await
+                            .map_err(|e| anyhow!("Failed to merge entity: {:?}", e))?;
                     }
                 }
                 proto::NodeResponseBody::Error(e) => {
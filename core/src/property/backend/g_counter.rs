@@ -0,0 +1,174 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+    sync::{Arc, RwLock},
+};
+
+use ankurah_proto::{ClockOrdering, ID};
+
+use crate::{
+    property::{
+        backend::{
+            pn_counter::{ReplicaCounts, Reservations},
+            Operation, PropertyBackend,
+        },
+        PropertyName,
+    },
+    value::Value,
+};
+
+/// A grow-only counter per property. Replicas, pending amounts and reserved counts work the same way as in `PNBackend`
+#[derive(Debug)]
+pub struct GBackend {
+    replica: RwLock<Option<ID>>,
+    values: Arc<RwLock<BTreeMap<PropertyName, ReplicaCounts>>>,
+    /// Amounts added locally since the last call to `to_operations`
+    pending: Arc<RwLock<BTreeMap<PropertyName, u64>>>,
+    /// The counts reserved by this node's commits, shared by the backend and its forks
+    reserved: Arc<RwLock<Reservations<ReplicaCounts>>>,
+    /// The events which this backend collected counts for
+    collected_events: RwLock<Vec<ID>>,
+}
+
+impl Default for GBackend {
+    fn default() -> Self { Self::new() }
+}
+
+impl GBackend {
+    pub fn new() -> GBackend { Self::with_values(BTreeMap::default(), Arc::default()) }
+
+    fn with_values(values: BTreeMap<PropertyName, ReplicaCounts>, reserved: Arc<RwLock<Reservations<ReplicaCounts>>>) -> Self {
+        Self {
+            replica: RwLock::new(None),
+            values: Arc::new(RwLock::new(values)),
+            pending: Arc::new(RwLock::new(BTreeMap::new())),
+            reserved,
+            collected_events: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub fn get(&self, property_name: PropertyName) -> u64 {
+        let values = self.values.read().unwrap();
+        let pending = self.pending.read().unwrap();
+        values.get(&property_name).map(|counts| counts.total()).unwrap_or(0) + pending.get(&property_name).copied().unwrap_or(0)
+    }
+
+    pub fn add(&self, property_name: PropertyName, amount: u64) {
+        *self.pending.write().unwrap().entry(property_name).or_default() += amount;
+    }
+}
+
+impl PropertyBackend for GBackend {
+    fn as_arc_dyn_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync + 'static> { self as Arc<dyn Any + Send + Sync + 'static> }
+
+    fn as_debug(&self) -> &dyn Debug { self as &dyn Debug }
+
+    fn fork(&self) -> Box<dyn PropertyBackend> {
+        let values = self.values.read().unwrap();
+        let forked = Self::with_values(values.clone(), self.reserved.clone());
+        *forked.replica.write().unwrap() = *self.replica.read().unwrap();
+        Box::new(forked)
+    }
+
+    fn set_replica(&self, replica: ID) { *self.replica.write().unwrap() = Some(replica); }
+
+    fn properties(&self) -> Vec<String> {
+        let values = self.values.read().unwrap();
+        let pending = self.pending.read().unwrap();
+        values.keys().chain(pending.keys()).cloned().collect::<BTreeSet<String>>().into_iter().collect()
+    }
+
+    fn materialized(&self) -> BTreeMap<PropertyName, Value> {
+        let properties = self.properties();
+        properties.into_iter().map(|property| (property.clone(), Value::Integer(self.get(property) as i64))).collect()
+    }
+
+    fn property_backend_name() -> String { "g".to_owned() }
+
+    fn to_state_buffer(&self) -> anyhow::Result<Vec<u8>> {
+        let values = self.values.read().unwrap();
+        Ok(bincode::serialize(&*values)?)
+    }
+
+    fn from_state_buffer(state_buffer: &Vec<u8>) -> std::result::Result<Self, crate::error::RetrievalError> {
+        let values = bincode::deserialize::<BTreeMap<PropertyName, ReplicaCounts>>(state_buffer)?;
+        Ok(Self::with_values(values, Arc::default()))
+    }
+
+    fn to_operations(&self, event_id: ID) -> anyhow::Result<Vec<Operation>> {
+        let mut pending = self.pending.write().unwrap();
+        if pending.is_empty() {
+            return Ok(Vec::new());
+        }
+        let replica = self.replica.read().unwrap().ok_or_else(|| anyhow::anyhow!("No replica to attribute the counts to"))?;
+
+        // Cumulative counts, reserved the same way as for PN counters
+        let counts = {
+            let mut reserved = self.reserved.write().unwrap();
+            let values = self.values.read().unwrap();
+            let reservations = std::mem::take(&mut *pending)
+                .into_iter()
+                .map(|(property, amount)| {
+                    let mut reservation = reserved.highest(&property, ReplicaCounts::merge);
+                    if let Some(counts) = values.get(&property) {
+                        reservation.merge_count(replica, counts.get(&replica));
+                    }
+                    reservation.add(replica, amount);
+                    (property, reservation)
+                })
+                .collect::<BTreeMap<_, _>>();
+            let counts = reservations
+                .iter()
+                .map(|(property, reservation)| (property.clone(), (replica, reservation.get(&replica))))
+                .collect::<BTreeMap<_, _>>();
+            reserved.collect(event_id, reservations);
+            counts
+        };
+        self.collected_events.write().unwrap().push(event_id);
+
+        let mut values = self.values.write().unwrap();
+        for (property, (replica, count)) in &counts {
+            values.entry(property.clone()).or_default().merge_count(*replica, *count);
+        }
+
+        Ok(vec![Operation { diff: bincode::serialize(&counts)? }])
+    }
+
+    fn apply_operations(&self, event_id: ID, operations: &Vec<Operation>) -> anyhow::Result<()> {
+        let mut reserved = self.reserved.write().unwrap();
+        let applied = reserved.apply(event_id);
+        let mut values = self.values.write().unwrap();
+        for operation in operations {
+            let counts = bincode::deserialize::<BTreeMap<PropertyName, (ID, u64)>>(&operation.diff)?;
+            for (property, (replica, count)) in counts {
+                values.entry(property.clone()).or_default().merge_count(replica, count);
+                applied.entry(property).or_default().merge_count(replica, count);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn merge_state(&self, state_buffer: &Vec<u8>, _ordering: ClockOrdering) -> anyhow::Result<()> {
+        let incoming = bincode::deserialize::<BTreeMap<PropertyName, ReplicaCounts>>(state_buffer)?;
+        let mut values = self.values.write().unwrap();
+        for (property, counts) in incoming {
+            values.entry(property).or_default().merge(&counts);
+        }
+        Ok(())
+    }
+
+    fn property_value(&self, property_name: &str) -> Option<Value> {
+        let known = self.values.read().unwrap().contains_key(property_name) || self.pending.read().unwrap().contains_key(property_name);
+        known.then(|| Value::Integer(self.get(property_name.to_owned()) as i64))
+    }
+}
+
+impl Drop for GBackend {
+    fn drop(&mut self) {
+        if let (Ok(mut reserved), Ok(collected)) = (self.reserved.write(), self.collected_events.get_mut()) {
+            reserved.release(collected);
+        }
+    }
+}
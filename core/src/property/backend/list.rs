@@ -0,0 +1,325 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+    sync::{Arc, RwLock},
+};
+
+use ankurah_proto::{ClockOrdering, ID};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    property::{
+        backend::{Operation, PropertyBackend},
+        PropertyName,
+    },
+    value::Value,
+};
+
+/// A fractional index. Positions compare lexicographically, and there is always room for another position between two of them
+pub type Position = Vec<u32>;
+
+/// Ordered lists per property, using fractional indexing.
+/// Every item has a stable ID and a position, and the list is the items sorted by position.
+/// Moving an item only changes its position, so concurrent moves can't duplicate it. Positions are last-writer-wins,
+/// stamped with the ID of the event which set them, in the same way as `LWWBackend`. Removals are permanent.
+#[derive(Debug)]
+pub struct ListBackend {
+    values: Arc<RwLock<BTreeMap<PropertyName, ListValue>>>,
+    /// Items changed since the last call to `to_operations`
+    pending: Arc<RwLock<BTreeMap<(PropertyName, ID), PendingChange>>>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ListValue {
+    pub items: BTreeMap<ID, ListItem>,
+    pub removed: BTreeSet<ID>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListItem {
+    pub value: Vec<u8>,
+    pub position: Position,
+    /// The event which set the position, or None if it was set locally and hasn't been committed yet
+    pub stamp: Option<ID>,
+}
+
+impl ListValue {
+    /// The IDs of the items in list order. Items at the same position are ordered by ID
+    pub fn ordered(&self) -> Vec<ID> {
+        let mut ids = self.items.iter().map(|(id, item)| (&item.position, *id)).collect::<Vec<_>>();
+        ids.sort();
+        ids.into_iter().map(|(_, id)| id).collect()
+    }
+
+    pub fn values(&self) -> Vec<Vec<u8>> { self.ordered().iter().map(|id| self.items[id].value.clone()).collect() }
+
+    /// A position for an item placed at `index` among `ordered`
+    fn position_at(&self, ordered: &[ID], index: usize) -> Position {
+        let before = index.checked_sub(1).map(|i| &self.items[&ordered[i]].position);
+        let after = ordered.get(index).map(|id| &self.items[id].position);
+        position_between(before, after)
+    }
+
+    /// Record an item or a new position for it which was set by `event_id`, unless it was removed or we have a newer position
+    fn apply_item(&mut self, item_id: ID, value: Option<Vec<u8>>, position: Position, event_id: ID) {
+        if self.removed.contains(&item_id) {
+            return;
+        }
+        match self.items.get_mut(&item_id) {
+            Some(item) => {
+                if item.stamp.is_some_and(|stamp| stamp < event_id) {
+                    item.position = position;
+                    item.stamp = Some(event_id);
+                }
+            }
+            None => {
+                // A move can only arrive without its insert when merging partial states, in which case the insert follows
+                if let Some(value) = value {
+                    self.items.insert(item_id, ListItem { value, position, stamp: Some(event_id) });
+                }
+            }
+        }
+    }
+
+    fn remove_item(&mut self, item_id: ID) {
+        self.items.remove(&item_id);
+        self.removed.insert(item_id);
+    }
+}
+
+/// Generate a position which sorts strictly between `before` and `after`.
+/// The new digit is picked at random from the available range, so that concurrent inserts at the same place get distinct positions.
+pub fn position_between(before: Option<&Position>, after: Option<&Position>) -> Position {
+    const END: u64 = u32::MAX as u64 + 1;
+    let mut position = Position::new();
+    // Once we've diverged from `after` by taking a lower digit, any further digits are free of its bound
+    let mut bounded = after.is_some();
+    for i in 0.. {
+        let low = before.and_then(|before| before.get(i)).copied().unwrap_or(0) as u64;
+        let high = match (bounded, after.and_then(|after| after.get(i))) {
+            (true, Some(&digit)) if digit as u64 >= low => digit as u64,
+            // Only reachable for a pair which isn't in order, in which case we settle for a position after `before`
+            _ => END,
+        };
+        if high > low + 1 {
+            position.push(rand::thread_rng().gen_range(low + 1..high) as u32);
+            return position;
+        }
+        if high == low + 1 {
+            bounded = false;
+        }
+        position.push(low as u32);
+    }
+    unreachable!()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PendingChange {
+    Insert,
+    Move,
+    Remove,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum ListOperation {
+    Insert { property: PropertyName, item: ID, value: Vec<u8>, position: Position },
+    Move { property: PropertyName, item: ID, position: Position },
+    Remove { property: PropertyName, item: ID },
+}
+
+impl Default for ListBackend {
+    fn default() -> Self { Self::new() }
+}
+
+impl ListBackend {
+    pub fn new() -> ListBackend { Self::with_values(BTreeMap::default()) }
+
+    fn with_values(values: BTreeMap<PropertyName, ListValue>) -> Self {
+        Self { values: Arc::new(RwLock::new(values)), pending: Arc::new(RwLock::new(BTreeMap::new())) }
+    }
+
+    /// The values of the list in order
+    pub fn get(&self, property_name: &str) -> Vec<Vec<u8>> {
+        let values = self.values.read().unwrap();
+        values.get(property_name).map(|value| value.values()).unwrap_or_default()
+    }
+
+    /// The item IDs of the list in order
+    pub fn item_ids(&self, property_name: &str) -> Vec<ID> {
+        let values = self.values.read().unwrap();
+        values.get(property_name).map(|value| value.ordered()).unwrap_or_default()
+    }
+
+    pub fn len(&self, property_name: &str) -> usize {
+        let values = self.values.read().unwrap();
+        values.get(property_name).map(|value| value.items.len()).unwrap_or(0)
+    }
+
+    /// Insert a value at `index`, returning the ID of the new item. Panics if `index > len`
+    pub fn insert_at(&self, property_name: PropertyName, index: usize, value: Vec<u8>) -> ID {
+        let mut values = self.values.write().unwrap();
+        let list = values.entry(property_name.clone()).or_default();
+        let ordered = list.ordered();
+        assert!(index <= ordered.len(), "insertion index (is {index}) should be <= len (is {})", ordered.len());
+
+        let item_id = ID::new();
+        let position = list.position_at(&ordered, index);
+        list.items.insert(item_id, ListItem { value, position, stamp: None });
+        self.pending.write().unwrap().insert((property_name, item_id), PendingChange::Insert);
+        item_id
+    }
+
+    /// Move the item at `from` so that it ends up at index `to`. Panics if either index is out of bounds
+    pub fn move_item(&self, property_name: PropertyName, from: usize, to: usize) {
+        let mut values = self.values.write().unwrap();
+        let list = values.entry(property_name.clone()).or_default();
+        let mut ordered = list.ordered();
+        assert!(from < ordered.len() && to < ordered.len(), "move indices (are {from} and {to}) should be < len (is {})", ordered.len());
+        if from == to {
+            return;
+        }
+
+        let item_id = ordered.remove(from);
+        let position = list.position_at(&ordered, to);
+        let item = list.items.get_mut(&item_id).unwrap();
+        item.position = position;
+        item.stamp = None;
+
+        let mut pending = self.pending.write().unwrap();
+        // An item inserted in this same transaction is still sent as an insert, just at its new position
+        pending.entry((property_name, item_id)).or_insert(PendingChange::Move);
+    }
+
+    /// Remove the item at `index`, returning its value. Panics if `index >= len`
+    pub fn remove(&self, property_name: PropertyName, index: usize) -> Vec<u8> {
+        let mut values = self.values.write().unwrap();
+        let list = values.entry(property_name.clone()).or_default();
+        let ordered = list.ordered();
+        assert!(index < ordered.len(), "removal index (is {index}) should be < len (is {})", ordered.len());
+
+        let item_id = ordered[index];
+        let item = list.items.remove(&item_id).unwrap();
+        list.removed.insert(item_id);
+
+        let mut pending = self.pending.write().unwrap();
+        // Items which haven't been sent anywhere yet can simply be forgotten
+        if pending.remove(&(property_name.clone(), item_id)) != Some(PendingChange::Insert) {
+            pending.insert((property_name, item_id), PendingChange::Remove);
+        }
+        item.value
+    }
+}
+
+impl PropertyBackend for ListBackend {
+    fn as_arc_dyn_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync + 'static> { self as Arc<dyn Any + Send + Sync + 'static> }
+
+    fn as_debug(&self) -> &dyn Debug { self as &dyn Debug }
+
+    fn fork(&self) -> Box<dyn PropertyBackend> {
+        let values = self.values.read().unwrap();
+        Box::new(Self::with_values(values.clone()))
+    }
+
+    fn properties(&self) -> Vec<String> {
+        let values = self.values.read().unwrap();
+        values.keys().cloned().collect::<Vec<String>>()
+    }
+
+    fn materialized(&self) -> BTreeMap<PropertyName, Value> {
+        // Lists aren't scalars. Each item is bincode encoded, so the length followed by the items in order is the bincode
+        // encoding of a `Vec` of them, and decodes like any other non-scalar value
+        let values = self.values.read().unwrap();
+        values
+            .iter()
+            .filter_map(|(property, list)| {
+                let items = list.values();
+                let mut encoded = bincode::serialize(&(items.len() as u64)).ok()?;
+                encoded.extend(items.into_iter().flatten());
+                Some((property.clone(), Value::Bytes(encoded)))
+            })
+            .collect()
+    }
+
+    fn property_backend_name() -> String { "list".to_owned() }
+
+    fn property_value(&self, _property_name: &str) -> Option<Value> {
+        // Lists aren't scalar values
+        None
+    }
+
+    fn to_state_buffer(&self) -> anyhow::Result<Vec<u8>> {
+        let values = self.values.read().unwrap();
+        Ok(bincode::serialize(&*values)?)
+    }
+
+    fn from_state_buffer(state_buffer: &Vec<u8>) -> std::result::Result<Self, crate::error::RetrievalError> {
+        let values = bincode::deserialize::<BTreeMap<PropertyName, ListValue>>(state_buffer)?;
+        Ok(Self::with_values(values))
+    }
+
+    fn to_operations(&self, event_id: ID) -> anyhow::Result<Vec<Operation>> {
+        let mut values = self.values.write().unwrap();
+        let pending = std::mem::take(&mut *self.pending.write().unwrap());
+
+        let mut operations = Vec::new();
+        for ((property, item_id), change) in pending {
+            let item = values.get_mut(&property).and_then(|list| list.items.get_mut(&item_id));
+            let operation = match (change, item) {
+                (PendingChange::Remove, _) => ListOperation::Remove { property, item: item_id },
+                (PendingChange::Insert, Some(item)) => {
+                    item.stamp = Some(event_id);
+                    ListOperation::Insert { property, item: item_id, value: item.value.clone(), position: item.position.clone() }
+                }
+                (PendingChange::Move, Some(item)) => {
+                    item.stamp = Some(event_id);
+                    ListOperation::Move { property, item: item_id, position: item.position.clone() }
+                }
+                // The item was removed by someone else in the meantime
+                (_, None) => continue,
+            };
+            operations.push(Operation { diff: bincode::serialize(&operation)? });
+        }
+
+        Ok(operations)
+    }
+
+    fn apply_operations(&self, event_id: ID, operations: &Vec<Operation>) -> anyhow::Result<()> {
+        let mut values = self.values.write().unwrap();
+        for operation in operations {
+            match bincode::deserialize::<ListOperation>(&operation.diff)? {
+                ListOperation::Insert { property, item, value, position } => {
+                    values.entry(property).or_default().apply_item(item, Some(value), position, event_id);
+                }
+                ListOperation::Move { property, item, position } => {
+                    values.entry(property).or_default().apply_item(item, None, position, event_id);
+                }
+                ListOperation::Remove { property, item } => {
+                    values.entry(property).or_default().remove_item(item);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn merge_state(&self, state_buffer: &Vec<u8>, _ordering: ClockOrdering) -> anyhow::Result<()> {
+        // Stamps make the merge commutative, so we don't need to care how the heads relate
+        let incoming = bincode::deserialize::<BTreeMap<PropertyName, ListValue>>(state_buffer)?;
+        let mut values = self.values.write().unwrap();
+        for (property, other) in incoming {
+            let list = values.entry(property).or_default();
+            for item_id in other.removed {
+                list.remove_item(item_id);
+            }
+            for (item_id, item) in other.items {
+                // The other replica's uncommitted changes can't have been sent to anyone else, so they aren't ours to adopt
+                if let Some(stamp) = item.stamp {
+                    list.apply_item(item_id, Some(item.value), item.position, stamp);
+                }
+            }
+        }
+        Ok(())
+    }
+}
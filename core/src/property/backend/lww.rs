@@ -1,19 +1,96 @@
 use std::{
     any::Any,
-    collections::{btree_map::Entry, BTreeMap},
+    collections::{BTreeMap, BTreeSet},
     fmt::Debug,
     sync::{Arc, RwLock},
 };
 
+use ankurah_proto::{ClockOrdering, ID};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
 use crate::{
-    property::{backend::PropertyBackend, PropertyName},
-    storage::Materialized,
+    property::{
+        backend::{Operation, PropertyBackend},
+        PropertyName,
+    },
+    value::Value,
 };
 
+/// Last-writer-wins register per property.
+/// Every write is stamped with the ID of its event and records the writes it observed, which it supersedes. So a write always
+/// wins over those in its causal history, and only concurrent writes are ordered by their stamps. Event IDs are ULIDs, so the concurrent write with the latest wall clock time wins, with ties broken by the random
+/// component.
 #[derive(Clone, Debug)]
 pub struct LWWBackend {
-    // TODO: store a timestamp/precursor id at time of setting value (or maybe when we commit?).
-    values: Arc<RwLock<BTreeMap<PropertyName, Vec<u8>>>>,
+    values: Arc<RwLock<BTreeMap<PropertyName, LWWWrites>>>,
+    /// The writes superseded by each property's uncommitted local value
+    pending: Arc<RwLock<BTreeMap<PropertyName, BTreeSet<ID>>>>,
+}
+
+/// The writes to a property which haven't been superseded
+#[derive(Clone, Debug, Default)]
+struct LWWWrites {
+    /// Committed writes by the event which wrote them. The last is the winner, unless there is an uncommitted value
+    entries: BTreeMap<ID, Value>,
+    /// A value which was set locally and hasn't been committed yet
+    uncommitted: Option<Value>,
+    /// Writes which have been superseded
+    superseded: BTreeSet<ID>,
+}
+
+impl LWWWrites {
+    fn current(&self) -> Option<&Value> { self.uncommitted.as_ref().or_else(|| self.entries.values().next_back()) }
+
+    fn apply_write(&mut self, event_id: ID, value: Value, supersedes: impl IntoIterator<Item = ID>) {
+        self.superseded.extend(supersedes);
+        if !self.superseded.contains(&event_id) {
+            self.entries.insert(event_id, value);
+        }
+        let superseded = &self.superseded;
+        self.entries.retain(|id, _| !superseded.contains(id));
+    }
+
+    /// Replace every value we hold with an uncommitted one, returning the writes it supersedes
+    fn overwrite(&mut self, value: Value) -> BTreeSet<ID> {
+        let observed = std::mem::take(&mut self.entries).into_keys().collect::<BTreeSet<_>>();
+        self.superseded.extend(observed.iter().cloned());
+        self.uncommitted = Some(value);
+        observed
+    }
+
+    /// Stamp the uncommitted value with the event it's being committed in, returning it
+    fn commit(&mut self, event_id: ID) -> Option<Value> {
+        let uncommitted = self.uncommitted.take()?;
+        self.entries.insert(event_id, uncommitted.clone());
+        Some(uncommitted)
+    }
+
+    /// Merge another replica's writes. Writes and supersessions only accumulate, so the union is correct however the heads relate.
+    /// The other replica's uncommitted value can't have been sent to anyone else, so it isn't ours to adopt
+    fn merge(&mut self, other: LWWWrites) {
+        self.superseded.extend(other.superseded);
+        for (event_id, entry) in other.entries {
+            self.apply_write(event_id, entry, []);
+        }
+    }
+}
+
+/// The winning value of a property. State buffers begin with these, so that those written before writes recorded what they
+/// observed still decode.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LWWValue {
+    pub value: Value,
+    /// The event which wrote this value, or None if it was set locally and hasn't been committed yet
+    pub event_id: Option<ID>,
+}
+
+/// The rest of a property's writes, which follow the winning values in the state buffer
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LWWCausality {
+    /// Concurrent writes which lost to the winning value
+    concurrent: BTreeMap<ID, Value>,
+    /// Writes which have been superseded
+    superseded: BTreeSet<ID>,
 }
 
 impl Default for LWWBackend {
@@ -21,24 +98,75 @@ impl Default for LWWBackend {
 }
 
 impl LWWBackend {
-    pub fn new() -> LWWBackend { Self { values: Arc::new(RwLock::new(BTreeMap::default())) } }
+    pub fn new() -> LWWBackend { Self::with_values(BTreeMap::default()) }
 
-    pub fn set(&mut self, property_name: PropertyName, value: Vec<u8>) {
+    fn with_values(values: BTreeMap<PropertyName, LWWWrites>) -> Self {
+        Self { values: Arc::new(RwLock::new(values)), pending: Arc::new(RwLock::new(BTreeMap::new())) }
+    }
+
+    /// Write a value which supersedes every value we've seen for the property
+    pub fn set(&self, property_name: PropertyName, value: Value) {
         let mut values = self.values.write().unwrap();
-        match values.entry(property_name) {
-            Entry::Occupied(mut entry) => {
-                entry.insert(value);
-            }
-            Entry::Vacant(entry) => {
-                entry.insert(value);
+        let observed = values.entry(property_name.clone()).or_default().overwrite(value);
+        self.pending.write().unwrap().entry(property_name).or_default().extend(observed);
+    }
+
+    pub fn get(&self, property_name: PropertyName) -> Option<Value> {
+        let values = self.values.read().unwrap();
+        values.get(&property_name).and_then(|value| value.current().cloned())
+    }
+
+    fn decode_values(state_buffer: &[u8]) -> bincode::Result<BTreeMap<PropertyName, LWWWrites>> {
+        let (winners, causality) =
+            match decode_extended::<BTreeMap<PropertyName, LWWValue>, BTreeMap<PropertyName, LWWCausality>>(state_buffer) {
+                Ok(decoded) => decoded,
+                Err(err) => {
+                    // Values were once stored as bare bytes, without any stamp. Any stamped write wins over them
+                    let Ok(legacy) = bincode::deserialize::<BTreeMap<PropertyName, Vec<u8>>>(state_buffer) else { return Err(err) };
+                    let unstamped = ID::from_ulid(ulid::Ulid::nil());
+                    let winners = legacy
+                        .into_iter()
+                        .map(|(property, bytes)| {
+                            let value = Value::String(String::from_utf8_lossy(&bytes).to_string());
+                            (property, LWWValue { value, event_id: Some(unstamped) })
+                        })
+                        .collect();
+                    (winners, BTreeMap::new())
+                }
+            };
+
+        let mut values: BTreeMap<PropertyName, LWWWrites> = causality
+            .into_iter()
+            .map(|(property, causality)| {
+                (property, LWWWrites { entries: causality.concurrent, uncommitted: None, superseded: causality.superseded })
+            })
+            .collect();
+        for (property, winner) in winners {
+            let value = values.entry(property).or_default();
+            match winner.event_id {
+                Some(event_id) => {
+                    value.entries.insert(event_id, winner.value);
+                }
+                None => value.uncommitted = Some(winner.value),
             }
         }
+        Ok(values)
     }
+}
 
-    pub fn get(&self, property_name: PropertyName) -> Option<Vec<u8>> {
-        let values = self.values.read().unwrap();
-        values.get(&property_name).cloned()
+/// Decode a buffer encoded as `T`, which may be followed by an extension encoded as `E`.
+/// Buffers written before the extension existed end after `T`, and decode with the default extension.
+fn decode_extended<T: DeserializeOwned, E: DeserializeOwned + Default>(buffer: &[u8]) -> bincode::Result<(T, E)> {
+    let mut reader = buffer;
+    let base = bincode::deserialize_from(&mut reader)?;
+    if reader.is_empty() {
+        return Ok((base, E::default()));
+    }
+    let extension = bincode::deserialize_from(&mut reader)?;
+    if !reader.is_empty() {
+        return Err(Box::new(bincode::ErrorKind::Custom(format!("{} trailing bytes", reader.len()))));
     }
+    Ok((base, extension))
 }
 
 impl PropertyBackend for LWWBackend {
@@ -47,11 +175,9 @@ impl PropertyBackend for LWWBackend {
     fn as_debug(&self) -> &dyn Debug { self as &dyn Debug }
 
     fn fork(&self) -> Box<dyn PropertyBackend> {
-        let values = self.values.read().unwrap();
-        let cloned = (*values).clone();
-        drop(values);
-
-        Box::new(Self { values: Arc::new(RwLock::new(cloned)) })
+        let values = self.values.read().unwrap().clone();
+        let pending = self.pending.read().unwrap().clone();
+        Box::new(Self { values: Arc::new(RwLock::new(values)), pending: Arc::new(RwLock::new(pending)) })
     }
 
     fn properties(&self) -> Vec<String> {
@@ -59,29 +185,118 @@ impl PropertyBackend for LWWBackend {
         values.keys().cloned().collect::<Vec<String>>()
     }
 
-    fn materialized(&self) -> BTreeMap<PropertyName, Materialized> { unimplemented!() }
+    fn materialized(&self) -> BTreeMap<PropertyName, Value> {
+        let values = self.values.read().unwrap();
+        values.iter().filter_map(|(property, value)| Some((property.clone(), value.current()?.clone()))).collect()
+    }
 
     fn property_backend_name() -> String { "lww".to_owned() }
 
     fn to_state_buffer(&self) -> anyhow::Result<Vec<u8>> {
         let values = self.values.read().unwrap();
-        let state_buffer = bincode::serialize(&*values)?;
+        let mut winners = BTreeMap::new();
+        let mut causality = BTreeMap::new();
+        for (property, value) in values.iter() {
+            let mut concurrent = value.entries.clone();
+            let winner = match &value.uncommitted {
+                Some(uncommitted) => Some(LWWValue { value: uncommitted.clone(), event_id: None }),
+                None => concurrent.pop_last().map(|(event_id, value)| LWWValue { value, event_id: Some(event_id) }),
+            };
+            if let Some(winner) = winner {
+                winners.insert(property.clone(), winner);
+            }
+            causality.insert(property.clone(), LWWCausality { concurrent, superseded: value.superseded.clone() });
+        }
+
+        let mut state_buffer = bincode::serialize(&winners)?;
+        state_buffer.extend(bincode::serialize(&causality)?);
         Ok(state_buffer)
     }
 
     fn from_state_buffer(state_buffer: &Vec<u8>) -> std::result::Result<Self, crate::error::RetrievalError>
     where Self: Sized {
-        let map = bincode::deserialize::<BTreeMap<PropertyName, Vec<u8>>>(state_buffer)?;
-        Ok(Self { values: Arc::new(RwLock::new(map)) })
+        Ok(Self::with_values(Self::decode_values(state_buffer)?))
     }
 
-    fn to_operations(&self /*precursor: ULID*/) -> anyhow::Result<Vec<super::Operation>> { todo!() }
+    fn to_operations(&self, event_id: ID) -> anyhow::Result<Vec<Operation>> {
+        // Only the values which were set since the last commit are sent, and they are now stamped with this event
+        let mut values = self.values.write().unwrap();
+        let mut pending = self.pending.write().unwrap();
+        let mut changed = BTreeMap::new();
+        let mut supersedes = BTreeMap::new();
+        for (property, value) in values.iter_mut() {
+            let Some(committed) = value.commit(event_id) else { continue };
+            changed.insert(property.clone(), committed);
+            supersedes.insert(property.clone(), pending.remove(property).unwrap_or_default());
+        }
 
-    fn apply_operations(&self, _operations: &Vec<super::Operation>) -> anyhow::Result<()> { todo!() }
+        if changed.is_empty() {
+            return Ok(Vec::new());
+        }
+        // The superseded writes follow the values, so that operations from before writes recorded them still decode
+        let mut diff = bincode::serialize(&changed)?;
+        diff.extend(bincode::serialize(&supersedes)?);
+        Ok(vec![Operation { diff }])
+    }
 
-    fn get_property_value_string(&self, property_name: &str) -> Option<String> {
-        self.values.read().unwrap().get(property_name).map(|v| String::from_utf8_lossy(v).to_string())
+    fn apply_operations(&self, event_id: ID, operations: &Vec<Operation>) -> anyhow::Result<()> {
+        let mut values = self.values.write().unwrap();
+        for operation in operations {
+            let (changed, mut supersedes) =
+                decode_extended::<BTreeMap<PropertyName, Value>, BTreeMap<PropertyName, BTreeSet<ID>>>(&operation.diff)?;
+            for (property, value) in changed {
+                // A write which doesn't say what it observed is concurrent with everything
+                let observed = supersedes.remove(&property).unwrap_or_default();
+                values.entry(property).or_default().apply_write(event_id, value, observed);
+            }
+        }
+
+        Ok(())
     }
+
+    fn merge_state(&self, state_buffer: &Vec<u8>, _ordering: ClockOrdering) -> anyhow::Result<()> {
+        let incoming = Self::decode_values(state_buffer)?;
+        let mut values = self.values.write().unwrap();
+        for (property, other) in incoming {
+            values.entry(property).or_default().merge(other);
+        }
+        Ok(())
+    }
+
+    fn property_value(&self, property_name: &str) -> Option<Value> { self.get(property_name.to_owned()) }
 }
 
-// Need ID based happens-before determination to resolve conflicts
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_older_encodings() {
+        let showbiz = Value::String("Showbiz".into());
+        let absolution = Value::String("Absolution".into());
+
+        // State buffers with only the winning values, and operations with only the changed values, decode as writes which
+        // didn't observe anything
+        // IDs made within the same millisecond aren't ordered by creation, so sort them
+        let (a, b) = (ID::new(), ID::new());
+        let (first, second) = (a.min(b), a.max(b));
+        let winners = BTreeMap::from([("name".to_string(), LWWValue { value: showbiz.clone(), event_id: Some(second) })]);
+        let backend = LWWBackend::from_state_buffer(&bincode::serialize(&winners).unwrap()).unwrap();
+        assert_eq!(backend.get("name".into()), Some(showbiz.clone()));
+
+        let changed = BTreeMap::from([("name".to_string(), absolution.clone())]);
+        backend.apply_operations(first, &vec![Operation { diff: bincode::serialize(&changed).unwrap() }]).unwrap();
+        assert_eq!(backend.get("name".into()), Some(showbiz.clone()));
+
+        // The losing write is retained, so it survives a round trip through the current encoding
+        let reloaded = LWWBackend::from_state_buffer(&backend.to_state_buffer().unwrap()).unwrap();
+        assert_eq!(reloaded.values.read().unwrap()["name"].entries, BTreeMap::from([(first, absolution.clone()), (second, showbiz)]));
+
+        // Bare values lose to any stamped write
+        let bare = BTreeMap::from([("name".to_string(), b"Origin of Symmetry".to_vec())]);
+        let backend = LWWBackend::from_state_buffer(&bincode::serialize(&bare).unwrap()).unwrap();
+        assert_eq!(backend.get("name".into()), Some(Value::String("Origin of Symmetry".into())));
+        backend.apply_operations(first, &vec![Operation { diff: bincode::serialize(&changed).unwrap() }]).unwrap();
+        assert_eq!(backend.get("name".into()), Some(absolution));
+    }
+}
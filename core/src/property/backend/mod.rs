@@ -1,43 +1,59 @@
-use ankurah_proto::{Clock, Operation, State};
+use ankurah_proto::{Clock, ClockOrdering, Operation, State, ID};
 use anyhow::Result;
 use std::any::Any;
 use std::fmt::Debug;
 use std::{
     collections::BTreeMap,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
 };
 
-use crate::storage::Materialized;
+use crate::value::Value;
 
+pub mod g_counter;
+pub mod list;
 pub mod lww;
+pub mod mv_register;
+pub mod or_set;
 pub mod pn_counter;
+pub mod registry;
 pub mod yrs;
 use crate::error::RetrievalError;
+pub use g_counter::GBackend;
+pub use list::ListBackend;
 pub use lww::LWWBackend;
+pub use mv_register::MVBackend;
+pub use or_set::ORSetBackend;
 pub use pn_counter::PNBackend;
+pub use registry::{backend_from_string, register_backend, BackendRegistration};
 pub use yrs::YrsBackend;
 
 use super::PropertyName;
 
 pub trait PropertyBackend: Any + Send + Sync + Debug + 'static {
     fn as_arc_dyn_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync + 'static>;
-    fn downcasted(self: Arc<Self>, name: &str) -> BackendDowncasted {
-        let upcasted = self.as_arc_dyn_any();
-        match name {
-            "yrs" => BackendDowncasted::Yrs(upcasted.downcast::<YrsBackend>().unwrap()),
-            "lww" => BackendDowncasted::LWW(upcasted.downcast::<LWWBackend>().unwrap()),
-            "pn" => BackendDowncasted::PN(upcasted.downcast::<PNBackend>().unwrap()),
-            _ => BackendDowncasted::Unknown(upcasted),
-        }
-    }
+    /// Downcast to the backend registered under `name`, which is `Unknown` for backends which aren't built into core
+    fn downcasted(self: Arc<Self>, name: &str) -> BackendDowncasted { registry::downcast(name, self.as_arc_dyn_any()) }
     fn as_debug(&self) -> &dyn Debug;
     fn fork(&self) -> Box<dyn PropertyBackend>;
+    /// Set the replica which local changes are attributed to, which is the ID of the node committing them.
+    /// Forks keep the replica of the backend they were forked from. Only backends which count per replica use it.
+    fn set_replica(&self, _replica: ID) {}
 
     fn properties(&self) -> Vec<String>;
-    fn materialized(&self) -> BTreeMap<PropertyName, Materialized>;
+    /// The scalar value of every property which has one, for storage engines to store alongside the state
+    fn materialized(&self) -> BTreeMap<PropertyName, Value>;
+
+    /// The scalar value of a property, for query evaluation and indexing
+    fn property_value(&self, property_name: &str) -> Option<Value>;
+
+    /// The members of a collection-valued property, for backends which have them
+    fn get_property_members(&self, _property_name: &str) -> Option<Vec<String>> { None }
 
-    // TODO: This should be a specific typecast, not just a string
-    fn get_property_value_string(&self, property_name: &str) -> Option<String>;
+    /// Properties which currently hold conflicting concurrent values, for backends which surface conflicts
+    fn conflicted_properties(&self) -> Vec<PropertyName> { Vec::new() }
 
     /// Unique property backend identifier.
     fn property_backend_name() -> String
@@ -50,16 +66,26 @@ pub trait PropertyBackend: Any + Send + Sync + Debug + 'static {
     where Self: Sized;
 
     /// Retrieve operations applied to this backend since the last time we called this method.
-    // TODO: Should this take a precursor id?
-    fn to_operations(&self /*precursor: ULID*/) -> anyhow::Result<Vec<Operation>>;
-    fn apply_operations(&self, operations: &Vec<Operation>) -> anyhow::Result<()>;
+    /// `event_id` is the ID of the event the operations are being collected for.
+    fn to_operations(&self, event_id: ID) -> anyhow::Result<Vec<Operation>>;
+    /// Apply the operations of the event `event_id`.
+    fn apply_operations(&self, event_id: ID, operations: &Vec<Operation>) -> anyhow::Result<()>;
+
+    /// Merge the state buffer of another replica of this backend into this one.
+    /// `ordering` is how the other replica's head relates to ours, and is either `Descends` or `Concurrent`.
+    fn merge_state(&self, state_buffer: &Vec<u8>, ordering: ClockOrdering) -> anyhow::Result<()>;
 }
 
 pub enum BackendDowncasted {
     Yrs(Arc<YrsBackend>),
     LWW(Arc<LWWBackend>),
     PN(Arc<PNBackend>),
-    Unknown(Arc<dyn Any>),
+    G(Arc<GBackend>),
+    ORSet(Arc<ORSetBackend>),
+    MV(Arc<MVBackend>),
+    List(Arc<ListBackend>),
+    /// A backend which isn't built into core, see `registry::register_backend`
+    Unknown(Arc<dyn Any + Send + Sync>),
 }
 
 // impl Event {
@@ -91,27 +117,8 @@ pub enum BackendDowncasted {
 pub struct Backends {
     pub backends: Arc<Mutex<BTreeMap<String, Arc<dyn PropertyBackend>>>>,
     pub head: Arc<Mutex<Clock>>,
-}
-
-// This is where this gets a bit tough.
-// PropertyBackends should either have a concrete type of some sort,
-// or if they can take a generic, they should also take a `Vec<u8>`.
-pub fn backend_from_string(name: &str, buffer: Option<&Vec<u8>>) -> Result<Arc<dyn PropertyBackend>, RetrievalError> {
-    if name == "yrs" {
-        let backend = match buffer {
-            Some(buffer) => YrsBackend::from_state_buffer(buffer)?,
-            None => YrsBackend::new(),
-        };
-        Ok(Arc::new(backend))
-    } else if name == "lww" {
-        let backend = match buffer {
-            Some(buffer) => LWWBackend::from_state_buffer(buffer)?,
-            None => LWWBackend::new(),
-        };
-        Ok(Arc::new(backend))
-    } else {
-        panic!("unknown backend: {:?}", name);
-    }
+    /// Set once the entity has been deleted
+    pub tombstone: Arc<AtomicBool>,
 }
 
 impl Default for Backends {
@@ -119,13 +126,22 @@ impl Default for Backends {
 }
 
 impl Backends {
-    pub fn new() -> Self { Self { backends: Arc::new(Mutex::new(BTreeMap::default())), head: Arc::new(Mutex::new(Clock::default())) } }
+    pub fn new() -> Self {
+        Self {
+            backends: Arc::new(Mutex::new(BTreeMap::default())),
+            head: Arc::new(Mutex::new(Clock::default())),
+            tombstone: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn is_tombstoned(&self) -> bool { self.tombstone.load(Ordering::SeqCst) }
+
+    pub fn set_tombstone(&self) { self.tombstone.store(true, Ordering::SeqCst); }
 
     pub fn get<P: PropertyBackend>(&self) -> Result<Arc<P>, RetrievalError> {
         let backend_name = P::property_backend_name();
-        let backend = self.get_raw(backend_name)?;
-        let upcasted = backend.as_arc_dyn_any();
-        Ok(upcasted.downcast::<P>().unwrap())
+        let backend = self.get_raw(backend_name.clone())?;
+        backend.as_arc_dyn_any().downcast::<P>().map_err(|_| RetrievalError::BackendTypeMismatch(backend_name))
     }
 
     pub fn get_with_name(&self, backend_name: String) -> Result<BackendDowncasted, RetrievalError> {
@@ -149,6 +165,24 @@ impl Backends {
         backends.iter().map(|(name, backend)| backend.clone().downcasted(name)).collect()
     }
 
+    /// The scalar value of a property from whichever backend holds it
+    pub fn property_value(&self, property_name: &str) -> Option<Value> {
+        let backends = self.backends.lock().unwrap();
+        backends.values().find_map(|backend| backend.property_value(property_name))
+    }
+
+    /// The materialized values of every backend
+    pub fn materialized(&self) -> BTreeMap<PropertyName, Value> {
+        let backends = self.backends.lock().unwrap();
+        backends.values().flat_map(|backend| backend.materialized()).collect()
+    }
+
+    /// Properties which currently hold conflicting concurrent values
+    pub fn conflicts(&self) -> Vec<PropertyName> {
+        let backends = self.backends.lock().unwrap();
+        backends.values().flat_map(|backend| backend.conflicted_properties()).collect()
+    }
+
     /// Fork the data behind the backends.
     pub fn fork(&self) -> Backends {
         let backends = self.backends.lock().unwrap();
@@ -157,7 +191,11 @@ impl Backends {
             forked.insert(name.clone(), backend.fork().into());
         }
 
-        Self { backends: Arc::new(Mutex::new(forked)), head: Arc::new(Mutex::new(self.head.lock().unwrap().clone())) }
+        Self {
+            backends: Arc::new(Mutex::new(forked)),
+            head: Arc::new(Mutex::new(self.head.lock().unwrap().clone())),
+            tombstone: Arc::new(AtomicBool::new(self.is_tombstoned())),
+        }
     }
 
     fn insert(&self, backend_name: String, backend: Arc<dyn PropertyBackend>) {
@@ -172,7 +210,7 @@ impl Backends {
             let state_buffer = backend.to_state_buffer()?;
             state_buffers.insert(name.clone(), state_buffer);
         }
-        Ok(State { state_buffers, head: self.head.lock().unwrap().clone() })
+        Ok(State { state_buffers, head: self.head.lock().unwrap().clone(), tombstone: self.is_tombstoned() })
     }
 
     pub fn from_state_buffers(entity_state: &State) -> Result<Self, RetrievalError> {
@@ -182,34 +220,46 @@ impl Backends {
             backends.insert(name.to_owned(), backend);
         }
         *backends.head.lock().unwrap() = entity_state.head.clone();
+        if entity_state.tombstone {
+            backends.set_tombstone();
+        }
         Ok(backends)
     }
 
-    pub fn to_operations(&self) -> Result<BTreeMap<String, Vec<Operation>>> {
+    /// Collect the operations of every backend for the event `event_id`, which the node `replica` is committing
+    pub fn to_operations(&self, event_id: ID, replica: ID) -> Result<BTreeMap<String, Vec<Operation>>> {
         let backends = self.backends.lock().unwrap();
         let mut operations = BTreeMap::<String, Vec<Operation>>::new();
         for (name, backend) in &*backends {
-            operations.insert(name.clone(), backend.to_operations()?);
+            backend.set_replica(replica);
+            operations.insert(name.clone(), backend.to_operations(event_id)?);
         }
 
         Ok(operations)
     }
 
-    pub fn apply_operations(&self, backend_name: String, operations: &Vec<Operation>, head: &Clock) -> Result<()> {
+    pub fn apply_operations(&self, backend_name: String, event_id: ID, operations: &Vec<Operation>) -> Result<()> {
         let backend = self.get_raw(backend_name)?;
-        backend.apply_operations(operations)?;
-        *self.head.lock().unwrap() = head.clone();
+        backend.apply_operations(event_id, operations)?;
         Ok(())
     }
 
-    /// HACK - this should be based on a play forward of events
-    pub fn apply_state(&self, state: &State) -> Result<(), RetrievalError> {
+    /// Merge the state buffers of another replica into our backends in place.
+    /// Backends which we don't have yet are constructed from the incoming buffer.
+    pub fn merge_state(&self, state: &State, ordering: ClockOrdering) -> Result<(), RetrievalError> {
         let mut backends = self.backends.lock().unwrap();
         for (name, state_buffer) in &state.state_buffers {
-            let backend = backend_from_string(name, Some(state_buffer))?;
-            backends.insert(name.to_owned(), backend);
+            match backends.get(name) {
+                Some(backend) => backend.merge_state(state_buffer, ordering)?,
+                None => {
+                    let backend = backend_from_string(name, Some(state_buffer))?;
+                    backends.insert(name.to_owned(), backend);
+                }
+            }
+        }
+        if state.tombstone {
+            self.set_tombstone();
         }
-        *self.head.lock().unwrap() = state.head.clone();
         Ok(())
     }
 }
@@ -0,0 +1,186 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+    sync::{Arc, RwLock},
+};
+
+use ankurah_proto::{ClockOrdering, ID};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    property::{
+        backend::{Operation, PropertyBackend},
+        PropertyName,
+    },
+    value::Value,
+};
+
+/// Multi-value register per property.
+/// Every write is stamped with the ID of its event and records the writes it observed, which it supersedes.
+/// A write only observes the writes in its causal history, so the values which survive are exactly those whose write events
+/// are concurrent with one another. The application can read all of them and resolve the conflict by writing a new value.
+#[derive(Debug)]
+pub struct MVBackend {
+    values: Arc<RwLock<BTreeMap<PropertyName, MVValue>>>,
+    /// The writes superseded by each property's uncommitted local value
+    pending: Arc<RwLock<BTreeMap<PropertyName, BTreeSet<ID>>>>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct MVValue {
+    /// The surviving values, by the event which wrote them
+    pub entries: BTreeMap<ID, Value>,
+    /// A value which was set locally and hasn't been committed yet
+    pub uncommitted: Option<Value>,
+    /// Writes which have been superseded
+    pub superseded: BTreeSet<ID>,
+}
+
+impl MVValue {
+    /// The surviving values ordered by the event which wrote them, with an uncommitted value last
+    pub fn values(&self) -> Vec<Value> { self.entries.values().chain(self.uncommitted.iter()).cloned().collect() }
+
+    /// The value with the highest stamp, which is what materialized views and queries see
+    pub fn current(&self) -> Option<&Value> { self.uncommitted.as_ref().or_else(|| self.entries.values().next_back()) }
+
+    pub fn is_conflicted(&self) -> bool { self.entries.len() + self.uncommitted.iter().count() > 1 }
+
+    fn apply_write(&mut self, event_id: ID, value: Value, supersedes: impl IntoIterator<Item = ID>) {
+        self.superseded.extend(supersedes);
+        if !self.superseded.contains(&event_id) {
+            self.entries.insert(event_id, value);
+        }
+        let superseded = &self.superseded;
+        self.entries.retain(|id, _| !superseded.contains(id));
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MVWrite {
+    value: Value,
+    supersedes: BTreeSet<ID>,
+}
+
+impl Default for MVBackend {
+    fn default() -> Self { Self::new() }
+}
+
+impl MVBackend {
+    pub fn new() -> MVBackend { Self::with_values(BTreeMap::default()) }
+
+    fn with_values(values: BTreeMap<PropertyName, MVValue>) -> Self {
+        Self { values: Arc::new(RwLock::new(values)), pending: Arc::new(RwLock::new(BTreeMap::new())) }
+    }
+
+    /// Write a value which supersedes every value we currently hold for the property, resolving any conflict
+    pub fn set(&self, property_name: PropertyName, value: Value) {
+        let mut values = self.values.write().unwrap();
+        let current = values.entry(property_name.clone()).or_default();
+        let observed = std::mem::take(&mut current.entries).into_keys().collect::<BTreeSet<_>>();
+        current.superseded.extend(observed.iter().cloned());
+        current.uncommitted = Some(value);
+        self.pending.write().unwrap().entry(property_name).or_default().extend(observed);
+    }
+
+    pub fn get(&self, property_name: &str) -> Option<Value> {
+        let values = self.values.read().unwrap();
+        values.get(property_name).and_then(|value| value.current().cloned())
+    }
+
+    /// Every concurrently written value of the property
+    pub fn get_all(&self, property_name: &str) -> Vec<Value> {
+        let values = self.values.read().unwrap();
+        values.get(property_name).map(|value| value.values()).unwrap_or_default()
+    }
+
+    pub fn is_conflicted(&self, property_name: &str) -> bool {
+        let values = self.values.read().unwrap();
+        values.get(property_name).is_some_and(|value| value.is_conflicted())
+    }
+}
+
+impl PropertyBackend for MVBackend {
+    fn as_arc_dyn_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync + 'static> { self as Arc<dyn Any + Send + Sync + 'static> }
+
+    fn as_debug(&self) -> &dyn Debug { self as &dyn Debug }
+
+    fn fork(&self) -> Box<dyn PropertyBackend> {
+        let values = self.values.read().unwrap();
+        Box::new(Self::with_values(values.clone()))
+    }
+
+    fn properties(&self) -> Vec<String> {
+        let values = self.values.read().unwrap();
+        values.keys().cloned().collect::<Vec<String>>()
+    }
+
+    fn materialized(&self) -> BTreeMap<PropertyName, Value> {
+        let values = self.values.read().unwrap();
+        values.iter().filter_map(|(property, value)| Some((property.clone(), value.current()?.clone()))).collect()
+    }
+
+    fn property_backend_name() -> String { "mv".to_owned() }
+
+    fn property_value(&self, property_name: &str) -> Option<Value> { self.get(property_name) }
+
+    fn conflicted_properties(&self) -> Vec<PropertyName> {
+        let values = self.values.read().unwrap();
+        values.iter().filter(|(_, value)| value.is_conflicted()).map(|(property, _)| property.clone()).collect()
+    }
+
+    fn to_state_buffer(&self) -> anyhow::Result<Vec<u8>> {
+        let values = self.values.read().unwrap();
+        Ok(bincode::serialize(&*values)?)
+    }
+
+    fn from_state_buffer(state_buffer: &Vec<u8>) -> std::result::Result<Self, crate::error::RetrievalError> {
+        let values = bincode::deserialize::<BTreeMap<PropertyName, MVValue>>(state_buffer)?;
+        Ok(Self::with_values(values))
+    }
+
+    fn to_operations(&self, event_id: ID) -> anyhow::Result<Vec<Operation>> {
+        let mut values = self.values.write().unwrap();
+        let pending = std::mem::take(&mut *self.pending.write().unwrap());
+
+        let mut writes = BTreeMap::new();
+        for (property, supersedes) in pending {
+            let Some(value) = values.get_mut(&property) else { continue };
+            let Some(uncommitted) = value.uncommitted.take() else { continue };
+            value.entries.insert(event_id, uncommitted.clone());
+            writes.insert(property, MVWrite { value: uncommitted, supersedes });
+        }
+
+        if writes.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(vec![Operation { diff: bincode::serialize(&writes)? }])
+    }
+
+    fn apply_operations(&self, event_id: ID, operations: &Vec<Operation>) -> anyhow::Result<()> {
+        let mut values = self.values.write().unwrap();
+        for operation in operations {
+            let writes = bincode::deserialize::<BTreeMap<PropertyName, MVWrite>>(&operation.diff)?;
+            for (property, write) in writes {
+                values.entry(property).or_default().apply_write(event_id, write.value, write.supersedes);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn merge_state(&self, state_buffer: &Vec<u8>, _ordering: ClockOrdering) -> anyhow::Result<()> {
+        // Writes and supersessions only accumulate, so the union is correct however the heads relate
+        let incoming = bincode::deserialize::<BTreeMap<PropertyName, MVValue>>(state_buffer)?;
+        let mut values = self.values.write().unwrap();
+        for (property, other) in incoming {
+            let value = values.entry(property).or_default();
+            value.superseded.extend(other.superseded);
+            // The other replica's uncommitted value can't have been sent to anyone else, so it isn't ours to adopt
+            for (event_id, entry) in other.entries {
+                value.apply_write(event_id, entry, []);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+    sync::{Arc, RwLock},
+};
+
+use ankurah_proto::{ClockOrdering, ID};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    property::{
+        backend::{Operation, PropertyBackend},
+        PropertyName,
+    },
+    value::Value,
+};
+
+/// Observed-remove sets of string-keyed elements.
+/// Every insert is tagged with the ID of its event, and a remove only removes the tags it observed,
+/// so an insert which is concurrent with a remove survives it (add wins).
+#[derive(Debug)]
+pub struct ORSetBackend {
+    values: Arc<RwLock<BTreeMap<PropertyName, ORSetValue>>>,
+    /// Operations since the last call to `to_operations`
+    pending: Arc<RwLock<Vec<PendingOperation>>>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ORSetValue {
+    /// The tags of every insert of each element
+    pub adds: BTreeMap<String, BTreeSet<ID>>,
+    /// Tags which have been removed
+    pub removed: BTreeSet<ID>,
+}
+
+impl ORSetValue {
+    fn live_tags(&self, element: &str) -> BTreeSet<ID> {
+        self.adds.get(element).map(|tags| tags.difference(&self.removed).cloned().collect()).unwrap_or_default()
+    }
+
+    pub fn contains(&self, element: &str) -> bool { !self.live_tags(element).is_empty() }
+
+    pub fn members(&self) -> Vec<String> { self.adds.keys().filter(|element| self.contains(element)).cloned().collect() }
+
+    pub fn merge(&mut self, other: &ORSetValue) {
+        for (element, tags) in &other.adds {
+            self.adds.entry(element.clone()).or_default().extend(tags.iter().cloned());
+        }
+        self.removed.extend(other.removed.iter().cloned());
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum ORSetOperation {
+    /// Tagged with the ID of the event which carries it
+    Add {
+        property: PropertyName,
+        element: String,
+    },
+    Remove {
+        property: PropertyName,
+        element: String,
+        tags: BTreeSet<ID>,
+    },
+}
+
+#[derive(Debug)]
+enum PendingOperation {
+    /// Inserted locally under a provisional tag, which is swapped for the event ID once the event is known
+    Add {
+        property: PropertyName,
+        element: String,
+        provisional: ID,
+    },
+    Remove {
+        property: PropertyName,
+        element: String,
+        tags: BTreeSet<ID>,
+    },
+}
+
+impl Default for ORSetBackend {
+    fn default() -> Self { Self::new() }
+}
+
+impl ORSetBackend {
+    pub fn new() -> ORSetBackend { Self::with_values(BTreeMap::default()) }
+
+    fn with_values(values: BTreeMap<PropertyName, ORSetValue>) -> Self {
+        Self { values: Arc::new(RwLock::new(values)), pending: Arc::new(RwLock::new(Vec::new())) }
+    }
+
+    pub fn contains(&self, property_name: &str, element: &str) -> bool {
+        let values = self.values.read().unwrap();
+        values.get(property_name).is_some_and(|value| value.contains(element))
+    }
+
+    pub fn members(&self, property_name: &str) -> Vec<String> {
+        let values = self.values.read().unwrap();
+        values.get(property_name).map(|value| value.members()).unwrap_or_default()
+    }
+
+    pub fn insert(&self, property_name: PropertyName, element: String) {
+        let provisional = ID::new();
+        let mut values = self.values.write().unwrap();
+        values.entry(property_name.clone()).or_default().adds.entry(element.clone()).or_default().insert(provisional);
+        self.pending.write().unwrap().push(PendingOperation::Add { property: property_name, element, provisional });
+    }
+
+    pub fn remove(&self, property_name: PropertyName, element: String) {
+        let mut values = self.values.write().unwrap();
+        let mut pending = self.pending.write().unwrap();
+        let value = values.entry(property_name.clone()).or_default();
+
+        // Inserts which haven't been sent anywhere yet can simply be forgotten
+        pending.retain(|operation| match operation {
+            PendingOperation::Add { property, element: e, provisional } if *property == property_name && *e == element => {
+                if let Some(tags) = value.adds.get_mut(&element) {
+                    tags.remove(provisional);
+                }
+                false
+            }
+            _ => true,
+        });
+
+        let tags = value.live_tags(&element);
+        if !tags.is_empty() {
+            value.removed.extend(tags.iter().cloned());
+            pending.push(PendingOperation::Remove { property: property_name, element, tags });
+        }
+    }
+
+    fn apply_operation(values: &mut BTreeMap<PropertyName, ORSetValue>, event_id: ID, operation: ORSetOperation) {
+        match operation {
+            ORSetOperation::Add { property, element } => {
+                values.entry(property).or_default().adds.entry(element).or_default().insert(event_id);
+            }
+            ORSetOperation::Remove { property, element, tags } => {
+                let value = values.entry(property).or_default();
+                value.adds.entry(element).or_default();
+                value.removed.extend(tags);
+            }
+        }
+    }
+}
+
+impl PropertyBackend for ORSetBackend {
+    fn as_arc_dyn_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync + 'static> { self as Arc<dyn Any + Send + Sync + 'static> }
+
+    fn as_debug(&self) -> &dyn Debug { self as &dyn Debug }
+
+    fn fork(&self) -> Box<dyn PropertyBackend> {
+        let values = self.values.read().unwrap();
+        Box::new(Self::with_values(values.clone()))
+    }
+
+    fn properties(&self) -> Vec<String> {
+        let values = self.values.read().unwrap();
+        values.keys().cloned().collect::<Vec<String>>()
+    }
+
+    fn materialized(&self) -> BTreeMap<PropertyName, Value> {
+        // Sets aren't scalars, so their members are encoded like any other non-scalar value
+        let values = self.values.read().unwrap();
+        values.iter().filter_map(|(property, value)| Some((property.clone(), Value::encode(&value.members()).ok()?))).collect()
+    }
+
+    fn property_backend_name() -> String { "orset".to_owned() }
+
+    fn property_value(&self, _property_name: &str) -> Option<Value> {
+        // Sets aren't scalar values. Queries test membership via `get_property_members` instead
+        None
+    }
+
+    fn get_property_members(&self, property_name: &str) -> Option<Vec<String>> {
+        let values = self.values.read().unwrap();
+        values.get(property_name).map(|value| value.members())
+    }
+
+    fn to_state_buffer(&self) -> anyhow::Result<Vec<u8>> {
+        let values = self.values.read().unwrap();
+        Ok(bincode::serialize(&*values)?)
+    }
+
+    fn from_state_buffer(state_buffer: &Vec<u8>) -> std::result::Result<Self, crate::error::RetrievalError> {
+        let values = bincode::deserialize::<BTreeMap<PropertyName, ORSetValue>>(state_buffer)?;
+        Ok(Self::with_values(values))
+    }
+
+    fn to_operations(&self, event_id: ID) -> anyhow::Result<Vec<Operation>> {
+        let mut values = self.values.write().unwrap();
+        let pending = std::mem::take(&mut *self.pending.write().unwrap());
+
+        let mut operations = Vec::new();
+        for operation in pending {
+            let operation = match operation {
+                PendingOperation::Add { property, element, provisional } => {
+                    if let Some(tags) = values.get_mut(&property).and_then(|value| value.adds.get_mut(&element)) {
+                        tags.remove(&provisional);
+                        tags.insert(event_id);
+                    }
+                    ORSetOperation::Add { property, element }
+                }
+                PendingOperation::Remove { property, element, tags } => ORSetOperation::Remove { property, element, tags },
+            };
+            operations.push(Operation { diff: bincode::serialize(&operation)? });
+        }
+
+        Ok(operations)
+    }
+
+    fn apply_operations(&self, event_id: ID, operations: &Vec<Operation>) -> anyhow::Result<()> {
+        let mut values = self.values.write().unwrap();
+        for operation in operations {
+            let operation = bincode::deserialize::<ORSetOperation>(&operation.diff)?;
+            Self::apply_operation(&mut values, event_id, operation);
+        }
+
+        Ok(())
+    }
+
+    fn merge_state(&self, state_buffer: &Vec<u8>, _ordering: ClockOrdering) -> anyhow::Result<()> {
+        // Tags and removals only ever accumulate, so the union is correct however the heads relate
+        let incoming = bincode::deserialize::<BTreeMap<PropertyName, ORSetValue>>(state_buffer)?;
+        let mut values = self.values.write().unwrap();
+        for (property, value) in incoming {
+            values.entry(property).or_default().merge(&value);
+        }
+        Ok(())
+    }
+}
@@ -1,36 +1,128 @@
 use std::{
     any::Any,
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     fmt::Debug,
-    ops::DerefMut,
     sync::{Arc, RwLock},
 };
 
+use ankurah_proto::{ClockOrdering, ID};
+use serde::{Deserialize, Serialize};
+
 use crate::{
     property::{
         backend::{Operation, PropertyBackend},
         PropertyName,
     },
-    storage::Materialized,
+    value::Value,
 };
 
+/// Grow-only counts per replica. Merging takes the maximum of each replica's count, so it is idempotent and commutative
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ReplicaCounts(BTreeMap<ID, u64>);
+
+impl ReplicaCounts {
+    pub fn total(&self) -> u64 { self.0.values().sum() }
+
+    pub fn get(&self, replica: &ID) -> u64 { self.0.get(replica).copied().unwrap_or(0) }
+
+    pub fn add(&mut self, replica: ID, amount: u64) { *self.0.entry(replica).or_default() += amount; }
+
+    pub fn merge_count(&mut self, replica: ID, count: u64) {
+        let entry = self.0.entry(replica).or_default();
+        *entry = (*entry).max(count);
+    }
+
+    pub fn merge(&mut self, other: &ReplicaCounts) {
+        for (replica, count) in &other.0 {
+            self.merge_count(*replica, *count);
+        }
+    }
+}
+
+/// A positive-negative counter per property, made of two grow-only vectors of per-replica counts.
+/// Each node is a replica, see `PropertyBackend::set_replica`. Local changes are held as pending amounts until they are
+/// committed. Concurrent transactions on the same node edit their own forks, so each commit reserves the node's next count
+/// from reservations shared with the forks, and the node's values only take the count once the event is applied.
 #[derive(Debug)]
 pub struct PNBackend {
+    replica: RwLock<Option<ID>>,
     values: Arc<RwLock<BTreeMap<PropertyName, PNValue>>>,
+    /// Amounts added locally since the last call to `to_operations`
+    pending: Arc<RwLock<BTreeMap<PropertyName, i64>>>,
+    /// The counts reserved by this node's commits, shared by the backend and its forks
+    reserved: Arc<RwLock<Reservations<PNValue>>>,
+    /// The events which this backend collected counts for
+    collected_events: RwLock<Vec<ID>>,
+}
+
+/// The counts reserved by a node's commits. A commit's counts are held for its event until the event is applied, which
+/// advances the applied counts, or until the backend which collected them is dropped without it being applied.
+#[derive(Debug)]
+pub(crate) struct Reservations<C> {
+    applied: BTreeMap<PropertyName, C>,
+    collected: BTreeMap<ID, BTreeMap<PropertyName, C>>,
 }
 
-#[derive(Debug, Default)]
+impl<C> Default for Reservations<C> {
+    fn default() -> Self { Self { applied: BTreeMap::new(), collected: BTreeMap::new() } }
+}
+
+impl<C: Clone + Default> Reservations<C> {
+    /// The highest counts reserved for a property, merged with `merge`
+    pub(crate) fn highest(&self, property: &str, merge: impl Fn(&mut C, &C)) -> C {
+        let mut highest = self.applied.get(property).cloned().unwrap_or_default();
+        for counts in self.collected.values().filter_map(|collected| collected.get(property)) {
+            merge(&mut highest, counts);
+        }
+        highest
+    }
+
+    /// Hold the counts collected for an event until it is applied or released
+    pub(crate) fn collect(&mut self, event_id: ID, counts: BTreeMap<PropertyName, C>) { self.collected.insert(event_id, counts); }
+
+    /// The applied counts to merge an event's counts into, which no longer need to be held for it
+    pub(crate) fn apply(&mut self, event_id: ID) -> &mut BTreeMap<PropertyName, C> {
+        self.collected.remove(&event_id);
+        &mut self.applied
+    }
+
+    /// Release the counts held for events which will never be applied
+    pub(crate) fn release(&mut self, event_ids: &[ID]) {
+        for event_id in event_ids {
+            self.collected.remove(event_id);
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct PNValue {
-    // TODO: Maybe use something aside from i64 for the base?
-    pub value: i64,
-    // TODO: replace with precursor entity?
-    pub previous_value: i64,
+    pub positive: ReplicaCounts,
+    pub negative: ReplicaCounts,
 }
 
 impl PNValue {
-    pub fn snapshot(&self) -> Self { Self { value: self.value, previous_value: self.value } }
+    pub fn value(&self) -> i64 { self.positive.total() as i64 - self.negative.total() as i64 }
 
-    pub fn diff(&self) -> i64 { self.value - self.previous_value }
+    pub fn add(&mut self, replica: ID, amount: i64) {
+        if amount >= 0 {
+            self.positive.add(replica, amount as u64);
+        } else {
+            self.negative.add(replica, amount.unsigned_abs());
+        }
+    }
+
+    pub fn merge(&mut self, other: &PNValue) {
+        self.positive.merge(&other.positive);
+        self.negative.merge(&other.negative);
+    }
+}
+
+/// The counts of a single replica for one property, which is what operations carry
+#[derive(Debug, Serialize, Deserialize)]
+struct PNReplicaCount {
+    replica: ID,
+    positive: u64,
+    negative: u64,
 }
 
 impl Default for PNBackend {
@@ -38,21 +130,26 @@ impl Default for PNBackend {
 }
 
 impl PNBackend {
-    pub fn new() -> PNBackend { Self { values: Arc::new(RwLock::new(BTreeMap::default())) } }
+    pub fn new() -> PNBackend { Self::with_values(BTreeMap::default(), Arc::default()) }
+
+    fn with_values(values: BTreeMap<PropertyName, PNValue>, reserved: Arc<RwLock<Reservations<PNValue>>>) -> Self {
+        Self {
+            replica: RwLock::new(None),
+            values: Arc::new(RwLock::new(values)),
+            pending: Arc::new(RwLock::new(BTreeMap::new())),
+            reserved,
+            collected_events: RwLock::new(Vec::new()),
+        }
+    }
 
     pub fn get(&self, property_name: PropertyName) -> i64 {
         let values = self.values.read().unwrap();
-        values.get(&property_name).map(|pnvalue| pnvalue.value).unwrap_or(0)
+        let pending = self.pending.read().unwrap();
+        values.get(&property_name).map(|pnvalue| pnvalue.value()).unwrap_or(0) + pending.get(&property_name).copied().unwrap_or(0)
     }
 
     pub fn add(&self, property_name: PropertyName, amount: i64) {
-        let values = self.values.write().unwrap();
-        Self::add_raw(values, property_name, amount);
-    }
-
-    pub fn add_raw(mut values: impl DerefMut<Target = BTreeMap<PropertyName, PNValue>>, property_name: PropertyName, amount: i64) {
-        let value = values.deref_mut().entry(property_name).or_default();
-        value.value += amount;
+        *self.pending.write().unwrap().entry(property_name).or_default() += amount;
     }
 }
 
@@ -63,62 +160,125 @@ impl PropertyBackend for PNBackend {
 
     fn fork(&self) -> Box<dyn PropertyBackend> {
         let values = self.values.read().unwrap();
-        let snapshotted = values.iter().map(|(key, value)| (key.to_owned(), value.snapshot())).collect::<BTreeMap<_, _>>();
-        Box::new(Self { values: Arc::new(RwLock::new(snapshotted)) })
+        let forked = Self::with_values(values.clone(), self.reserved.clone());
+        *forked.replica.write().unwrap() = *self.replica.read().unwrap();
+        Box::new(forked)
     }
 
+    fn set_replica(&self, replica: ID) { *self.replica.write().unwrap() = Some(replica); }
+
     fn properties(&self) -> Vec<String> {
         let values = self.values.read().unwrap();
-        values.keys().cloned().collect::<Vec<String>>()
+        let pending = self.pending.read().unwrap();
+        values.keys().chain(pending.keys()).cloned().collect::<BTreeSet<String>>().into_iter().collect()
     }
 
-    fn materialized(&self) -> BTreeMap<PropertyName, Materialized> {
-        let values = self.values.read().unwrap();
-        let mut map = BTreeMap::new();
-        for (property, data) in values.iter() {
-            map.insert(property.clone(), Materialized::Number(data.value));
-        }
-
-        map
+    fn materialized(&self) -> BTreeMap<PropertyName, Value> {
+        let properties = self.properties();
+        properties.into_iter().map(|property| (property.clone(), Value::Integer(self.get(property)))).collect()
     }
 
     fn property_backend_name() -> String { "pn".to_owned() }
 
     fn to_state_buffer(&self) -> anyhow::Result<Vec<u8>> {
         let values = self.values.read().unwrap();
-        let serializable = values.iter().map(|(key, value)| (key, value.value)).collect::<BTreeMap<_, _>>();
-        let serialized = bincode::serialize(&serializable)?;
+        let serialized = bincode::serialize(&*values)?;
         Ok(serialized)
     }
 
     fn from_state_buffer(state_buffer: &Vec<u8>) -> std::result::Result<Self, crate::error::RetrievalError> {
-        let values = bincode::deserialize::<BTreeMap<PropertyName, i64>>(state_buffer)?;
-        let values = values.into_iter().map(|(key, value)| (key, PNValue { value, previous_value: value })).collect::<BTreeMap<_, _>>();
-        Ok(Self { values: Arc::new(RwLock::new(values)) })
+        let values = bincode::deserialize::<BTreeMap<PropertyName, PNValue>>(state_buffer)?;
+        Ok(Self::with_values(values, Arc::default()))
     }
 
-    fn to_operations(&self /*precursor: ULID*/) -> anyhow::Result<Vec<Operation>> {
-        let values = self.values.read().unwrap();
-        let diffs = values.iter().map(|(key, value)| (key, value.diff())).collect::<BTreeMap<_, _>>();
+    fn to_operations(&self, event_id: ID) -> anyhow::Result<Vec<Operation>> {
+        let mut pending = self.pending.write().unwrap();
+        if pending.is_empty() {
+            return Ok(Vec::new());
+        }
+        let replica = self.replica.read().unwrap().ok_or_else(|| anyhow::anyhow!("No replica to attribute the counts to"))?;
+
+        // Send our cumulative counts rather than a diff, so applying an operation more than once is harmless
+        let counts = {
+            let mut reserved = self.reserved.write().unwrap();
+            let values = self.values.read().unwrap();
+            let reservations = std::mem::take(&mut *pending)
+                .into_iter()
+                .map(|(property, amount)| {
+                    let mut reservation = reserved.highest(&property, PNValue::merge);
+                    // Counts may have been loaded or merged since the last reservation
+                    if let Some(value) = values.get(&property) {
+                        reservation.positive.merge_count(replica, value.positive.get(&replica));
+                        reservation.negative.merge_count(replica, value.negative.get(&replica));
+                    }
+                    reservation.add(replica, amount);
+                    (property, reservation)
+                })
+                .collect::<BTreeMap<_, _>>();
+            let counts = reservations
+                .iter()
+                .map(|(property, reservation)| {
+                    let count = PNReplicaCount {
+                        replica,
+                        positive: reservation.positive.get(&replica),
+                        negative: reservation.negative.get(&replica),
+                    };
+                    (property.clone(), count)
+                })
+                .collect::<BTreeMap<_, _>>();
+            reserved.collect(event_id, reservations);
+            counts
+        };
+        self.collected_events.write().unwrap().push(event_id);
+
+        let mut values = self.values.write().unwrap();
+        for (property, count) in &counts {
+            let value = values.entry(property.clone()).or_default();
+            value.positive.merge_count(replica, count.positive);
+            value.negative.merge_count(replica, count.negative);
+        }
 
-        let serialized_diffs = bincode::serialize(&diffs)?;
-        Ok(vec![Operation { diff: serialized_diffs }])
+        Ok(vec![Operation { diff: bincode::serialize(&counts)? }])
     }
 
-    fn apply_operations(&self, operations: &Vec<Operation>) -> anyhow::Result<()> {
+    fn apply_operations(&self, event_id: ID, operations: &Vec<Operation>) -> anyhow::Result<()> {
+        let mut reserved = self.reserved.write().unwrap();
+        let applied = reserved.apply(event_id);
+        let mut values = self.values.write().unwrap();
         for operation in operations {
-            let diffs = bincode::deserialize::<BTreeMap<PropertyName, i64>>(&operation.diff)?;
-
-            let mut values = self.values.write().unwrap();
-            for (property, diff) in diffs {
-                Self::add_raw(&mut *values, property, diff);
+            let counts = bincode::deserialize::<BTreeMap<PropertyName, PNReplicaCount>>(&operation.diff)?;
+            for (property, count) in counts {
+                for value in [values.entry(property.clone()).or_default(), applied.entry(property).or_default()] {
+                    value.positive.merge_count(count.replica, count.positive);
+                    value.negative.merge_count(count.replica, count.negative);
+                }
             }
         }
 
         Ok(())
     }
 
-    fn get_property_value_string(&self, property_name: &str) -> Option<String> {
-        self.values.read().unwrap().get(property_name).map(|v| v.value.to_string())
+    fn merge_state(&self, state_buffer: &Vec<u8>, _ordering: ClockOrdering) -> anyhow::Result<()> {
+        // Per-replica counts merge the same way regardless of how the heads relate
+        let incoming = bincode::deserialize::<BTreeMap<PropertyName, PNValue>>(state_buffer)?;
+        let mut values = self.values.write().unwrap();
+        for (property, value) in incoming {
+            values.entry(property).or_default().merge(&value);
+        }
+        Ok(())
+    }
+
+    fn property_value(&self, property_name: &str) -> Option<Value> {
+        let known = self.values.read().unwrap().contains_key(property_name) || self.pending.read().unwrap().contains_key(property_name);
+        known.then(|| Value::Integer(self.get(property_name.to_owned())))
+    }
+}
+
+impl Drop for PNBackend {
+    // The events this backend collected counts for which haven't been applied by now never will be, such as when a commit fails
+    fn drop(&mut self) {
+        if let (Ok(mut reserved), Ok(collected)) = (self.reserved.write(), self.collected_events.get_mut()) {
+            reserved.release(collected);
+        }
     }
 }
@@ -0,0 +1,84 @@
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    sync::{Arc, LazyLock, RwLock},
+};
+
+use crate::error::RetrievalError;
+
+use super::{BackendDowncasted, GBackend, LWWBackend, ListBackend, MVBackend, ORSetBackend, PNBackend, PropertyBackend, YrsBackend};
+
+type Constructor = fn() -> Arc<dyn PropertyBackend>;
+type Decoder = fn(&Vec<u8>) -> Result<Arc<dyn PropertyBackend>, RetrievalError>;
+type Downcaster = fn(Arc<dyn Any + Send + Sync>) -> BackendDowncasted;
+
+/// How to construct a property backend, either empty or from a state buffer
+#[derive(Clone, Copy)]
+pub struct BackendRegistration {
+    pub new: Constructor,
+    pub from_state_buffer: Decoder,
+    /// Wrap a backend in its `BackendDowncasted` variant, which is `Unknown` for backends outside of core
+    pub downcast: Downcaster,
+}
+
+impl BackendRegistration {
+    pub fn of<P: PropertyBackend + Default>() -> Self {
+        Self {
+            new: || Arc::new(P::default()),
+            from_state_buffer: |state_buffer| Ok(Arc::new(P::from_state_buffer(state_buffer)?)),
+            downcast: BackendDowncasted::Unknown,
+        }
+    }
+}
+
+/// The backends built into core, which can't be replaced
+fn builtins() -> [(String, BackendRegistration); 7] {
+    fn builtin<P: PropertyBackend + Default>(downcast: Downcaster) -> (String, BackendRegistration) {
+        (P::property_backend_name(), BackendRegistration { downcast, ..BackendRegistration::of::<P>() })
+    }
+    [
+        builtin::<YrsBackend>(|backend| backend.downcast().map_or_else(BackendDowncasted::Unknown, BackendDowncasted::Yrs)),
+        builtin::<LWWBackend>(|backend| backend.downcast().map_or_else(BackendDowncasted::Unknown, BackendDowncasted::LWW)),
+        builtin::<PNBackend>(|backend| backend.downcast().map_or_else(BackendDowncasted::Unknown, BackendDowncasted::PN)),
+        builtin::<GBackend>(|backend| backend.downcast().map_or_else(BackendDowncasted::Unknown, BackendDowncasted::G)),
+        builtin::<ORSetBackend>(|backend| backend.downcast().map_or_else(BackendDowncasted::Unknown, BackendDowncasted::ORSet)),
+        builtin::<MVBackend>(|backend| backend.downcast().map_or_else(BackendDowncasted::Unknown, BackendDowncasted::MV)),
+        builtin::<ListBackend>(|backend| backend.downcast().map_or_else(BackendDowncasted::Unknown, BackendDowncasted::List)),
+    ]
+}
+
+static REGISTRY: LazyLock<RwLock<BTreeMap<String, BackendRegistration>>> = LazyLock::new(|| RwLock::new(BTreeMap::from(builtins())));
+
+/// Register a property backend under its `property_backend_name`, so that entities using it can be constructed
+/// from storage and from peers. Registering a name again replaces the previous registration, except for built in backends.
+pub fn register_backend<P: PropertyBackend + Default>() -> Result<(), RetrievalError> {
+    register_backend_with(P::property_backend_name(), BackendRegistration::of::<P>())
+}
+
+/// Register a property backend with explicit constructors, for backends which aren't `Default`
+pub fn register_backend_with(name: String, registration: BackendRegistration) -> Result<(), RetrievalError> {
+    if builtins().iter().any(|(builtin, _)| *builtin == name) {
+        return Err(RetrievalError::BuiltinBackend(name));
+    }
+    REGISTRY.write().unwrap().insert(name, registration);
+    Ok(())
+}
+
+pub fn is_registered(name: &str) -> bool { REGISTRY.read().unwrap().contains_key(name) }
+
+/// Construct the named property backend, from a state buffer if one is given
+pub fn backend_from_string(name: &str, buffer: Option<&Vec<u8>>) -> Result<Arc<dyn PropertyBackend>, RetrievalError> {
+    let registration = *REGISTRY.read().unwrap().get(name).ok_or_else(|| RetrievalError::UnknownBackend(name.to_owned()))?;
+    match buffer {
+        Some(buffer) => (registration.from_state_buffer)(buffer),
+        None => Ok((registration.new)()),
+    }
+}
+
+/// Downcast a backend to the `BackendDowncasted` variant of the backend registered under `name`
+pub fn downcast(name: &str, backend: Arc<dyn Any + Send + Sync>) -> BackendDowncasted {
+    match REGISTRY.read().unwrap().get(name) {
+        Some(registration) => (registration.downcast)(backend),
+        None => BackendDowncasted::Unknown(backend),
+    }
+}
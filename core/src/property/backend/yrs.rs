@@ -5,15 +5,20 @@ use std::{
     sync::{Arc, Mutex},
 };
 
+use ankurah_proto::{ClockOrdering, ID};
+use yrs::types::{text::YChange, Attrs};
 use yrs::Update;
-use yrs::{updates::decoder::Decode, GetString, ReadTxn, StateVector, Text, Transact};
+use yrs::{
+    updates::decoder::Decode, Any as YrsAny, Array, GetString, Map, Out, ReadTxn, StateVector, Text, Transact, XmlElementPrelim,
+    XmlFragment, XmlTextPrelim,
+};
 
 use crate::{
     property::{
         backend::{Operation, PropertyBackend},
         PropertyName,
     },
-    storage::Materialized,
+    value::Value,
 };
 
 /// Stores one or more properties of an entity
@@ -52,6 +57,117 @@ impl YrsBackend {
         text.remove_range(&mut ytx, index, length);
     }
 
+    pub fn insert_with_attributes(&self, property_name: impl AsRef<str>, index: u32, value: &str, attributes: Attrs) {
+        let text = self.doc.get_or_insert_text(property_name.as_ref());
+        let mut ytx = self.doc.transact_mut();
+        text.insert_with_attributes(&mut ytx, index, value, attributes);
+    }
+
+    /// Apply formatting attributes to a range of text. An attribute set to `Any::Null` is removed
+    pub fn format(&self, property_name: impl AsRef<str>, index: u32, length: u32, attributes: Attrs) {
+        let text = self.doc.get_or_insert_text(property_name.as_ref());
+        let mut ytx = self.doc.transact_mut();
+        text.format(&mut ytx, index, length, attributes);
+    }
+
+    /// The text as a sequence of runs which share the same formatting attributes
+    pub fn get_delta(&self, property_name: impl AsRef<str>) -> Option<Vec<(String, Attrs)>> {
+        let txn = self.doc.transact();
+        let text = txn.get_text(property_name.as_ref())?;
+        let runs = text.diff(&txn, YChange::identity).into_iter().filter_map(|diff| match diff.insert {
+            Out::Any(YrsAny::String(s)) => Some((s.to_string(), diff.attributes.map(|attributes| *attributes).unwrap_or_default())),
+            _ => None, // Embeds aren't supported yet
+        });
+        Some(runs.collect())
+    }
+
+    /// The primitive entries of a map property. Nested shared types are skipped
+    pub fn get_map(&self, property_name: impl AsRef<str>) -> Option<BTreeMap<String, YrsAny>> {
+        let txn = self.doc.transact();
+        let map = txn.get_map(property_name.as_ref())?;
+        let entries = map.iter(&txn).filter_map(|(key, value)| match value {
+            Out::Any(any) => Some((key.to_owned(), any)),
+            _ => None,
+        });
+        Some(entries.collect())
+    }
+
+    pub fn map_insert(&self, property_name: impl AsRef<str>, key: &str, value: YrsAny) {
+        let map = self.doc.get_or_insert_map(property_name.as_ref());
+        let mut ytx = self.doc.transact_mut();
+        map.insert(&mut ytx, key, value);
+    }
+
+    pub fn map_remove(&self, property_name: impl AsRef<str>, key: &str) {
+        let map = self.doc.get_or_insert_map(property_name.as_ref());
+        let mut ytx = self.doc.transact_mut();
+        map.remove(&mut ytx, key);
+    }
+
+    /// The primitive elements of an array property. Nested shared types are skipped
+    pub fn get_array(&self, property_name: impl AsRef<str>) -> Option<Vec<YrsAny>> {
+        let txn = self.doc.transact();
+        let array = txn.get_array(property_name.as_ref())?;
+        let elements = array.iter(&txn).filter_map(|value| match value {
+            Out::Any(any) => Some(any),
+            _ => None,
+        });
+        Some(elements.collect())
+    }
+
+    pub fn array_insert(&self, property_name: impl AsRef<str>, index: u32, value: YrsAny) {
+        let array = self.doc.get_or_insert_array(property_name.as_ref());
+        let mut ytx = self.doc.transact_mut();
+        array.insert(&mut ytx, index, value);
+    }
+
+    pub fn array_remove(&self, property_name: impl AsRef<str>, index: u32, length: u32) {
+        let array = self.doc.get_or_insert_array(property_name.as_ref());
+        let mut ytx = self.doc.transact_mut();
+        array.remove_range(&mut ytx, index, length);
+    }
+
+    pub fn array_len(&self, property_name: impl AsRef<str>) -> u32 {
+        let txn = self.doc.transact();
+        txn.get_array(property_name.as_ref()).map(|array| array.len(&txn)).unwrap_or(0)
+    }
+
+    /// The XML serialization of an XML fragment property
+    pub fn get_xml(&self, property_name: impl AsRef<str>) -> Option<String> {
+        let txn = self.doc.transact();
+        let fragment = txn.get_xml_fragment(property_name.as_ref())?;
+        Some(fragment.get_string(&txn))
+    }
+
+    pub fn xml_insert_text(&self, property_name: impl AsRef<str>, index: u32, text: &str) {
+        let fragment = self.doc.get_or_insert_xml_fragment(property_name.as_ref());
+        let mut ytx = self.doc.transact_mut();
+        fragment.insert(&mut ytx, index, XmlTextPrelim::new(text));
+    }
+
+    pub fn xml_insert_element(&self, property_name: impl AsRef<str>, index: u32, tag: &str) {
+        let fragment = self.doc.get_or_insert_xml_fragment(property_name.as_ref());
+        let mut ytx = self.doc.transact_mut();
+        fragment.insert(&mut ytx, index, XmlElementPrelim::empty(tag));
+    }
+
+    pub fn xml_remove(&self, property_name: impl AsRef<str>, index: u32, length: u32) {
+        let fragment = self.doc.get_or_insert_xml_fragment(property_name.as_ref());
+        let mut ytx = self.doc.transact_mut();
+        fragment.remove_range(&mut ytx, index, length);
+    }
+
+    /// Edit an XML fragment property directly, for structure which the helpers above don't cover
+    pub fn with_xml_fragment<R>(
+        &self,
+        property_name: impl AsRef<str>,
+        f: impl FnOnce(&yrs::XmlFragmentRef, &mut yrs::TransactionMut) -> R,
+    ) -> R {
+        let fragment = self.doc.get_or_insert_xml_fragment(property_name.as_ref());
+        let mut ytx = self.doc.transact_mut();
+        f(&fragment, &mut ytx)
+    }
+
     fn apply_update(&self, update: &[u8]) -> anyhow::Result<()> {
         let mut txn = self.doc.transact_mut();
         let update = Update::decode_v2(update)?;
@@ -78,14 +194,26 @@ impl PropertyBackend for YrsBackend {
         root_refs.map(|(name, _)| name.to_owned()).collect()
     }
 
-    fn materialized(&self) -> BTreeMap<PropertyName, Materialized> {
-        //let mut map = BTreeMap::new();
-        unimplemented!()
+    fn materialized(&self) -> BTreeMap<PropertyName, Value> {
+        let txn = self.doc.transact();
+        txn.root_refs()
+            .filter_map(|(name, out)| {
+                let string = match out {
+                    Out::YText(text) => text.get_string(&txn),
+                    Out::YXmlFragment(fragment) => fragment.get_string(&txn),
+                    // Roots which were decoded from a state buffer and haven't been accessed yet don't know their type
+                    Out::UndefinedRef(_) => txn.get_text(name)?.get_string(&txn),
+                    // Maps and arrays aren't scalar values
+                    _ => return None,
+                };
+                Some((name.to_owned(), Value::String(string)))
+            })
+            .collect()
     }
 
     fn property_backend_name() -> String { "yrs".to_owned() }
 
-    fn get_property_value_string(&self, property_name: &str) -> Option<String> { self.get_string(property_name) }
+    fn property_value(&self, property_name: &str) -> Option<Value> { self.get_string(property_name).map(Value::String) }
 
     fn to_state_buffer(&self) -> anyhow::Result<Vec<u8>> {
         let txn = self.doc.transact();
@@ -111,7 +239,7 @@ impl PropertyBackend for YrsBackend {
         Ok(Self { doc, previous_state: Arc::new(Mutex::new(starting_state)) })
     }
 
-    fn to_operations(&self /*precursor: ULID*/) -> anyhow::Result<Vec<Operation>> {
+    fn to_operations(&self, _event_id: ID) -> anyhow::Result<Vec<Operation>> {
         let mut operations = Vec::new();
 
         let mut previous_state = self.previous_state.lock().unwrap();
@@ -127,7 +255,7 @@ impl PropertyBackend for YrsBackend {
         Ok(operations)
     }
 
-    fn apply_operations(&self, operations: &Vec<Operation>) -> anyhow::Result<()> {
+    fn apply_operations(&self, _event_id: ID, operations: &Vec<Operation>) -> anyhow::Result<()> {
         // println!("apply operations: {:?}", operations);
         for operation in operations {
             self.apply_update(&operation.diff)?;
@@ -135,4 +263,9 @@ impl PropertyBackend for YrsBackend {
 
         Ok(())
     }
+
+    fn merge_state(&self, state_buffer: &Vec<u8>, _ordering: ClockOrdering) -> anyhow::Result<()> {
+        // A yrs state buffer is just an update containing everything, and applying updates is idempotent and commutative
+        self.apply_update(state_buffer)
+    }
 }
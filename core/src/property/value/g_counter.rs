@@ -0,0 +1,45 @@
+use std::{
+    marker::PhantomData,
+    sync::{Arc, Weak},
+};
+
+use crate::property::{
+    backend::{Backends, GBackend},
+    traits::InitializeWith,
+    value::{pn_counter::Unsigned, ProjectedValue},
+    PropertyName,
+};
+
+/// A counter which can only be incremented
+#[derive(Debug)]
+pub struct GCounter<I: Unsigned> {
+    pub property_name: PropertyName,
+    pub backend: Weak<GBackend>,
+    phantom: PhantomData<I>,
+}
+
+impl<I: Unsigned> ProjectedValue for GCounter<I> {
+    type Projected = I;
+    fn projected(&self) -> Self::Projected { self.value() }
+}
+
+impl<I: Unsigned> GCounter<I> {
+    pub fn new(property_name: PropertyName, backend: Arc<GBackend>) -> Self {
+        Self { property_name, backend: Arc::downgrade(&backend), phantom: PhantomData }
+    }
+    pub fn from_backends(property_name: PropertyName, backends: &Backends) -> Self {
+        let backend = backends.get::<GBackend>().unwrap();
+        Self::new(property_name, backend)
+    }
+    pub fn backend(&self) -> Arc<GBackend> { self.backend.upgrade().expect("Expected `G` property backend to exist") }
+    pub fn value(&self) -> I { I::from_i64(self.backend().get(self.property_name.clone()) as i64) }
+    pub fn add(&self, amount: u64) { self.backend().add(self.property_name.clone(), amount); }
+}
+
+impl<I: Unsigned> InitializeWith<I> for GCounter<I> {
+    fn initialize_with(backends: &Backends, property_name: PropertyName, value: &I) -> Self {
+        let new = Self::from_backends(property_name, backends);
+        new.add(value.as_u64());
+        new
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    fmt::Debug,
+    marker::PhantomData,
+    sync::{Arc, Weak},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::property::{
+    backend::{Backends, ListBackend},
+    traits::InitializeWith,
+    value::ProjectedValue,
+    PropertyName,
+};
+
+/// An ordered list of any serde type, where moving an item never duplicates it. Values are bincode encoded in the backend.
+pub struct List<T> {
+    pub property_name: PropertyName,
+    pub backend: Weak<ListBackend>,
+
+    phantom: PhantomData<T>,
+}
+
+impl<T> Debug for List<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("List").field("property_name", &self.property_name).field("backend", &self.backend).finish()
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> ProjectedValue for List<T> {
+    type Projected = Vec<T>;
+    fn projected(&self) -> Self::Projected { self.value() }
+}
+
+impl<T: Serialize + DeserializeOwned> List<T> {
+    pub fn new(property_name: PropertyName, backend: Arc<ListBackend>) -> Self {
+        Self { property_name, backend: Arc::downgrade(&backend), phantom: PhantomData }
+    }
+    pub fn from_backends(property_name: PropertyName, backends: &Backends) -> Self {
+        let backend = backends.get::<ListBackend>().unwrap();
+        Self::new(property_name, backend)
+    }
+    pub fn backend(&self) -> Arc<ListBackend> { self.backend.upgrade().expect("Expected `List` property backend to exist") }
+    /// The items of the list in order, skipping any which can't be decoded as `T`
+    pub fn value(&self) -> Vec<T> {
+        self.backend().get(&self.property_name).iter().filter_map(|bytes| bincode::deserialize(bytes).ok()).collect()
+    }
+    pub fn len(&self) -> usize { self.backend().len(&self.property_name) }
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+    /// Insert a value at `index`, shifting the items after it. Panics if `index > len`
+    pub fn insert_at(&self, index: usize, value: &T) -> anyhow::Result<()> {
+        self.backend().insert_at(self.property_name.clone(), index, bincode::serialize(value)?);
+        Ok(())
+    }
+    pub fn push(&self, value: &T) -> anyhow::Result<()> { self.insert_at(self.len(), value) }
+    /// Move the item at `from` so that it ends up at index `to`. Panics if either index is out of bounds
+    pub fn move_item(&self, from: usize, to: usize) { self.backend().move_item(self.property_name.clone(), from, to); }
+    /// Remove the item at `index`, returning it if it can be decoded as `T`. Panics if `index >= len`
+    pub fn remove(&self, index: usize) -> Option<T> {
+        let bytes = self.backend().remove(self.property_name.clone(), index);
+        bincode::deserialize(&bytes).ok()
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> InitializeWith<Vec<T>> for List<T> {
+    fn initialize_with(backends: &Backends, property_name: PropertyName, value: &Vec<T>) -> Self {
+        let new = Self::from_backends(property_name, backends);
+        for item in value {
+            new.push(item).expect("Failed to encode `List` item");
+        }
+        new
+    }
+}
@@ -1,9 +1,22 @@
-use std::{marker::PhantomData, sync::Weak};
+use std::{
+    fmt::Debug,
+    marker::PhantomData,
+    sync::{Arc, Weak},
+};
 
-use crate::property::{backend::LWWBackend, PropertyName};
+use serde::{de::DeserializeOwned, Serialize};
 
-use super::ProjectedValue;
+use crate::{
+    property::{
+        backend::{Backends, LWWBackend},
+        traits::InitializeWith,
+        value::ProjectedValue,
+        PropertyName,
+    },
+    value::Value,
+};
 
+/// A last-writer-wins value of any serde type. Values are stored as a `Value`, see `Value::encode`.
 pub struct LWW<T> {
     pub property_name: PropertyName,
     pub backend: Weak<LWWBackend>,
@@ -11,10 +24,48 @@ pub struct LWW<T> {
     phantom: PhantomData<T>,
 }
 
-impl<T> ProjectedValue for LWW<T> {
+// Derived Debug would require `T: Debug`
+impl<T> Debug for LWW<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LWW").field("property_name", &self.property_name).field("backend", &self.backend).finish()
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> ProjectedValue for LWW<T> {
     type Projected = T;
+    /// Panics if the property has never been set or can't be decoded as `T`, which `get` reports instead
     fn projected(&self) -> Self::Projected {
-        //self.value()
-        todo!()
+        match self.get() {
+            Ok(Some(value)) => value,
+            Ok(None) => panic!("Expected `LWW` property {} to have a value", self.property_name),
+            Err(err) => panic!("Failed to decode `LWW` property {}: {}", self.property_name, err),
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> LWW<T> {
+    pub fn new(property_name: PropertyName, backend: Arc<LWWBackend>) -> Self {
+        Self { property_name, backend: Arc::downgrade(&backend), phantom: PhantomData }
+    }
+    pub fn from_backends(property_name: PropertyName, backends: &Backends) -> Self {
+        let backend = backends.get::<LWWBackend>().unwrap();
+        Self::new(property_name, backend)
+    }
+    pub fn backend(&self) -> Arc<LWWBackend> { self.backend.upgrade().expect("Expected `LWW` property backend to exist") }
+    /// The current value, or None if it has never been set. Fails if the value can't be decoded as `T`
+    pub fn get(&self) -> anyhow::Result<Option<T>> {
+        self.backend().get(self.property_name.clone()).map(|value| value.decode()).transpose()
+    }
+    pub fn set(&self, value: &T) -> anyhow::Result<()> {
+        self.backend().set(self.property_name.clone(), Value::encode(value)?);
+        Ok(())
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> InitializeWith<T> for LWW<T> {
+    fn initialize_with(backends: &Backends, property_name: PropertyName, value: &T) -> Self {
+        let new = Self::from_backends(property_name, backends);
+        new.set(value).expect("Failed to encode `LWW` value");
+        new
     }
 }
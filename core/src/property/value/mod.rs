@@ -1,9 +1,18 @@
+pub mod g_counter;
+pub mod list;
 pub mod lww;
+pub mod mv_register;
+pub mod or_set;
 pub mod pn_counter;
 pub mod yrs;
+pub use ::yrs::Any as YrsAny;
+pub use g_counter::GCounter;
+pub use list::List;
 pub use lww::LWW;
+pub use mv_register::MVRegister;
+pub use or_set::{ORSet, SetElement};
 pub use pn_counter::PNCounter;
-pub use yrs::YrsString;
+pub use yrs::{DeltaSpan, YrsArray, YrsMap, YrsPrimitive, YrsString, YrsXmlFragment};
 
 pub trait ProjectedValue {
     type Projected;
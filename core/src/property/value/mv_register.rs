@@ -0,0 +1,67 @@
+use std::{
+    fmt::Debug,
+    marker::PhantomData,
+    sync::{Arc, Weak},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    property::{
+        backend::{Backends, MVBackend},
+        traits::InitializeWith,
+        value::ProjectedValue,
+        PropertyName,
+    },
+    value::Value,
+};
+
+/// A register which keeps every concurrently written value of any serde type, so conflicts can be resolved by the application.
+/// Values are stored as a `Value`, see `Value::encode`.
+pub struct MVRegister<T> {
+    pub property_name: PropertyName,
+    pub backend: Weak<MVBackend>,
+
+    phantom: PhantomData<T>,
+}
+
+impl<T> Debug for MVRegister<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MVRegister").field("property_name", &self.property_name).field("backend", &self.backend).finish()
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> ProjectedValue for MVRegister<T> {
+    type Projected = T;
+    fn projected(&self) -> Self::Projected { self.get().expect("Expected `MVRegister` property to have a value") }
+}
+
+impl<T: Serialize + DeserializeOwned> MVRegister<T> {
+    pub fn new(property_name: PropertyName, backend: Arc<MVBackend>) -> Self {
+        Self { property_name, backend: Arc::downgrade(&backend), phantom: PhantomData }
+    }
+    pub fn from_backends(property_name: PropertyName, backends: &Backends) -> Self {
+        let backend = backends.get::<MVBackend>().unwrap();
+        Self::new(property_name, backend)
+    }
+    pub fn backend(&self) -> Arc<MVBackend> { self.backend.upgrade().expect("Expected `MVRegister` property backend to exist") }
+    /// A single value, picked deterministically from the conflicting values if there are several
+    pub fn get(&self) -> Option<T> { self.backend().get(&self.property_name)?.decode().ok() }
+    /// Every concurrently written value, skipping any which can't be decoded as `T`
+    pub fn values(&self) -> Vec<T> { self.backend().get_all(&self.property_name).iter().filter_map(|value| value.decode().ok()).collect() }
+    pub fn is_conflicted(&self) -> bool { self.backend().is_conflicted(&self.property_name) }
+    pub fn set(&self, value: &T) -> anyhow::Result<()> {
+        self.backend().set(self.property_name.clone(), Value::encode(value)?);
+        Ok(())
+    }
+    /// Replace all of the conflicting values with `value`. This is the same as `set`, which supersedes every value it observes
+    pub fn resolve(&self, value: &T) -> anyhow::Result<()> { self.set(value) }
+}
+
+impl<T: Serialize + DeserializeOwned> InitializeWith<T> for MVRegister<T> {
+    fn initialize_with(backends: &Backends, property_name: PropertyName, value: &T) -> Self {
+        let new = Self::from_backends(property_name, backends);
+        new.set(value).expect("Failed to encode `MVRegister` value");
+        new
+    }
+}
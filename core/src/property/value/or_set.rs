@@ -0,0 +1,88 @@
+use std::{
+    collections::BTreeSet,
+    marker::PhantomData,
+    sync::{Arc, Weak},
+};
+
+use ankurah_proto::ID;
+
+use crate::property::{
+    backend::{Backends, ORSetBackend},
+    traits::InitializeWith,
+    value::ProjectedValue,
+    PropertyName,
+};
+
+/// Values which can be members of an `ORSet`. Members are stored by their key, which is also what queries compare against
+pub trait SetElement: Ord + Sized {
+    fn to_key(&self) -> String;
+    fn from_key(key: &str) -> Option<Self>;
+}
+
+impl SetElement for String {
+    fn to_key(&self) -> String { self.clone() }
+    fn from_key(key: &str) -> Option<Self> { Some(key.to_owned()) }
+}
+
+impl SetElement for ID {
+    fn to_key(&self) -> String { self.to_base64() }
+    fn from_key(key: &str) -> Option<Self> { ID::from_base64(key).ok() }
+}
+
+macro_rules! impl_set_element {
+    ($($element:ty),*) => {
+        $(
+        impl SetElement for $element {
+            fn to_key(&self) -> String {
+                self.to_string()
+            }
+            fn from_key(key: &str) -> Option<Self> {
+                key.parse().ok()
+            }
+        }
+        )*
+    };
+}
+
+impl_set_element!(bool, u8, u16, u32, u64, i8, i16, i32, i64);
+
+/// An observed-remove set. An insert which is concurrent with a remove of the same member wins
+#[derive(Debug)]
+pub struct ORSet<T: SetElement> {
+    pub property_name: PropertyName,
+    pub backend: Weak<ORSetBackend>,
+    phantom: PhantomData<T>,
+}
+
+impl<T: SetElement> ProjectedValue for ORSet<T> {
+    type Projected = BTreeSet<T>;
+    fn projected(&self) -> Self::Projected { self.iter().collect() }
+}
+
+impl<T: SetElement> ORSet<T> {
+    pub fn new(property_name: PropertyName, backend: Arc<ORSetBackend>) -> Self {
+        Self { property_name, backend: Arc::downgrade(&backend), phantom: PhantomData }
+    }
+    pub fn from_backends(property_name: PropertyName, backends: &Backends) -> Self {
+        let backend = backends.get::<ORSetBackend>().unwrap();
+        Self::new(property_name, backend)
+    }
+    pub fn backend(&self) -> Arc<ORSetBackend> { self.backend.upgrade().expect("Expected `ORSet` property backend to exist") }
+    pub fn insert(&self, value: &T) { self.backend().insert(self.property_name.clone(), value.to_key()); }
+    pub fn remove(&self, value: &T) { self.backend().remove(self.property_name.clone(), value.to_key()); }
+    pub fn contains(&self, value: &T) -> bool { self.backend().contains(&self.property_name, &value.to_key()) }
+    /// The current members, skipping any which can't be decoded as a `T`
+    pub fn iter(&self) -> impl Iterator<Item = T> {
+        self.backend().members(&self.property_name).into_iter().filter_map(|key| T::from_key(&key))
+    }
+}
+
+impl<T: SetElement> InitializeWith<BTreeSet<T>> for ORSet<T> {
+    fn initialize_with(backends: &Backends, property_name: PropertyName, value: &BTreeSet<T>) -> Self {
+        let new = Self::from_backends(property_name, backends);
+        for member in value {
+            new.insert(member);
+        }
+        new
+    }
+}
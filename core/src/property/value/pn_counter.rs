@@ -32,6 +32,25 @@ macro_rules! impl_integer {
 
 impl_integer!(u8, u16, u32, u64, i8, i16, i32, i64);
 
+/// Integers which can't be negative, for counters which can only be incremented
+pub trait Unsigned: Integer {
+    fn as_u64(self) -> u64;
+}
+
+macro_rules! impl_unsigned {
+    ($($unsigned:ty),*) => {
+        $(
+        impl Unsigned for $unsigned {
+            fn as_u64(self) -> u64 {
+                self as u64
+            }
+        }
+        )*
+    };
+}
+
+impl_unsigned!(u8, u16, u32, u64);
+
 #[derive(Debug)]
 pub struct PNCounter<I: Integer> {
     pub property_name: PropertyName,
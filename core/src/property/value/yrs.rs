@@ -1,4 +1,9 @@
-use std::sync::{Arc, Weak};
+use std::{
+    collections::BTreeMap,
+    marker::PhantomData,
+    ops::Range,
+    sync::{Arc, Weak},
+};
 
 use crate::property::{
     backend::{Backends, YrsBackend},
@@ -40,6 +45,35 @@ impl YrsString {
         self.backend().delete(&self.property_name, 0, self.value().unwrap_or_default().len() as u32);
         self.backend().insert(&self.property_name, 0, value);
     }
+    /// Insert text carrying formatting attributes, such as `bold` or `link`
+    pub fn insert_formatted(&self, index: u32, value: &str, attributes: BTreeMap<String, yrs::Any>) {
+        self.backend().insert_with_attributes(&self.property_name, index, value, to_attrs(attributes));
+    }
+    /// Apply formatting attributes to a range of the text. Setting an attribute to `yrs::Any::Null` removes it
+    pub fn format(&self, range: Range<u32>, attributes: BTreeMap<String, yrs::Any>) {
+        self.backend().format(&self.property_name, range.start, range.end.saturating_sub(range.start), to_attrs(attributes));
+    }
+    /// The text as a sequence of spans which share the same formatting
+    pub fn to_delta(&self) -> Vec<DeltaSpan> {
+        let runs = self.backend().get_delta(&self.property_name).unwrap_or_default();
+        runs.into_iter()
+            .map(|(insert, attributes)| DeltaSpan {
+                insert,
+                attributes: attributes.into_iter().map(|(key, value)| (key.to_string(), value)).collect(),
+            })
+            .collect()
+    }
+}
+
+/// A span of text and its formatting attributes, as in a Quill delta
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeltaSpan {
+    pub insert: String,
+    pub attributes: BTreeMap<String, yrs::Any>,
+}
+
+fn to_attrs(attributes: BTreeMap<String, yrs::Any>) -> yrs::types::Attrs {
+    attributes.into_iter().map(|(key, value)| (key.into(), value)).collect()
 }
 
 impl InitializeWith<String> for YrsString {
@@ -50,6 +84,179 @@ impl InitializeWith<String> for YrsString {
     }
 }
 
+/// Primitive values which can be stored in a `YrsMap` or `YrsArray`
+pub trait YrsPrimitive: Sized {
+    fn into_any(self) -> yrs::Any;
+    fn from_any(any: yrs::Any) -> Option<Self>;
+}
+
+impl YrsPrimitive for String {
+    fn into_any(self) -> yrs::Any { yrs::Any::String(self.into()) }
+    fn from_any(any: yrs::Any) -> Option<Self> {
+        match any {
+            yrs::Any::String(s) => Some(s.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl YrsPrimitive for bool {
+    fn into_any(self) -> yrs::Any { yrs::Any::Bool(self) }
+    fn from_any(any: yrs::Any) -> Option<Self> {
+        match any {
+            yrs::Any::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+}
+
+impl YrsPrimitive for i64 {
+    fn into_any(self) -> yrs::Any { yrs::Any::BigInt(self) }
+    fn from_any(any: yrs::Any) -> Option<Self> {
+        match any {
+            yrs::Any::BigInt(i) => Some(i),
+            yrs::Any::Number(n) => Some(n as i64),
+            _ => None,
+        }
+    }
+}
+
+impl YrsPrimitive for f64 {
+    fn into_any(self) -> yrs::Any { yrs::Any::Number(self) }
+    fn from_any(any: yrs::Any) -> Option<Self> {
+        match any {
+            yrs::Any::Number(n) => Some(n),
+            yrs::Any::BigInt(i) => Some(i as f64),
+            _ => None,
+        }
+    }
+}
+
+/// A collaborative map from string keys to primitive values
+#[derive(Debug)]
+pub struct YrsMap<V> {
+    pub property_name: PropertyName,
+    pub backend: Weak<YrsBackend>,
+    phantom: PhantomData<V>,
+}
+
+impl<V: YrsPrimitive> ProjectedValue for YrsMap<V> {
+    type Projected = BTreeMap<String, V>;
+    fn projected(&self) -> Self::Projected { self.value().unwrap_or_default() }
+}
+
+impl<V: YrsPrimitive> YrsMap<V> {
+    pub fn new(property_name: PropertyName, backend: Arc<YrsBackend>) -> Self {
+        Self { property_name, backend: Arc::downgrade(&backend), phantom: PhantomData }
+    }
+    pub fn from_backends(property_name: PropertyName, backends: &Backends) -> Self {
+        let backend = backends.get::<YrsBackend>().unwrap();
+        Self::new(property_name, backend)
+    }
+    pub fn backend(&self) -> Arc<YrsBackend> { self.backend.upgrade().expect("Expected `Yrs` property backend to exist") }
+    /// The entries of the map, skipping any which aren't a `V`
+    pub fn value(&self) -> Option<BTreeMap<String, V>> {
+        let entries = self.backend().get_map(&self.property_name)?;
+        Some(entries.into_iter().filter_map(|(key, any)| Some((key, V::from_any(any)?))).collect())
+    }
+    pub fn get(&self, key: &str) -> Option<V> { self.backend().get_map(&self.property_name)?.remove(key).and_then(V::from_any) }
+    pub fn insert(&self, key: &str, value: V) { self.backend().map_insert(&self.property_name, key, value.into_any()); }
+    pub fn remove(&self, key: &str) { self.backend().map_remove(&self.property_name, key); }
+}
+
+impl<V: YrsPrimitive + Clone> InitializeWith<BTreeMap<String, V>> for YrsMap<V> {
+    fn initialize_with(backends: &Backends, property_name: PropertyName, value: &BTreeMap<String, V>) -> Self {
+        let new_map = Self::from_backends(property_name, backends);
+        for (key, v) in value {
+            new_map.insert(key, v.clone());
+        }
+        new_map
+    }
+}
+
+/// A collaborative sequence of primitive values
+#[derive(Debug)]
+pub struct YrsArray<V> {
+    pub property_name: PropertyName,
+    pub backend: Weak<YrsBackend>,
+    phantom: PhantomData<V>,
+}
+
+impl<V: YrsPrimitive> ProjectedValue for YrsArray<V> {
+    type Projected = Vec<V>;
+    fn projected(&self) -> Self::Projected { self.value().unwrap_or_default() }
+}
+
+impl<V: YrsPrimitive> YrsArray<V> {
+    pub fn new(property_name: PropertyName, backend: Arc<YrsBackend>) -> Self {
+        Self { property_name, backend: Arc::downgrade(&backend), phantom: PhantomData }
+    }
+    pub fn from_backends(property_name: PropertyName, backends: &Backends) -> Self {
+        let backend = backends.get::<YrsBackend>().unwrap();
+        Self::new(property_name, backend)
+    }
+    pub fn backend(&self) -> Arc<YrsBackend> { self.backend.upgrade().expect("Expected `Yrs` property backend to exist") }
+    /// The elements of the array, skipping any which aren't a `V`
+    pub fn value(&self) -> Option<Vec<V>> {
+        Some(self.backend().get_array(&self.property_name)?.into_iter().filter_map(V::from_any).collect())
+    }
+    pub fn len(&self) -> u32 { self.backend().array_len(&self.property_name) }
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+    pub fn insert(&self, index: u32, value: V) { self.backend().array_insert(&self.property_name, index, value.into_any()); }
+    pub fn push(&self, value: V) { self.insert(self.len(), value); }
+    pub fn delete(&self, index: u32, length: u32) { self.backend().array_remove(&self.property_name, index, length); }
+}
+
+impl<V: YrsPrimitive + Clone> InitializeWith<Vec<V>> for YrsArray<V> {
+    fn initialize_with(backends: &Backends, property_name: PropertyName, value: &Vec<V>) -> Self {
+        let new_array = Self::from_backends(property_name, backends);
+        for v in value {
+            new_array.push(v.clone());
+        }
+        new_array
+    }
+}
+
+/// A collaborative XML document, projected as its serialization
+#[derive(Debug)]
+pub struct YrsXmlFragment {
+    pub property_name: PropertyName,
+    pub backend: Weak<YrsBackend>,
+}
+
+impl ProjectedValue for YrsXmlFragment {
+    type Projected = String;
+    fn projected(&self) -> Self::Projected { self.value().unwrap_or_default() }
+}
+
+impl YrsXmlFragment {
+    pub fn new(property_name: PropertyName, backend: Arc<YrsBackend>) -> Self { Self { property_name, backend: Arc::downgrade(&backend) } }
+    pub fn from_backends(property_name: PropertyName, backends: &Backends) -> Self {
+        let backend = backends.get::<YrsBackend>().unwrap();
+        Self::new(property_name, backend)
+    }
+    pub fn backend(&self) -> Arc<YrsBackend> { self.backend.upgrade().expect("Expected `Yrs` property backend to exist") }
+    pub fn value(&self) -> Option<String> { self.backend().get_xml(&self.property_name) }
+    pub fn insert_text(&self, index: u32, text: &str) { self.backend().xml_insert_text(&self.property_name, index, text); }
+    pub fn insert_element(&self, index: u32, tag: &str) { self.backend().xml_insert_element(&self.property_name, index, tag); }
+    pub fn delete(&self, index: u32, length: u32) { self.backend().xml_remove(&self.property_name, index, length); }
+    /// Edit the fragment directly, for nested structure and attributes
+    pub fn edit<R>(&self, f: impl FnOnce(&yrs::XmlFragmentRef, &mut yrs::TransactionMut) -> R) -> R {
+        self.backend().with_xml_fragment(&self.property_name, f)
+    }
+}
+
+impl InitializeWith<String> for YrsXmlFragment {
+    /// The initial value is inserted as a single text node. Markup in it is not parsed
+    fn initialize_with(backends: &Backends, property_name: PropertyName, value: &String) -> Self {
+        let new_fragment = Self::from_backends(property_name, backends);
+        if !value.is_empty() {
+            new_fragment.insert_text(0, value);
+        }
+        new_fragment
+    }
+}
+
 // TODO: Figure out whether to remove this
 /*
 impl StateSync for YrsString {
@@ -4,9 +4,9 @@ use crate::model::Entity;
 use crate::resultset::ResultSet;
 use crate::storage::StorageEngine;
 use crate::subscription::{Subscription, SubscriptionHandle};
-use crate::value::Value;
 use ankql::ast;
 use ankql::selection::filter::Filterable;
+use ankql::selection::pattern::literal_prefix;
 use dashmap::{DashMap, DashSet};
 use std::collections::HashSet;
 use std::sync::Arc;
@@ -68,7 +68,7 @@ impl Reactor {
         self.manage_watchers_recurse(collection_id, &predicate, sub_id, WatcherOp::Add);
 
         // Find initial matching entities
-        let states = self.storage.fetch_states(collection_id.clone(), &predicate).await?;
+        let states = self.storage.fetch_states(collection_id.clone(), &predicate.clone().into()).await?;
         let mut matching_entities = Vec::new();
 
         // Convert states to Entity and filter by predicate
@@ -101,7 +101,7 @@ impl Reactor {
         if !matching_entities.is_empty() {
             (subscription.callback)(ChangeSet {
                 changes: matching_entities.iter().map(|entity| ItemChange::Initial { item: entity.clone() }).collect(),
-                resultset: ResultSet { items: matching_entities.clone() },
+                resultset: ResultSet::matching(matching_entities.clone(), &subscription.predicate),
             });
         }
 
@@ -115,54 +115,112 @@ impl Reactor {
         sub_id: proto::SubscriptionId,
         op: WatcherOp,
     ) {
-        use ankql::ast::{Expr, Identifier, Predicate};
+        use ankql::ast::{ComparisonOperator, Expr, Identifier, Literal, Predicate};
         match predicate {
             Predicate::Comparison { left, operator, right } => {
-                if let (Expr::Identifier(field), Expr::Literal(literal)) | (Expr::Literal(literal), Expr::Identifier(field)) =
-                    (&**left, &**right)
-                {
+                // IN and BETWEEN are indexed as the simple comparisons they are made of
+                let entries: Option<(&Identifier, Vec<(&Expr, ast::ComparisonOperator)>)> = match (&**left, operator, &**right) {
+                    (Expr::Identifier(field), ast::ComparisonOperator::In, Expr::ExprList(list)) => {
+                        Some((field, list.iter().map(|expr| (expr, ast::ComparisonOperator::Equal)).collect()))
+                    }
+                    (Expr::Identifier(field), ast::ComparisonOperator::Between, Expr::ExprList(bounds)) if bounds.len() == 2 => Some((
+                        field,
+                        vec![
+                            (&bounds[0], ast::ComparisonOperator::GreaterThanOrEqual),
+                            (&bounds[1], ast::ComparisonOperator::LessThanOrEqual),
+                        ],
+                    )),
+                    // A pattern on the left is matched against the field, so there is nothing to index
+                    (
+                        Expr::Literal(_),
+                        ComparisonOperator::Like
+                        | ComparisonOperator::ILike
+                        | ComparisonOperator::StartsWith
+                        | ComparisonOperator::Contains
+                        | ComparisonOperator::Matches,
+                        _,
+                    ) => None,
+                    (Expr::Identifier(field), _, literal @ Expr::Literal(_)) | (literal @ Expr::Literal(_), _, Expr::Identifier(field)) => {
+                        Some((field, vec![(literal, operator.clone())]))
+                    }
+                    _ => None,
+                };
+                // Only comparisons of a field against literals can be indexed. Prefix matches are indexed as ranges, and a
+                // LIKE pattern by the prefix before its first wildcard. Other patterns and searches could match anywhere in the value.
+                let entries = entries.and_then(|(field, entries)| {
+                    let literals = entries.into_iter().map(|(expr, operator)| match (expr, operator) {
+                        (Expr::Literal(Literal::String(pattern)), ComparisonOperator::Like) => {
+                            let prefix = literal_prefix(pattern);
+                            (!prefix.is_empty()).then_some((Literal::String(prefix), ComparisonOperator::StartsWith))
+                        }
+                        (Expr::Literal(literal @ Literal::String(_)), ComparisonOperator::StartsWith) => {
+                            Some((literal.clone(), ComparisonOperator::StartsWith))
+                        }
+                        (
+                            _,
+                            ComparisonOperator::Like
+                            | ComparisonOperator::ILike
+                            | ComparisonOperator::StartsWith
+                            | ComparisonOperator::Contains
+                            | ComparisonOperator::Matches,
+                        ) => None,
+                        (Expr::Literal(literal), operator) => Some((literal.clone(), operator)),
+                        _ => None,
+                    });
+                    Some((field, literals.collect::<Option<Vec<_>>>()?))
+                });
+
+                if let Some((field, entries)) = entries {
                     let field_name = match field {
                         Identifier::Property(name) => name.clone(),
                         Identifier::CollectionProperty(_, name) => name.clone(),
                     };
 
                     let field_id = FieldId(field_name);
-                    match op {
-                        WatcherOp::Add => {
-                            let entry = self.index_watchers.entry((collection_id.clone(), field_id));
-                            let foo = entry.or_default().add((*literal).clone(), operator.clone(), sub_id);
-                            info!("recurse_predicate add: {:?}", foo);
-                        }
-                        WatcherOp::Remove => {
-                            if let Some(mut index) = self.index_watchers.get_mut(&(collection_id.clone(), field_id)) {
-                                index.remove((*literal).clone(), operator.clone(), sub_id);
+                    for (literal, operator) in entries {
+                        match op {
+                            WatcherOp::Add => {
+                                let entry = self.index_watchers.entry((collection_id.clone(), field_id.clone()));
+                                let foo = entry.or_default().add(literal, operator, sub_id);
+                                info!("recurse_predicate add: {:?}", foo);
+                            }
+                            WatcherOp::Remove => {
+                                if let Some(mut index) = self.index_watchers.get_mut(&(collection_id.clone(), field_id.clone())) {
+                                    index.remove(literal, operator, sub_id);
+                                }
                             }
                         }
                     }
                 } else {
-                    // warn!("Unsupported predicate: {:?}", predicate);
+                    // Comparisons involving arithmetic or more than one field could change with any edit
+                    self.manage_wildcard_watcher(collection_id, sub_id, op);
                 }
             }
             Predicate::And(left, right) | Predicate::Or(left, right) => {
                 self.manage_watchers_recurse(collection_id, left, sub_id, op);
                 self.manage_watchers_recurse(collection_id, right, sub_id, op);
             }
-            Predicate::Not(pred) => {
-                self.manage_watchers_recurse(collection_id, pred, sub_id, op);
+            Predicate::Not(_) => {
+                // The index only finds values which match the inner predicate, whereas a negation matches everything else
+                self.manage_wildcard_watcher(collection_id, sub_id, op);
             }
             Predicate::IsNull(_) => {
                 unimplemented!("Not sure how to implement this")
             }
             Predicate::True => {
-                let set = self.wildcard_watchers.entry(collection_id.clone()).or_default();
-                match op {
-                    WatcherOp::Add => {
-                        set.insert(sub_id);
-                    }
-                    WatcherOp::Remove => {
-                        set.remove(&sub_id);
-                    }
-                }
+                self.manage_wildcard_watcher(collection_id, sub_id, op);
+            }
+        }
+    }
+
+    fn manage_wildcard_watcher(&self, collection_id: &proto::CollectionId, sub_id: proto::SubscriptionId, op: WatcherOp) {
+        let set = self.wildcard_watchers.entry(collection_id.clone()).or_default();
+        match op {
+            WatcherOp::Add => {
+                set.insert(sub_id);
+            }
+            WatcherOp::Remove => {
+                set.remove(&sub_id);
             }
         }
     }
@@ -223,8 +281,11 @@ impl Reactor {
                 // Get the field value from the entity
                 let (collection_id, field_id) = index_ref.key();
                 if collection_id == &(change.entity.collection) {
-                    if let Some(field_value) = change.entity.value(&field_id.0) {
-                        possibly_interested_subs.extend(index_ref.find_matching(Value::String(field_value)));
+                    if let Some(field_value) = change.entity.property_value(&field_id.0) {
+                        // Predicates compare across types, so the index has to be searched for each type the value converts to
+                        for value in field_value.coercions() {
+                            possibly_interested_subs.extend(index_ref.find_matching(value));
+                        }
                     }
                 }
             }
@@ -251,7 +312,9 @@ impl Reactor {
                     let entity = &change.entity;
                     // Use evaluate_predicate directly on the entity instead of fetch_entities
                     info!("\tnotify_change predicate: {} {:?}", sub_id, subscription.predicate);
-                    let matches = ankql::selection::filter::evaluate_predicate(&**entity, &subscription.predicate).unwrap_or(false);
+                    // Deleted entities never match, so they are removed from any subscription they were in
+                    let matches = !entity.is_deleted()
+                        && ankql::selection::filter::evaluate_predicate(&**entity, &subscription.predicate).unwrap_or(false);
 
                     let did_match = subscription.matching_entities.lock().unwrap().iter().any(|r| r.id == entity.id);
                     use ankql::selection::filter::Filterable;
@@ -288,7 +351,7 @@ impl Reactor {
         for (sub_id, changes) in sub_changes {
             if let Some(subscription) = self.subscriptions.get(&sub_id) {
                 (subscription.callback)(ChangeSet {
-                    resultset: ResultSet { items: subscription.matching_entities.lock().unwrap().clone() },
+                    resultset: ResultSet::matching(subscription.matching_entities.lock().unwrap().clone(), &subscription.predicate),
                     changes,
                 });
             }
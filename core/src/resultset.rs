@@ -1,14 +1,47 @@
+use std::sync::Arc;
+
+use ankql::ast::Predicate;
+use ankql::selection::search;
+
+use crate::model::Entity;
+
 #[derive(Debug)]
 pub struct ResultSet<T> {
     pub items: Vec<T>,
+    /// The relevance of each item to the query's full-text searches, in the same order as the items.
+    /// This is None unless the query has a MATCHES predicate.
+    pub scores: Option<Vec<f64>>,
 }
 
 impl<T: Clone> Clone for ResultSet<T> {
-    fn clone(&self) -> Self { Self { items: self.items.clone() } }
+    fn clone(&self) -> Self { Self { items: self.items.clone(), scores: self.scores.clone() } }
 }
 
 impl<T> Default for ResultSet<T> {
-    fn default() -> Self { Self { items: vec![] } }
+    fn default() -> Self { Self { items: vec![], scores: None } }
+}
+
+impl<T> ResultSet<T> {
+    /// The items along with their relevance scores, if there are any
+    pub fn scored(&self) -> impl Iterator<Item = (&T, Option<f64>)> {
+        self.items.iter().enumerate().map(|(i, item)| (item, self.scores.as_ref().map(|scores| scores[i])))
+    }
+
+    pub(crate) fn map<U>(self, f: impl FnMut(T) -> U) -> ResultSet<U> {
+        ResultSet { items: self.items.into_iter().map(f).collect(), scores: self.scores }
+    }
+}
+
+impl ResultSet<Arc<Entity>> {
+    /// The entities matching a predicate, scored if it searches for anything
+    pub(crate) fn matching(items: Vec<Arc<Entity>>, predicate: &Predicate) -> Self {
+        let scores = if search::searches(predicate).is_empty() {
+            None
+        } else {
+            Some(items.iter().map(|entity| search::relevance(&**entity, predicate).unwrap_or(0.0)).collect())
+        };
+        Self { items, scores }
+    }
 }
 
 impl<T> From<ResultSet<T>> for Vec<T> {
@@ -20,3 +53,16 @@ impl<R> core::ops::Deref for ResultSet<R> {
 
     fn deref(&self) -> &Self::Target { &self.items }
 }
+
+/// One page of a paginated fetch, and the cursor to fetch the following page with if there are more results
+#[derive(Debug)]
+pub struct Page<T> {
+    pub resultset: ResultSet<T>,
+    pub next: Option<ankurah_proto::Cursor>,
+}
+
+impl<R> core::ops::Deref for Page<R> {
+    type Target = ResultSet<R>;
+
+    fn deref(&self) -> &Self::Target { &self.resultset }
+}
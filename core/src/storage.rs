@@ -1,30 +1,67 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
 
+use anyhow::anyhow;
 use async_trait::async_trait;
-use serde::{Deserialize, Serialize};
 
 use crate::error::RetrievalError;
-use ankurah_proto::{CollectionId, State, ID};
+use crate::model::Entity;
+use crate::property::PropertyName;
+use crate::value::Value;
+use ankql::ast::{Aggregation, Query};
+use ankql::selection::aggregate::{aggregate, AggregateRow};
+use ankql::selection::order::{compare_keys, sort_key};
+use ankql::selection::search::tokenize;
+use ankurah_proto::{Clock, ClockOrdering, CollectionId, Cursor, Event, NodeId, State, ID};
 
 #[async_trait]
 pub trait StorageEngine: Send + Sync {
     // Opens and/or creates a storage bucket.
     async fn collection(&self, id: &CollectionId) -> anyhow::Result<Arc<dyn StorageCollection>>;
 
-    // Fetch raw entity states matching a predicate
+    // Fetch raw entity states matching a query's predicate, sorted and windowed by its ORDER BY, LIMIT and OFFSET
     // TODO: Move this to the StorageCollection trait
-    async fn fetch_states(
+    async fn fetch_states(&self, collection_id: CollectionId, query: &ankql::ast::Query) -> Result<Vec<(ID, State)>, RetrievalError>;
+
+    // Fetch up to `limit` raw entity states matching a query's predicate, which come strictly after the cursor in the order of
+    // its ORDER BY clause with ties broken by ID. The query's own LIMIT and OFFSET are ignored.
+    // The default implementation sorts every matching state, so engines which can seek to the cursor should override it
+    async fn fetch_page(
         &self,
         collection_id: CollectionId,
-        predicate: &ankql::ast::Predicate,
-    ) -> Result<Vec<(ID, State)>, RetrievalError>;
+        query: &Query,
+        after: Option<&Cursor>,
+        limit: u64,
+    ) -> Result<Vec<(ID, State)>, RetrievalError> {
+        let unwindowed = Query { limit: None, offset: None, ..query.clone() };
+        let mut page = Vec::new();
+        for (id, state) in self.fetch_states(collection_id.clone(), &unwindowed).await? {
+            let entity = Entity::from_state(id, collection_id.clone(), &state)?;
+            let key = sort_key(&entity, &query.order_by);
+            if let Some(after) = after {
+                if compare_keys(&key, &after.key, &query.order_by).then(id.cmp(&after.id)).is_le() {
+                    continue;
+                }
+            }
+            page.push((key, id, state));
+        }
+        page.sort_by(|(a_key, a_id, _), (b_key, b_id, _)| compare_keys(a_key, b_key, &query.order_by).then(a_id.cmp(b_id)));
+        Ok(page.into_iter().take(limit as usize).map(|(_, id, state)| (id, state)).collect())
+    }
+
+    // Compute an aggregation over the entities matching its predicate, with a row for each group in ascending group order.
+    // The default implementation folds over every matching state, so engines which can aggregate natively should override it
+    async fn aggregate(&self, collection_id: CollectionId, aggregation: &Aggregation) -> Result<Vec<AggregateRow>, RetrievalError> {
+        let states = self.fetch_states(collection_id.clone(), &aggregation.predicate.clone().into()).await?;
+        let entities =
+            states.into_iter().map(|(id, state)| Entity::from_state(id, collection_id.clone(), &state)).collect::<Result<Vec<_>, _>>()?;
+        Ok(aggregate(&entities, aggregation)?)
+    }
 }
 
 #[async_trait]
 pub trait StorageCollection: Send + Sync {
-    // TODO - implement merge_states based on event history.
-    // Consider whether to play events forward from a prior checkpoint (probably this)
-    // or maybe to require PropertyBackends to be able to merge states.
+    // Merging incoming states with our own happens in the Node, so this only needs to persist the result
     async fn set_state(&self, id: ID, state: &State) -> anyhow::Result<bool>;
     async fn get_state(&self, id: ID) -> Result<State, RetrievalError>;
 
@@ -35,20 +72,63 @@ pub trait StorageCollection: Send + Sync {
         Ok(())
     }
 
-    // TODO:
-    // fn add_event(&self, entity_event: &Event) -> anyhow::Result<()>;
-    // fn get_events(&self, id: ID) -> Result<Vec<Event>, crate::error::RetrievalError>;
-}
+    /// Durably record an event in the collection's event log.
+    /// Returns true if the event was newly added, false if it was already present.
+    async fn add_event(&self, entity_event: &Event) -> anyhow::Result<bool>;
+
+    /// Retrieve all events for the given entity, ordered by event ID
+    async fn get_events(&self, id: ID) -> Result<Vec<Event>, RetrievalError>;
+
+    /// Remove the given events from the entity's event log, along with their acknowledgements
+    async fn remove_events(&self, id: ID, event_ids: &[ID]) -> anyhow::Result<()>;
+
+    /// Durably record that a durable peer has acknowledged the given events, so they may be compacted as far as that peer is concerned
+    async fn acknowledge_events(&self, peer_id: &NodeId, event_ids: &[ID]) -> anyhow::Result<()>;
+
+    /// Retrieve the peers which have acknowledged each of the given events. Events nobody has acknowledged are omitted
+    async fn get_acknowledgements(&self, event_ids: &[ID]) -> Result<BTreeMap<ID, BTreeSet<NodeId>>, RetrievalError>;
 
-#[derive(Serialize, Deserialize)]
-pub enum MaterializedTag {
-    String,
-    Number,
+    /// Store a checkpoint of the entity's state at `state.head`, replacing any prior checkpoint
+    async fn checkpoint(&self, id: ID, state: &State) -> anyhow::Result<()>;
+
+    /// Retrieve the newest checkpoint for the entity, if there is one
+    async fn get_checkpoint(&self, id: ID) -> Result<Option<State>, RetrievalError>;
+
+    /// Remove every event which `clock` descends from (inclusive) from the event log, returning how many were removed.
+    /// The newest checkpoint must cover the clock, because state can only be reconstructed from a checkpoint plus the events after it.
+    async fn compact_before(&self, id: ID, clock: &Clock) -> anyhow::Result<usize> {
+        let checkpoint = self.get_checkpoint(id).await?.ok_or_else(|| anyhow!("No checkpoint to compact entity {} against", id))?;
+        let history = self.get_events(id).await?;
+        let lineage: BTreeMap<ID, &Clock> = history.iter().map(|e| (e.id, &e.parent)).collect();
+        let parent_of = |id: &ID| lineage.get(id).copied();
+
+        match checkpoint.head.compare(clock, parent_of) {
+            ClockOrdering::Equal | ClockOrdering::Descends => {}
+            _ => return Err(anyhow!("Checkpoint {} does not cover {} for entity {}", checkpoint.head, clock, id)),
+        }
+
+        let compactable: Vec<ID> = clock.ancestry(parent_of).into_iter().filter(|event_id| lineage.contains_key(event_id)).collect();
+        if !compactable.is_empty() {
+            self.remove_events(id, &compactable).await?;
+        }
+        Ok(compactable.len())
+    }
 }
 
-pub enum Materialized {
-    String(String),
-    Number(i64),
+/// The full-text search terms in each of an entity's string properties, for engines which keep an inverted index of them to
+/// narrow down the entities a MATCHES predicate is evaluated against. Deleted entities never match, so they have none.
+pub fn search_terms(id: ID, collection_id: CollectionId, state: &State) -> Result<BTreeSet<(PropertyName, String)>, RetrievalError> {
+    if state.tombstone {
+        return Ok(BTreeSet::new());
+    }
+    let entity = Entity::from_state(id, collection_id, state)?;
+    let mut terms = BTreeSet::new();
+    for (property, value) in entity.backends().materialized() {
+        if let Value::String(text) = value {
+            terms.extend(tokenize(&text).into_iter().map(|term| (property.clone(), term)));
+        }
+    }
+    Ok(terms)
 }
 
 /// Manages the storage and state of the collection without any knowledge of the model type
@@ -68,10 +68,9 @@ impl Transaction {
         // this should probably be done in parallel, but microoptimizations
         let mut entity_events = Vec::new();
         for entity in self.entities.iter() {
-            if let Some(entity_event) = entity.commit()? {
-                if let Some(upstream) = &entity.upstream {
-                    upstream.apply_event(&entity_event)?;
-                } else {
+            if let Some(entity_event) = entity.commit(&self.node.id)? {
+                // Entities with an upstream are already resident in the node, and will receive the event when it is committed
+                if entity.upstream.is_none() {
                     self.node.insert_entity(entity.clone()).await?;
                 }
                 entity_events.push(entity_event);
@@ -87,20 +86,14 @@ impl Transaction {
         self.consumed = true; // just do nothing on drop
     }
 
-    // TODO: Implement delete functionality after core query/edit operations are stable
-    // For now, "removal" from result sets is handled by edits that cause entities to no longer match queries
-    /*
-    pub async fn delete<'rec, 'trx: 'rec, M: Model>(
-        &'trx self,
-        id: impl Into<ID>,
-    ) -> Result<(), crate::error::RetrievalError> {
+    /// Delete an entity. A tombstone event is written when the transaction is committed,
+    /// after which the entity is removed from all result sets and subscriptions.
+    pub async fn delete<M: Model>(&self, id: impl Into<proto::ID>) -> Result<(), crate::error::RetrievalError> {
         let id = id.into();
-        let entity = self.fetch_entity(id, M::collection()).await?;
-        let entity = Arc::new(entity.clone());
-        self.node.delete_entity(entity).await?;
+        let entity = self.get_entity(id, &M::collection()).await?;
+        entity.delete();
         Ok(())
     }
-    */
 }
 
 impl Drop for Transaction {
@@ -1,14 +1,114 @@
+use ankql::ast::Literal;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
 use crate::collation::Collatable;
 
 /// A dynamically typed value
 /// This is a short term expedience. Ideally we would NOT have one canonical set of types, but rather a pairwise mapping between the
 /// storage engine types and the backend types.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Value {
     String(String),
     Integer(i64),
     Float(f64),
     Boolean(bool),
+    /// Anything which isn't a scalar, bincode encoded
+    Bytes(Vec<u8>),
+    Null,
+}
+
+impl Value {
+    /// Encode any serde value. Scalars become the matching variant, so they can be queried and indexed,
+    /// and anything else is bincode encoded as `Bytes`.
+    pub fn encode<T: Serialize + DeserializeOwned>(value: &T) -> anyhow::Result<Value> {
+        let scalar = match serde_json::to_value(value) {
+            Ok(serde_json::Value::String(string)) => Some(Value::String(string)),
+            Ok(serde_json::Value::Bool(boolean)) => Some(Value::Boolean(boolean)),
+            Ok(serde_json::Value::Number(number)) => number.as_i64().map(Value::Integer).or_else(|| {
+                // Integers beyond i64 don't fit, but floats always do
+                if number.is_f64() {
+                    number.as_f64().map(Value::Float)
+                } else {
+                    None
+                }
+            }),
+            // NaN also serializes as null, and must not be decoded as None
+            Ok(serde_json::Value::Null) if serde_json::from_value::<T>(serde_json::Value::Null).is_ok() => Some(Value::Null),
+            _ => None,
+        };
+        match scalar {
+            Some(scalar) => Ok(scalar),
+            None => Ok(Value::Bytes(bincode::serialize(value)?)),
+        }
+    }
+
+    /// The value as a query literal, for predicate evaluation. Null has no literal, and bytes are compared as text
+    pub fn to_literal(&self) -> Option<Literal> {
+        match self {
+            Value::String(string) => Some(Literal::String(string.clone())),
+            Value::Integer(integer) => Some(Literal::Integer(*integer)),
+            Value::Float(float) => Some(Literal::Float(*float)),
+            Value::Boolean(boolean) => Some(Literal::Boolean(*boolean)),
+            Value::Bytes(bytes) => Some(Literal::String(String::from_utf8_lossy(bytes).to_string())),
+            Value::Null => None,
+        }
+    }
+
+    /// The values of other types which this value is comparable with under the coercion rules of
+    /// `ankql::selection::filter::compare_values`, so that index lookups find literals of any of those types.
+    /// Floats are represented among integers by the integers either side of them, and numbers and booleans are represented
+    /// among strings by their canonical text.
+    pub fn coercions(&self) -> Vec<Value> {
+        let mut values = vec![self.clone()];
+        match self {
+            Value::String(string) => {
+                let string = string.trim();
+                values.extend(string.parse().ok().map(Value::Integer));
+                values.extend(string.parse().ok().map(Value::Float));
+                values.extend(string.parse().ok().map(Value::Boolean));
+            }
+            Value::Integer(integer) => {
+                values.push(Value::Float(*integer as f64));
+                values.push(Value::String(integer.to_string()));
+            }
+            Value::Float(float) => {
+                if float.is_finite() {
+                    values.push(Value::Integer(float.floor() as i64));
+                    values.push(Value::Integer(float.ceil() as i64));
+                }
+                values.push(Value::String(float.to_string()));
+            }
+            Value::Boolean(boolean) => values.push(Value::String(boolean.to_string())),
+            _ => {}
+        }
+        values
+    }
+
+    /// Decode a value which was encoded with `encode`
+    pub fn decode<T: DeserializeOwned>(&self) -> anyhow::Result<T> {
+        let json = match self {
+            Value::Bytes(bytes) => return Ok(bincode::deserialize(bytes)?),
+            Value::String(string) => serde_json::Value::String(string.clone()),
+            Value::Integer(integer) => serde_json::Value::from(*integer),
+            Value::Float(float) => serde_json::Value::from(*float),
+            Value::Boolean(boolean) => serde_json::Value::Bool(*boolean),
+            Value::Null => serde_json::Value::Null,
+        };
+        Ok(serde_json::from_value(json)?)
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::String(string) => write!(f, "{}", string),
+            Value::Integer(integer) => write!(f, "{}", integer),
+            Value::Float(float) => write!(f, "{}", float),
+            Value::Boolean(boolean) => write!(f, "{}", boolean),
+            Value::Bytes(bytes) => write!(f, "{}", String::from_utf8_lossy(bytes)),
+            Value::Null => write!(f, "null"),
+        }
+    }
 }
 
 impl Collatable for Value {
@@ -30,6 +130,8 @@ impl Collatable for Value {
                 bits.to_be_bytes().to_vec()
             }
             Value::Boolean(b) => vec![*b as u8],
+            Value::Bytes(bytes) => bytes.clone(),
+            Value::Null => Vec::new(),
         }
     }
 
@@ -69,6 +171,12 @@ impl Collatable for Value {
                     Some(vec![1])
                 }
             }
+            Value::Bytes(bytes) => {
+                let mut bytes = bytes.clone();
+                bytes.push(0);
+                Some(bytes)
+            }
+            Value::Null => None,
         }
     }
 
@@ -105,6 +213,14 @@ impl Collatable for Value {
                     None
                 }
             }
+            Value::Bytes(bytes) => {
+                if bytes.is_empty() {
+                    None
+                } else {
+                    Some(bytes[..bytes.len() - 1].to_vec())
+                }
+            }
+            Value::Null => None,
         }
     }
 
@@ -114,6 +230,8 @@ impl Collatable for Value {
             Value::Integer(i) => *i == i64::MIN,
             Value::Float(f) => *f == f64::NEG_INFINITY,
             Value::Boolean(b) => !b,
+            Value::Bytes(bytes) => bytes.is_empty(),
+            Value::Null => true,
         }
     }
 
@@ -123,6 +241,8 @@ impl Collatable for Value {
             Value::Integer(i) => *i == i64::MAX,
             Value::Float(f) => *f == f64::INFINITY,
             Value::Boolean(b) => *b,
+            Value::Bytes(_) => false,
+            Value::Null => true,
         }
     }
 }
@@ -69,7 +69,7 @@ pub fn derive_model_impl(input: TokenStream) -> TokenStream {
 
                 let backends = ankurah::property::Backends::new();
                 #(
-                    #active_field_types::initialize_with(&backends, #active_field_name_strs.into(), &self.#active_field_names);
+                    <#active_field_types>::initialize_with(&backends, #active_field_name_strs.into(), &self.#active_field_names);
                 )*
                 ::ankurah::model::Entity::create(
                     id,
@@ -125,6 +125,12 @@ pub fn derive_model_impl(input: TokenStream) -> TokenStream {
                 // TODO - get rid of this in favor of directly cloning the entity of the ModelView struct
                 trx.edit::<#name>(self.id()).await
             }
+
+            /// This entity as it was at the given clock. See `Node::get_at`
+            pub async fn as_of(&self, node: &::ankurah::Node, clock: &ankurah::derive_deps::ankurah_proto::Clock) -> Result<Self, ankurah::error::RetrievalError> {
+                use ::ankurah::model::View;
+                node.get_at::<Self>(self.id(), clock).await
+            }
         }
 
         #wasm_attributes
@@ -135,7 +141,7 @@ pub fn derive_model_impl(input: TokenStream) -> TokenStream {
             #(
                 #active_field_visibility fn #active_field_names(&self) -> #projected_field_types {
                     use ankurah::property::ProjectedValue;
-                    #active_field_types::from_backends(#active_field_name_strs.into(), self.entity.backends()).projected()
+                    <#active_field_types>::from_backends(#active_field_name_strs.into(), self.entity.backends()).projected()
                 }
             )*
             // #(
@@ -164,7 +170,7 @@ pub fn derive_model_impl(input: TokenStream) -> TokenStream {
                 assert_eq!(entity.collection, Self::collection());
                 Self {
                     entity,
-                    #( #active_field_names: #active_field_types::from_backends(#active_field_name_strs.into(), entity.backends()), )*
+                    #( #active_field_names: <#active_field_types>::from_backends(#active_field_name_strs.into(), entity.backends()), )*
                 }
             }
         }
@@ -198,6 +204,10 @@ pub fn derive_model_impl(input: TokenStream) -> TokenStream {
 }
 
 static ACTIVE_TYPE_MOD_PREFIX: &str = "::ankurah::property::value";
+/// Active types which are generic over the projected type, and so are parameterized with the field's type
+static GENERIC_ACTIVE_TYPES: &[&str] = &["LWW", "MVRegister", "PNCounter", "GCounter"];
+/// Active collection types, which are parameterized with the element type of the field's collection type
+static ELEMENT_ACTIVE_TYPES: &[&str] = &["YrsMap", "YrsArray", "ORSet", "List"];
 fn get_active_type(field: &syn::Field) -> Result<syn::Path, syn::Error> {
     let active_type_ident = format_ident!("active_type");
 
@@ -213,7 +223,17 @@ fn get_active_type(field: &syn::Field) -> Result<syn::Path, syn::Error> {
         };
 
         if !value_str.contains("::") {
-            let path = format!("{}::{}", ACTIVE_TYPE_MOD_PREFIX, value_str);
+            let path = if GENERIC_ACTIVE_TYPES.contains(&value_str.as_str()) {
+                let field_ty = &field.ty;
+                format!("{}::{}<{}>", ACTIVE_TYPE_MOD_PREFIX, value_str, quote!(#field_ty))
+            } else if ELEMENT_ACTIVE_TYPES.contains(&value_str.as_str()) {
+                let element_ty = get_element_type(&field.ty).ok_or_else(|| {
+                    syn::Error::new_spanned(&field.ty, format!("{} requires a collection type such as Vec<T>", value_str))
+                })?;
+                format!("{}::{}<{}>", ACTIVE_TYPE_MOD_PREFIX, value_str, quote!(#element_ty))
+            } else {
+                format!("{}::{}", ACTIVE_TYPE_MOD_PREFIX, value_str)
+            };
             return syn::parse_str(&path).map_err(|_| syn::Error::new_spanned(active_type, "Failed to parse active_type path"));
         }
         return syn::parse_str(&value_str).map_err(|_| syn::Error::new_spanned(active_type, "Failed to parse active_type path"));
@@ -246,6 +266,23 @@ fn get_active_type(field: &syn::Field) -> Result<syn::Path, syn::Error> {
     ))
 }
 
+/// The last type argument of a collection type, ie `V` in `Vec<V>` or `BTreeMap<String, V>`
+fn get_element_type(ty: &Type) -> Option<&Type> {
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+    let syn::PathArguments::AngleBracketed(args) = &type_path.path.segments.last()?.arguments else {
+        return None;
+    };
+    args.args
+        .iter()
+        .filter_map(|arg| match arg {
+            syn::GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        })
+        .next_back()
+}
+
 fn get_model_flag(attrs: &Vec<syn::Attribute>, flag_name: &str) -> bool {
     attrs.iter().any(|attr| {
         attr.path().segments.iter().any(|seg| seg.ident == "model")
@@ -26,7 +26,7 @@ pub async fn create_client() -> Result<WebsocketClient, JsValue> {
     Ok(connector)
 }
 
-use ankurah::{changes::ChangeSet, ResultSet, WasmSignal};
+use ankurah::{changes::ChangeSet, Cursor, Page, ResultSet, WasmSignal};
 #[wasm_bindgen]
 pub async fn fetch_test_items(client: &WebsocketClient) -> Result<Vec<SessionView>, JsValue> {
     let sessions: ResultSet<SessionView> =
@@ -34,6 +34,30 @@ pub async fn fetch_test_items(client: &WebsocketClient) -> Result<Vec<SessionVie
     Ok(sessions.into())
 }
 
+/// One page of test items, and the cursor to pass back to `fetch_test_items_page` for the following page
+#[wasm_bindgen]
+pub struct TestItemsPage {
+    items: Vec<SessionView>,
+    next: Option<String>,
+}
+
+#[wasm_bindgen]
+impl TestItemsPage {
+    pub fn items(&self) -> Vec<SessionView> { self.items.clone() }
+    pub fn next(&self) -> Option<String> { self.next.clone() }
+}
+
+#[wasm_bindgen]
+pub async fn fetch_test_items_page(client: &WebsocketClient, after: Option<String>, limit: u32) -> Result<TestItemsPage, JsValue> {
+    let after = after.map(Cursor::try_from).transpose().map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let page: Page<SessionView> = client
+        .node()
+        .fetch_page("date_connected = '2024-01-01' ORDER BY ip_address", after, limit as u64)
+        .await
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    Ok(TestItemsPage { items: page.resultset.items, next: page.next.map(|next| next.to_string()) })
+}
+
 #[wasm_bindgen]
 pub fn subscribe_test_items(client: &WebsocketClient) -> Result<TestResultSetSignal, JsValue> {
     let (signal, rwsignal) = reactive_graph::signal::RwSignal::new(TestResultSet::default()).split();
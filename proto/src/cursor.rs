@@ -0,0 +1,46 @@
+use ankql::ast::Literal;
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::{DecodeError, ID};
+
+/// The position of the last entity of a page of fetch results, from which the next page continues.
+/// It records the values the entity was sorted by along with its ID, which breaks ties, so that entities inserted or
+/// removed between pages can't cause the next page to skip or repeat any others.
+/// Clients should treat it as opaque, passing it around in its string form.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cursor {
+    /// The values of the ORDER BY properties of the last entity, in clause order
+    pub key: Vec<Option<Literal>>,
+    /// The ID of the last entity
+    pub id: ID,
+}
+
+impl Cursor {
+    pub fn new(key: Vec<Option<Literal>>, id: ID) -> Self { Self { key, id } }
+
+    pub fn to_base64(&self) -> String {
+        // Serializing literals and an ID can't fail
+        general_purpose::URL_SAFE_NO_PAD.encode(bincode::serialize(self).expect("Failed to serialize cursor"))
+    }
+
+    pub fn from_base64(base64_string: &str) -> Result<Self, DecodeError> {
+        let decoded = general_purpose::URL_SAFE_NO_PAD.decode(base64_string).map_err(DecodeError::InvalidBase64)?;
+        bincode::deserialize(&decoded).map_err(|e| DecodeError::Other(anyhow::anyhow!("Invalid cursor: {}", e)))
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{}", self.to_base64()) }
+}
+
+impl TryFrom<&str> for Cursor {
+    type Error = DecodeError;
+    fn try_from(cursor: &str) -> Result<Self, Self::Error> { Self::from_base64(cursor) }
+}
+
+impl TryFrom<String> for Cursor {
+    type Error = DecodeError;
+    fn try_from(cursor: String) -> Result<Self, Self::Error> { Self::from_base64(&cursor) }
+}
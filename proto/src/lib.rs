@@ -1,12 +1,15 @@
+pub mod cursor;
 pub mod human_id;
 pub mod message;
 // pub mod entity;
 pub mod entity_id;
 
+pub use cursor::Cursor;
 pub use human_id::*;
 pub use message::*;
 // pub use entity::*;
 use ankql::ast;
+use ankql::selection::aggregate::AggregateRow;
 pub use entity_id::ID;
 
 use serde::{Deserialize, Serialize};
@@ -99,6 +102,14 @@ impl Default for NodeId {
 
 impl NodeId {
     pub fn new() -> Self { Self(Ulid::new()) }
+
+    pub fn from_ulid(ulid: Ulid) -> Self { Self(ulid) }
+
+    pub fn to_bytes(&self) -> [u8; 16] { self.0.to_bytes() }
+}
+
+impl From<NodeId> for Ulid {
+    fn from(node_id: NodeId) -> Self { node_id.0 }
 }
 
 impl Default for RequestId {
@@ -156,6 +167,8 @@ pub struct Event {
     pub operations: BTreeMap<String, Vec<Operation>>,
     /// The set of concurrent events (usually only one) which is the precursor of this event
     pub parent: Clock,
+    /// Deletes the entity. Tombstones are permanent, and win over any concurrent edits
+    pub tombstone: bool,
 }
 
 /// S set of event ids which create a dag of events
@@ -176,9 +189,58 @@ impl Clock {
 
     pub fn insert(&mut self, id: ID) { self.0.insert(id); }
 
+    pub fn contains(&self, id: &ID) -> bool { self.0.contains(id) }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ID> { self.0.iter() }
+
     pub fn len(&self) -> usize { self.0.len() }
 
     pub fn is_empty(&self) -> bool { self.0.is_empty() }
+
+    /// All event ids reachable from this clock, inclusive of the clock's own ids.
+    /// `parent_of` looks up the parent clock of an event. Events it doesn't know about are treated as roots.
+    pub fn ancestry<'a>(&self, parent_of: impl Fn(&ID) -> Option<&'a Clock>) -> BTreeSet<ID> {
+        let mut visited = BTreeSet::new();
+        let mut frontier: Vec<ID> = self.0.iter().cloned().collect();
+        while let Some(id) = frontier.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            if let Some(parent) = parent_of(&id) {
+                frontier.extend(parent.0.iter().filter(|p| !visited.contains(*p)).cloned());
+            }
+        }
+        visited
+    }
+
+    /// Determine the causal relationship of this clock to another, walking the event DAG via `parent_of`
+    pub fn compare<'a>(&self, other: &Clock, parent_of: impl Fn(&ID) -> Option<&'a Clock>) -> ClockOrdering {
+        if self == other {
+            return ClockOrdering::Equal;
+        }
+        let ours = self.ancestry(&parent_of);
+        let theirs = other.ancestry(&parent_of);
+        if other.0.iter().all(|id| ours.contains(id)) {
+            ClockOrdering::Descends
+        } else if self.0.iter().all(|id| theirs.contains(id)) {
+            ClockOrdering::Ancestor
+        } else {
+            ClockOrdering::Concurrent
+        }
+    }
+}
+
+/// The causal relationship between two clocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockOrdering {
+    /// Both clocks refer to the same events
+    Equal,
+    /// Every event in the other clock is an ancestor of this one
+    Descends,
+    /// Every event in this clock is an ancestor of the other one
+    Ancestor,
+    /// Neither clock descends from the other
+    Concurrent,
 }
 
 impl From<Vec<Uuid>> for Clock {
@@ -211,11 +273,12 @@ impl std::fmt::Display for Event {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Event({} {}/{} {} {})",
+            "Event({} {}/{} {}{} {})",
             self.id,
             self.collection,
             self.entity_id,
             self.parent,
+            if self.tombstone { " tombstone" } else { "" },
             self.operations
                 .iter()
                 .map(|(backend, ops)| format!("{} => {}b", backend, ops.iter().map(|op| op.diff.len()).sum::<usize>()))
@@ -240,14 +303,17 @@ pub struct State {
     pub state_buffers: BTreeMap<String, Vec<u8>>,
     /// The set of concurrent events (usually only one) which have been applied to the entity state above
     pub head: Clock,
+    /// Whether a tombstone event has been applied to the entity
+    pub tombstone: bool,
 }
 
 impl std::fmt::Display for State {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "State(clock {} buffers {})",
+            "State(clock {}{} buffers {})",
             self.head,
+            if self.tombstone { " tombstone" } else { "" },
             self.state_buffers.iter().map(|(backend, buf)| format!("{} => {}b", backend, buf.len())).collect::<Vec<_>>().join(" ")
         )
     }
@@ -257,9 +323,15 @@ impl std::fmt::Display for State {
 pub enum NodeRequestBody {
     // Events to be committed on the remote node
     CommitEvents(Vec<Event>),
-    // Request to fetch entities matching a predicate
-    Fetch { collection: CollectionId, predicate: ast::Predicate },
+    // Request to fetch entities matching a query, in the query's order and window
+    Fetch { collection: CollectionId, query: ast::Query },
+    // Request to fetch one page of entities matching a query, continuing from the end of the previous page
+    FetchPage { collection: CollectionId, query: ast::Query, after: Option<Cursor>, limit: u64 },
     Subscribe { collection: CollectionId, predicate: ast::Predicate },
+    // Request to compute aggregates over the entities matching a selection
+    Aggregate { collection: CollectionId, aggregation: ast::Aggregation },
+    // Request for an entity's recorded events which aren't in the ancestry of the given clock
+    FetchEvents { collection: CollectionId, entity_id: ID, since: Clock },
     Unsubscribe { subscription_id: SubscriptionId },
 }
 
@@ -269,12 +341,22 @@ impl std::fmt::Display for NodeRequestBody {
             NodeRequestBody::CommitEvents(events) => {
                 write!(f, "CommitEvents [{}]", events.iter().map(|e| format!("{}", e)).collect::<Vec<_>>().join(", "))
             }
-            NodeRequestBody::Fetch { collection, predicate } => {
-                write!(f, "Fetch {collection} {predicate}")
+            NodeRequestBody::Fetch { collection, query } => {
+                write!(f, "Fetch {collection} {query}")
             }
+            NodeRequestBody::FetchPage { collection, query, after, limit } => match after {
+                Some(after) => write!(f, "FetchPage {collection} {query} after {after} limit {limit}"),
+                None => write!(f, "FetchPage {collection} {query} limit {limit}"),
+            },
             NodeRequestBody::Subscribe { collection, predicate } => {
                 write!(f, "Subscribe {collection} {predicate}")
             }
+            NodeRequestBody::Aggregate { collection, aggregation } => {
+                write!(f, "Aggregate {collection} {aggregation}")
+            }
+            NodeRequestBody::FetchEvents { collection, entity_id, since } => {
+                write!(f, "FetchEvents {collection}/{entity_id} since {since}")
+            }
             NodeRequestBody::Unsubscribe { subscription_id } => {
                 write!(f, "Unsubscribe {subscription_id}")
             }
@@ -287,7 +369,11 @@ pub enum NodeResponseBody {
     // Response to CommitEvents
     CommitComplete,
     Fetch(Vec<(ID, State)>),
+    // One page of states in the query's order, and the cursor for the next page if there are more
+    FetchPage { states: Vec<(ID, State)>, next: Option<Cursor> },
     Subscribe { initial: Vec<(ID, State)>, subscription_id: SubscriptionId },
+    Aggregate(Vec<AggregateRow>),
+    Events(Vec<Event>),
     Success,
     Error(String),
 }
@@ -299,12 +385,22 @@ impl std::fmt::Display for NodeResponseBody {
             NodeResponseBody::Fetch(tuples) => {
                 write!(f, "Fetch [{}]", tuples.iter().map(|(id, _)| id.to_string()).collect::<Vec<_>>().join(", "))
             }
+            NodeResponseBody::FetchPage { states, next } => write!(
+                f,
+                "FetchPage [{}]{}",
+                states.iter().map(|(id, _)| id.to_string()).collect::<Vec<_>>().join(", "),
+                next.as_ref().map(|next| format!(" next {next}")).unwrap_or_default()
+            ),
             NodeResponseBody::Subscribe { initial, subscription_id } => write!(
                 f,
                 "Subscribe {} initial [{}]",
                 subscription_id,
                 initial.iter().map(|(id, state)| format!("{} {}", id, state)).collect::<Vec<_>>().join(", ")
             ),
+            NodeResponseBody::Aggregate(rows) => write!(f, "Aggregate {} rows", rows.len()),
+            NodeResponseBody::Events(events) => {
+                write!(f, "Events [{}]", events.iter().map(|e| e.id.to_string()).collect::<Vec<_>>().join(", "))
+            }
             NodeResponseBody::Success => write!(f, "Success"),
             NodeResponseBody::Error(e) => write!(f, "Error: {e}"),
         }
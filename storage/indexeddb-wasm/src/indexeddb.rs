@@ -1,7 +1,11 @@
-use ankql::selection::filter::evaluate_predicate;
+use ankql::selection::{
+    filter::evaluate_predicate,
+    order::order_and_window,
+    search::{required_searches, searches, tokenize},
+};
 use ankurah_core::error::RetrievalError;
 use ankurah_core::model::Entity;
-use ankurah_core::storage::{StorageCollection, StorageEngine};
+use ankurah_core::storage::{search_terms, StorageCollection, StorageEngine};
 use ankurah_proto as proto;
 use anyhow::Result;
 use async_trait::async_trait;
@@ -9,6 +13,7 @@ use futures::StreamExt;
 use js_sys::Function;
 use send_wrapper::SendWrapper;
 use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::atomic::AtomicUsize;
 use std::sync::Arc;
 use tracing::info;
@@ -17,6 +22,9 @@ use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{Event, IdbDatabase, IdbFactory, IdbOpenDbRequest, IdbRequest, IdbVersionChangeEvent};
 
+/// Bump this whenever the object stores or indexes change, and add the migration to `onupgradeneeded`
+const DB_VERSION: u32 = 6;
+
 pub struct IndexedDBStorageEngine {
     // We need SendWrapper because despite the ability to declare an async trait as ?Send,
     // we can't actually define StorageEngine and StorageCollection as optionally Send or !Send.
@@ -50,26 +58,98 @@ impl IndexedDBStorageEngine {
             .map_err(|e| anyhow::anyhow!("IndexedDB error: {:?}", e))?
             .ok_or_else(|| anyhow::anyhow!("IndexedDB not available"))?;
 
-        let open_request: IdbOpenDbRequest = idb.open_with_u32(name, 1).map_err(|e| anyhow::anyhow!("Failed to open DB: {:?}", e))?;
+        let open_request: IdbOpenDbRequest =
+            idb.open_with_u32(name, DB_VERSION).map_err(|e| anyhow::anyhow!("Failed to open DB: {:?}", e))?;
 
         let mut callbacks: Vec<Box<dyn Any>> = Vec::new();
         let promise = js_sys::Promise::new(&mut |resolve: Function, reject: Function| {
             let onupgradeneeded = Closure::wrap(Box::new(move |event: IdbVersionChangeEvent| {
                 let target: IdbRequest = event.target().unwrap().unchecked_into();
                 let db: SendWrapper<IdbDatabase> = SendWrapper::new(target.result().unwrap().unchecked_into());
+                let old_version = event.old_version() as u32;
+
+                if old_version < 1 {
+                    // Create entities store with index on collection
+                    match db.create_object_store("entities") {
+                        Ok(store) => {
+                            // Create index on collection field
+                            if let Err(e) = store.create_index_with_str("by_collection", "collection") {
+                                tracing::error!("Failed to create collection index: {:?}", e);
+                            }
+                        }
+                        Err(e) => tracing::warn!("Error creating store (may already exist): {:?}", e),
+                    }
+                }
+
+                if old_version < 2 {
+                    // Create events store with index on entity id
+                    match db.create_object_store("events") {
+                        Ok(store) => {
+                            if let Err(e) = store.create_index_with_str("by_entity", "entity_id") {
+                                tracing::error!("Failed to create entity index: {:?}", e);
+                            }
+                        }
+                        Err(e) => tracing::warn!("Error creating events store (may already exist): {:?}", e),
+                    }
+                }
 
-                // Create entities store with index on collection
-                let store = match db.create_object_store("entities") {
-                    Ok(store) => store,
-                    Err(e) => {
-                        tracing::warn!("Error creating store (may already exist): {:?}", e);
-                        return;
+                if old_version < 3 {
+                    // Create checkpoints store, keyed by entity id
+                    if let Err(e) = db.create_object_store("checkpoints") {
+                        tracing::warn!("Error creating checkpoints store (may already exist): {:?}", e);
                     }
-                };
+                }
+
+                if old_version < 4 {
+                    // Create an inverted index of the full-text search terms of each entity, which are stored as an array of
+                    // term keys. Entities written before this version are indexed by the upgrade to version 6.
+                    let store = target.transaction().and_then(|transaction| transaction.object_store("entities").ok());
+                    match store {
+                        Some(store) => {
+                            let parameters = web_sys::IdbIndexParameters::new();
+                            parameters.set_multi_entry(true);
+                            if let Err(e) = store.create_index_with_str_and_optional_parameters("by_term", "terms", &parameters) {
+                                tracing::error!("Failed to create term index: {:?}", e);
+                            }
+                        }
+                        None => tracing::error!("Failed to get entities store to create term index"),
+                    }
+                }
 
-                // Create index on collection field
-                if let Err(e) = store.create_index_with_str("by_collection", "collection") {
-                    tracing::error!("Failed to create collection index: {:?}", e);
+                if old_version < 5 {
+                    // Create acknowledgements store, keyed by event id and node id
+                    if let Err(e) = db.create_object_store("acknowledgements") {
+                        tracing::warn!("Error creating acknowledgements store (may already exist): {:?}", e);
+                    }
+                }
+
+                if old_version < 6 {
+                    // Index the terms of the entities which were written before there was a term index, which the upgrade
+                    // transaction waits for
+                    let store = target.transaction().and_then(|transaction| transaction.object_store("entities").ok());
+                    match store.map(|store| store.open_cursor()) {
+                        Some(Ok(request)) => {
+                            let onsuccess = Closure::wrap(Box::new(move |event: Event| {
+                                let request: IdbRequest = event.target().unwrap().unchecked_into();
+                                // The result is null once every entity has been visited
+                                let Ok(cursor) = request.result().and_then(|result| result.dyn_into::<web_sys::IdbCursorWithValue>())
+                                else {
+                                    return;
+                                };
+                                if let Err(e) = index_entity_terms(&cursor) {
+                                    tracing::error!("Failed to index the terms of an entity: {:?}", e);
+                                }
+                                if let Err(e) = cursor.continue_() {
+                                    tracing::error!("Failed to advance cursor: {:?}", e);
+                                }
+                            }) as Box<dyn FnMut(_)>);
+                            request.set_onsuccess(Some(onsuccess.as_ref().unchecked_ref()));
+                            // Upgrades only happen once, so the closure lives for as long as the cursor
+                            onsuccess.forget();
+                        }
+                        Some(Err(e)) => tracing::error!("Failed to open cursor to index terms: {:?}", e),
+                        None => tracing::error!("Failed to get entities store to index terms"),
+                    }
                 }
             }) as Box<dyn FnMut(_)>);
 
@@ -119,6 +199,52 @@ impl IndexedDBStorageEngine {
     }
 }
 
+/// The key of a peer's acknowledgement of an event
+fn acknowledgement_key(event_id: proto::ID, peer_id: &proto::NodeId) -> String {
+    format!("{}/{}", event_id.as_string(), String::from(peer_id.clone()))
+}
+
+/// The keys of every acknowledgement of the event. IDs are URL-safe base64, so they never contain the separator
+fn acknowledgement_range(event_id: proto::ID) -> anyhow::Result<web_sys::IdbKeyRange> {
+    let event_id = event_id.as_string();
+    web_sys::IdbKeyRange::bound(&format!("{}/", event_id).into(), &format!("{}/{}", event_id, '\u{ffff}').into())
+        .map_err(|_e| anyhow::anyhow!("Failed to create key range"))
+}
+
+/// The key of a full-text search term in the `by_term` index, which spans every collection
+fn term_key(collection_id: &proto::CollectionId, property: &str, term: &str) -> String {
+    format!("{}\0{}\0{}", collection_id.as_str(), property, term)
+}
+
+/// The keys of an entity's search terms, which the by_term index is built from
+fn entity_terms(id: proto::ID, collection_id: &proto::CollectionId, state: &proto::State) -> Result<js_sys::Array, RetrievalError> {
+    Ok(search_terms(id, collection_id.clone(), state)?
+        .iter()
+        .map(|(property, term)| JsValue::from(term_key(collection_id, property, term)))
+        .collect())
+}
+
+/// Set the terms of the entity at the cursor from its state
+fn index_entity_terms(cursor: &web_sys::IdbCursorWithValue) -> anyhow::Result<()> {
+    let entity = cursor.value().map_err(|e| anyhow::anyhow!("Failed to get cursor value: {:?}", e))?;
+    let get = |name: &str| js_sys::Reflect::get(&entity, &name.into()).map_err(|_e| anyhow::anyhow!("Failed to get {}", name));
+
+    let id: proto::ID = get("id")?.try_into().map_err(|_e| anyhow::anyhow!("Failed to convert id to proto::ID"))?;
+    let collection_id: proto::CollectionId =
+        get("collection")?.as_string().ok_or_else(|| anyhow::anyhow!("Failed to get collection"))?.as_str().into();
+    let array: js_sys::Uint8Array = get("state_buffer")?.dyn_into().map_err(|_e| anyhow::anyhow!("Failed to convert state buffer"))?;
+    let state_buffers: BTreeMap<String, Vec<u8>> = bincode::deserialize(&array.to_vec())?;
+    let head: proto::Clock = get("head")?.try_into().map_err(|e| anyhow::anyhow!("Failed to deserialize head: {}", e))?;
+    // Entities written before tombstones existed have none
+    let tombstone = get("tombstone")?.as_bool().unwrap_or(false);
+    let state = proto::State { state_buffers, head, tombstone };
+
+    js_sys::Reflect::set(&entity, &"terms".into(), &entity_terms(id, &collection_id, &state)?.into())
+        .map_err(|_e| anyhow::anyhow!("Failed to set terms on entity"))?;
+    cursor.update(&entity).map_err(|e| anyhow::anyhow!("Failed to update entity: {:?}", e))?;
+    Ok(())
+}
+
 #[async_trait]
 impl StorageEngine for IndexedDBStorageEngine {
     async fn collection(&self, collection_id: &proto::CollectionId) -> anyhow::Result<Arc<dyn StorageCollection>> {
@@ -133,17 +259,34 @@ impl StorageEngine for IndexedDBStorageEngine {
     async fn fetch_states(
         &self,
         collection_id: proto::CollectionId,
-        predicate: &ankql::ast::Predicate,
+        query: &ankql::ast::Query,
     ) -> Result<Vec<(proto::ID, proto::State)>, RetrievalError> {
+        // Without an ORDER BY or a search to rank by, results are in cursor order, so the scan can stop as soon as the window is filled
+        let enough = match query.limit {
+            Some(limit) if query.order_by.is_empty() && searches(&query.predicate).is_empty() => {
+                (query.offset.unwrap_or(0) + limit) as usize
+            }
+            _ => usize::MAX,
+        };
+
         SendWrapper::new(async move {
             let transaction = self.db.transaction_with_str("entities").map_err(|_e| anyhow::anyhow!("Failed to create transaction"))?;
 
             let store = transaction.object_store("entities").map_err(|_e| anyhow::anyhow!("Failed to get object store"))?;
 
-            let index = store.index("by_collection").map_err(|_e| anyhow::anyhow!("Failed to get collection index"))?;
+            // A full-text search only needs to look at the entities with one of its terms, and otherwise we scan the whole collection
+            let (index_name, key) = match required_searches(&query.predicate).first() {
+                Some((property, search)) => match tokenize(search).first() {
+                    Some(term) => ("by_term", term_key(&collection_id, property, term)),
+                    // A search without any terms matches nothing
+                    None => return Ok(Vec::new()),
+                },
+                None => ("by_collection", collection_id.as_str().to_string()),
+            };
 
-            let key_range = web_sys::IdbKeyRange::only(&(&collection_id).as_str().into())
-                .map_err(|_e| anyhow::anyhow!("Failed to create key range"))?;
+            let index = store.index(index_name).map_err(|_e| anyhow::anyhow!("Failed to get {} index", index_name))?;
+
+            let key_range = web_sys::IdbKeyRange::only(&key.into()).map_err(|_e| anyhow::anyhow!("Failed to create key range"))?;
 
             let request = index.open_cursor_with_range(&key_range).map_err(|_e| anyhow::anyhow!("Failed to open cursor"))?;
 
@@ -162,6 +305,14 @@ impl StorageEngine for IndexedDBStorageEngine {
 
                 let entity = cursor.value().map_err(|e| anyhow::anyhow!("Failed to get cursor value: {:?}", e))?;
 
+                // Deleted entities are retained so their tombstones can be merged, but never match
+                let tombstone =
+                    js_sys::Reflect::get(&entity, &"tombstone".into()).map_err(|_e| anyhow::anyhow!("Failed to get tombstone"))?;
+                if tombstone.as_bool().unwrap_or(false) {
+                    cursor.continue_().map_err(|_e| anyhow::anyhow!("Failed to advance cursor"))?;
+                    continue;
+                }
+
                 let id_str = js_sys::Reflect::get(&entity, &"id".into()).map_err(|_e| anyhow::anyhow!("Failed to get entity id"))?;
                 let id: proto::ID = id_str.try_into().map_err(|_e| anyhow::anyhow!("Failed to convert id to proto::ID"))?;
 
@@ -178,20 +329,24 @@ impl StorageEngine for IndexedDBStorageEngine {
                 let head_data = js_sys::Reflect::get(&entity, &"head".into()).map_err(|_e| anyhow::anyhow!("Failed to get head"))?;
                 let head: proto::Clock = head_data.try_into().map_err(|e| anyhow::anyhow!("Failed to deserialize head: {}", e))?;
 
-                let entity_state = proto::State { state_buffers, head };
+                let entity_state = proto::State { state_buffers, head, tombstone: false };
 
                 // Create entity to evaluate predicate
                 let entity = Entity::from_state(id, collection_id.clone(), &entity_state)?;
 
                 // Apply predicate filter
-                if evaluate_predicate(&entity, predicate)? {
-                    tuples.push((id, entity_state));
+                if evaluate_predicate(&entity, &query.predicate)? {
+                    tuples.push((id, entity_state, entity));
+                    if tuples.len() >= enough {
+                        break;
+                    }
                 }
 
                 cursor.continue_().map_err(|_e| anyhow::anyhow!("Failed to advance cursor"))?;
             }
 
-            Ok(tuples)
+            let tuples = order_and_window(tuples, query, |(_, _, entity)| entity);
+            Ok(tuples.into_iter().map(|(id, state, _)| (id, state)).collect())
         })
         .await
     }
@@ -264,6 +419,12 @@ impl StorageCollection for IndexedDBBucket {
             js_sys::Reflect::set(&entity, &"head".into(), &(&(state.head)).into())
                 .map_err(|_e| anyhow::anyhow!("Failed to set head on entity"))?;
 
+            js_sys::Reflect::set(&entity, &"tombstone".into(), &state.tombstone.into())
+                .map_err(|_e| anyhow::anyhow!("Failed to set tombstone on entity"))?;
+
+            js_sys::Reflect::set(&entity, &"terms".into(), &entity_terms(id, &self.collection_id, state)?.into())
+                .map_err(|_e| anyhow::anyhow!("Failed to set terms on entity"))?;
+
             // Put the entity in the store
             let request =
                 store.put_with_key(&entity, &id.as_string().into()).map_err(|_e| anyhow::anyhow!("Failed to put entity in store"))?;
@@ -330,7 +491,314 @@ impl StorageCollection for IndexedDBBucket {
                 .try_into()
                 .map_err(|e| RetrievalError::StorageError(anyhow::anyhow!("Failed to deserialize head: {}", e).into()))?;
 
-            Ok(proto::State { state_buffers, head })
+            // Records written before tombstones existed won't have one
+            let tombstone = js_sys::Reflect::get(&entity, &"tombstone".into())
+                .map_err(|_e| RetrievalError::StorageError(anyhow::anyhow!("Failed to get tombstone").into()))?;
+
+            Ok(proto::State { state_buffers, head, tombstone: tombstone.as_bool().unwrap_or(false) })
+        })
+        .await
+    }
+
+    async fn add_event(&self, entity_event: &proto::Event) -> anyhow::Result<bool> {
+        SendWrapper::new(async move {
+            let transaction = self
+                .db
+                .transaction_with_str_and_mode("events", web_sys::IdbTransactionMode::Readwrite)
+                .map_err(|_e| anyhow::anyhow!("Failed to create transaction"))?;
+
+            let store = transaction.object_store("events").map_err(|_e| anyhow::anyhow!("Failed to get object store"))?;
+
+            // Events are immutable, so there is nothing to do if we already have it
+            let old_request = store.get(&entity_event.id.as_string().into()).map_err(|_e| anyhow::anyhow!("Failed to get old event"))?;
+            crate::cb_future::CBFuture::new(&old_request, "success", "error")
+                .await
+                .map_err(|_e| anyhow::anyhow!("Failed to get old event"))?;
+
+            let old_event: JsValue = old_request.result().unwrap();
+            if !old_event.is_undefined() && !old_event.is_null() {
+                return Ok(false);
+            }
+
+            let event = js_sys::Object::new();
+            js_sys::Reflect::set(&event, &"id".into(), &entity_event.id.as_string().into())
+                .map_err(|_e| anyhow::anyhow!("Failed to set id on event"))?;
+            js_sys::Reflect::set(&event, &"collection".into(), &self.collection_id.as_str().into())
+                .map_err(|_e| anyhow::anyhow!("Failed to set collection on event"))?;
+            js_sys::Reflect::set(&event, &"entity_id".into(), &entity_event.entity_id.as_string().into())
+                .map_err(|_e| anyhow::anyhow!("Failed to set entity_id on event"))?;
+
+            let operations = bincode::serialize(&entity_event.operations)?;
+            js_sys::Reflect::set(&event, &"operations".into(), &js_sys::Uint8Array::from(&operations[..]).into())
+                .map_err(|_e| anyhow::anyhow!("Failed to set operations on event"))?;
+
+            js_sys::Reflect::set(&event, &"parent".into(), &(&entity_event.parent).into())
+                .map_err(|_e| anyhow::anyhow!("Failed to set parent on event"))?;
+
+            js_sys::Reflect::set(&event, &"tombstone".into(), &entity_event.tombstone.into())
+                .map_err(|_e| anyhow::anyhow!("Failed to set tombstone on event"))?;
+
+            let request = store
+                .put_with_key(&event, &entity_event.id.as_string().into())
+                .map_err(|_e| anyhow::anyhow!("Failed to put event in store"))?;
+
+            crate::cb_future::CBFuture::new(&request, "success", "error")
+                .await
+                .map_err(|_e| anyhow::anyhow!("Failed to put event in store"))?;
+            crate::cb_future::CBFuture::new(&transaction, "complete", "error")
+                .await
+                .map_err(|_e| anyhow::anyhow!("Failed to complete transaction"))?;
+
+            Ok(true)
+        })
+        .await
+    }
+
+    async fn get_events(&self, id: proto::ID) -> Result<Vec<proto::Event>, RetrievalError> {
+        SendWrapper::new(async move {
+            let transaction = self.db.transaction_with_str("events").map_err(|_e| anyhow::anyhow!("Failed to create transaction"))?;
+
+            let store = transaction.object_store("events").map_err(|_e| anyhow::anyhow!("Failed to get object store"))?;
+
+            let index = store.index("by_entity").map_err(|_e| anyhow::anyhow!("Failed to get entity index"))?;
+
+            let key_range =
+                web_sys::IdbKeyRange::only(&id.as_string().into()).map_err(|_e| anyhow::anyhow!("Failed to create key range"))?;
+
+            let request = index.open_cursor_with_range(&key_range).map_err(|_e| anyhow::anyhow!("Failed to open cursor"))?;
+
+            let mut events = Vec::new();
+            let mut stream = crate::cb_stream::CBStream::new(&request, "success", "error");
+
+            while let Some(result) = stream.next().await {
+                let cursor_result = result.map_err(|e| anyhow::anyhow!("Cursor error: {}", e))?;
+
+                // Check if we've reached the end
+                if cursor_result.is_null() || cursor_result.is_undefined() {
+                    break;
+                }
+
+                let cursor: web_sys::IdbCursorWithValue = cursor_result.dyn_into().map_err(|_| anyhow::anyhow!("Failed to cast cursor"))?;
+
+                let event = cursor.value().map_err(|e| anyhow::anyhow!("Failed to get cursor value: {:?}", e))?;
+
+                let event_id = js_sys::Reflect::get(&event, &"id".into()).map_err(|_e| anyhow::anyhow!("Failed to get event id"))?;
+                let event_id: proto::ID = event_id.try_into().map_err(|_e| anyhow::anyhow!("Failed to convert id to proto::ID"))?;
+
+                let operations =
+                    js_sys::Reflect::get(&event, &"operations".into()).map_err(|_e| anyhow::anyhow!("Failed to get operations"))?;
+                let array: js_sys::Uint8Array = operations.dyn_into().map_err(|_e| anyhow::anyhow!("Failed to convert operations"))?;
+
+                let mut buffer = vec![0; array.length() as usize];
+                array.copy_to(&mut buffer);
+                let operations = bincode::deserialize(&buffer)?;
+
+                let parent = js_sys::Reflect::get(&event, &"parent".into()).map_err(|_e| anyhow::anyhow!("Failed to get parent"))?;
+                let parent: proto::Clock = parent.try_into().map_err(|e| anyhow::anyhow!("Failed to deserialize parent: {}", e))?;
+
+                let tombstone =
+                    js_sys::Reflect::get(&event, &"tombstone".into()).map_err(|_e| anyhow::anyhow!("Failed to get tombstone"))?;
+
+                events.push(proto::Event {
+                    id: event_id,
+                    collection: self.collection_id.clone(),
+                    entity_id: id,
+                    operations,
+                    parent,
+                    tombstone: tombstone.as_bool().unwrap_or(false),
+                });
+
+                cursor.continue_().map_err(|_e| anyhow::anyhow!("Failed to advance cursor"))?;
+            }
+
+            // The index is ordered by entity id only, so sort by event id to match the other engines
+            events.sort_by(|a, b| a.id.cmp(&b.id));
+            Ok(events)
+        })
+        .await
+    }
+
+    async fn remove_events(&self, _id: proto::ID, event_ids: &[proto::ID]) -> anyhow::Result<()> {
+        SendWrapper::new(async move {
+            let transaction = self
+                .db
+                .transaction_with_str_and_mode("events", web_sys::IdbTransactionMode::Readwrite)
+                .map_err(|_e| anyhow::anyhow!("Failed to create transaction"))?;
+
+            let store = transaction.object_store("events").map_err(|_e| anyhow::anyhow!("Failed to get object store"))?;
+
+            for event_id in event_ids {
+                let request = store.delete(&event_id.as_string().into()).map_err(|_e| anyhow::anyhow!("Failed to delete event"))?;
+                crate::cb_future::CBFuture::new(&request, "success", "error")
+                    .await
+                    .map_err(|_e| anyhow::anyhow!("Failed to delete event"))?;
+            }
+
+            crate::cb_future::CBFuture::new(&transaction, "complete", "error")
+                .await
+                .map_err(|_e| anyhow::anyhow!("Failed to complete transaction"))?;
+
+            let transaction = self
+                .db
+                .transaction_with_str_and_mode("acknowledgements", web_sys::IdbTransactionMode::Readwrite)
+                .map_err(|_e| anyhow::anyhow!("Failed to create transaction"))?;
+
+            let store = transaction.object_store("acknowledgements").map_err(|_e| anyhow::anyhow!("Failed to get object store"))?;
+
+            for event_id in event_ids {
+                let request =
+                    store.delete(&acknowledgement_range(*event_id)?).map_err(|_e| anyhow::anyhow!("Failed to delete acknowledgements"))?;
+                crate::cb_future::CBFuture::new(&request, "success", "error")
+                    .await
+                    .map_err(|_e| anyhow::anyhow!("Failed to delete acknowledgements"))?;
+            }
+
+            crate::cb_future::CBFuture::new(&transaction, "complete", "error")
+                .await
+                .map_err(|_e| anyhow::anyhow!("Failed to complete transaction"))?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn acknowledge_events(&self, peer_id: &proto::NodeId, event_ids: &[proto::ID]) -> anyhow::Result<()> {
+        SendWrapper::new(async move {
+            let transaction = self
+                .db
+                .transaction_with_str_and_mode("acknowledgements", web_sys::IdbTransactionMode::Readwrite)
+                .map_err(|_e| anyhow::anyhow!("Failed to create transaction"))?;
+
+            let store = transaction.object_store("acknowledgements").map_err(|_e| anyhow::anyhow!("Failed to get object store"))?;
+
+            for event_id in event_ids {
+                let acknowledgement = js_sys::Object::new();
+                js_sys::Reflect::set(&acknowledgement, &"event_id".into(), &event_id.as_string().into())
+                    .map_err(|_e| anyhow::anyhow!("Failed to set event_id on acknowledgement"))?;
+                js_sys::Reflect::set(&acknowledgement, &"node_id".into(), &String::from(peer_id.clone()).into())
+                    .map_err(|_e| anyhow::anyhow!("Failed to set node_id on acknowledgement"))?;
+
+                let request = store
+                    .put_with_key(&acknowledgement, &acknowledgement_key(*event_id, peer_id).into())
+                    .map_err(|_e| anyhow::anyhow!("Failed to put acknowledgement in store"))?;
+                crate::cb_future::CBFuture::new(&request, "success", "error")
+                    .await
+                    .map_err(|_e| anyhow::anyhow!("Failed to put acknowledgement in store"))?;
+            }
+
+            crate::cb_future::CBFuture::new(&transaction, "complete", "error")
+                .await
+                .map_err(|_e| anyhow::anyhow!("Failed to complete transaction"))?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_acknowledgements(&self, event_ids: &[proto::ID]) -> Result<BTreeMap<proto::ID, BTreeSet<proto::NodeId>>, RetrievalError> {
+        SendWrapper::new(async move {
+            let transaction =
+                self.db.transaction_with_str("acknowledgements").map_err(|_e| anyhow::anyhow!("Failed to create transaction"))?;
+
+            let store = transaction.object_store("acknowledgements").map_err(|_e| anyhow::anyhow!("Failed to get object store"))?;
+
+            let mut acknowledgements: BTreeMap<proto::ID, BTreeSet<proto::NodeId>> = BTreeMap::new();
+            for event_id in event_ids {
+                let request = store
+                    .get_all_with_key(&acknowledgement_range(*event_id)?)
+                    .map_err(|_e| anyhow::anyhow!("Failed to get acknowledgements"))?;
+                crate::cb_future::CBFuture::new(&request, "success", "error")
+                    .await
+                    .map_err(|_e| anyhow::anyhow!("Failed to get acknowledgements"))?;
+
+                let results: js_sys::Array =
+                    request.result().unwrap().dyn_into().map_err(|_e| anyhow::anyhow!("Failed to convert acknowledgements"))?;
+                for acknowledgement in results.iter() {
+                    let node_id = js_sys::Reflect::get(&acknowledgement, &"node_id".into())
+                        .map_err(|_e| anyhow::anyhow!("Failed to get node_id"))?
+                        .as_string()
+                        .ok_or_else(|| anyhow::anyhow!("node_id is not a string"))?;
+                    let ulid = ulid::Ulid::from_string(&node_id).map_err(|e| anyhow::anyhow!("Failed to parse node_id: {}", e))?;
+                    acknowledgements.entry(*event_id).or_default().insert(proto::NodeId::from_ulid(ulid));
+                }
+            }
+
+            Ok(acknowledgements)
+        })
+        .await
+    }
+
+    async fn checkpoint(&self, id: proto::ID, state: &proto::State) -> anyhow::Result<()> {
+        SendWrapper::new(async move {
+            let transaction = self
+                .db
+                .transaction_with_str_and_mode("checkpoints", web_sys::IdbTransactionMode::Readwrite)
+                .map_err(|_e| anyhow::anyhow!("Failed to create transaction"))?;
+
+            let store = transaction.object_store("checkpoints").map_err(|_e| anyhow::anyhow!("Failed to get object store"))?;
+
+            let checkpoint = js_sys::Object::new();
+            js_sys::Reflect::set(&checkpoint, &"id".into(), &id.as_string().into())
+                .map_err(|_e| anyhow::anyhow!("Failed to set id on checkpoint"))?;
+            js_sys::Reflect::set(&checkpoint, &"collection".into(), &self.collection_id.as_str().into())
+                .map_err(|_e| anyhow::anyhow!("Failed to set collection on checkpoint"))?;
+
+            let state_buffer = bincode::serialize(&state.state_buffers)?;
+            js_sys::Reflect::set(&checkpoint, &"state_buffer".into(), &js_sys::Uint8Array::from(&state_buffer[..]).into())
+                .map_err(|_e| anyhow::anyhow!("Failed to set data on checkpoint"))?;
+
+            js_sys::Reflect::set(&checkpoint, &"head".into(), &(&(state.head)).into())
+                .map_err(|_e| anyhow::anyhow!("Failed to set head on checkpoint"))?;
+
+            js_sys::Reflect::set(&checkpoint, &"tombstone".into(), &state.tombstone.into())
+                .map_err(|_e| anyhow::anyhow!("Failed to set tombstone on checkpoint"))?;
+
+            let request = store
+                .put_with_key(&checkpoint, &id.as_string().into())
+                .map_err(|_e| anyhow::anyhow!("Failed to put checkpoint in store"))?;
+
+            crate::cb_future::CBFuture::new(&request, "success", "error")
+                .await
+                .map_err(|_e| anyhow::anyhow!("Failed to put checkpoint in store"))?;
+            crate::cb_future::CBFuture::new(&transaction, "complete", "error")
+                .await
+                .map_err(|_e| anyhow::anyhow!("Failed to complete transaction"))?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_checkpoint(&self, id: proto::ID) -> Result<Option<proto::State>, RetrievalError> {
+        SendWrapper::new(async move {
+            let transaction = self.db.transaction_with_str("checkpoints").map_err(|_e| anyhow::anyhow!("Failed to create transaction"))?;
+
+            let store = transaction.object_store("checkpoints").map_err(|_e| anyhow::anyhow!("Failed to get object store"))?;
+
+            let request = store.get(&id.as_string().into()).map_err(|_e| anyhow::anyhow!("Failed to get checkpoint"))?;
+
+            crate::cb_future::CBFuture::new(&request, "success", "error")
+                .await
+                .map_err(|_e| anyhow::anyhow!("Failed to get checkpoint"))?;
+
+            let result = request.result().unwrap();
+            if result.is_undefined() || result.is_null() {
+                return Ok(None);
+            }
+
+            let data = js_sys::Reflect::get(&result, &"state_buffer".into()).map_err(|_e| anyhow::anyhow!("Failed to get state buffer"))?;
+            let array: js_sys::Uint8Array = data.dyn_into().map_err(|_e| anyhow::anyhow!("Failed to convert state buffer"))?;
+
+            let mut buffer = vec![0; array.length() as usize];
+            array.copy_to(&mut buffer);
+            let state_buffers = bincode::deserialize(&buffer)?;
+
+            let head = js_sys::Reflect::get(&result, &"head".into()).map_err(|_e| anyhow::anyhow!("Failed to get head"))?;
+            let head: proto::Clock = head.try_into().map_err(|e| anyhow::anyhow!("Failed to deserialize head: {}", e))?;
+
+            let tombstone = js_sys::Reflect::get(&result, &"tombstone".into()).map_err(|_e| anyhow::anyhow!("Failed to get tombstone"))?;
+
+            Ok(Some(proto::State { state_buffers, head, tombstone: tombstone.as_bool().unwrap_or(false) }))
         })
         .await
     }
@@ -407,7 +875,7 @@ mod tests {
         let id = proto::ID::new();
         let mut state_buffers = std::collections::BTreeMap::new();
         state_buffers.insert("propertybackend_yrs".to_string(), vec![1, 2, 3]);
-        let state = proto::State { state_buffers, head: proto::Clock::default() };
+        let state = proto::State { state_buffers, head: proto::Clock::default(), tombstone: false };
 
         // Set the entity
         bucket.set_state(id.clone(), &state).await.expect("Failed to set entity");
@@ -1,24 +1,31 @@
-use std::{collections::BTreeMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
 
+use ankql::ast::{AggregateFunction, Aggregation, Literal, SelectItem};
+use ankql::selection::aggregate::AggregateRow;
 use ankurah_core::{
     error::RetrievalError,
-    property::{
-        backend::{BackendDowncasted, PropertyBackend},
-        Backends,
-    },
+    model::Entity,
+    property::Backends,
     storage::{StorageCollection, StorageEngine},
+    value::Value,
 };
-use ankurah_proto::State;
+use ankurah_proto::{Event, NodeId, Operation, State};
 
 use futures_util::TryStreamExt;
 
 pub mod predicate;
 
-use ankurah_proto::{Clock, CollectionId, ID};
+use ankurah_proto::{Clock, CollectionId, Cursor, ID};
 use async_trait::async_trait;
 use bb8_postgres::{tokio_postgres::NoTls, PostgresConnectionManager};
-use tokio_postgres::{error::SqlState, types::ToSql};
-use tracing::{error, info};
+use tokio_postgres::{
+    error::SqlState,
+    types::{ToSql, Type},
+};
+use tracing::{debug, error, info};
 
 pub struct Postgres {
     // TODO: the rest of the owl
@@ -60,7 +67,81 @@ impl StorageEngine for Postgres {
         Ok(Arc::new(bucket))
     }
 
-    async fn fetch_states(&self, collection: CollectionId, predicate: &ankql::ast::Predicate) -> Result<Vec<(ID, State)>, RetrievalError> {
+    async fn fetch_states(&self, collection: CollectionId, query: &ankql::ast::Query) -> Result<Vec<(ID, State)>, RetrievalError> {
+        self.select_states(collection, query, None, true).await
+    }
+
+    async fn fetch_page(
+        &self,
+        collection: CollectionId,
+        query: &ankql::ast::Query,
+        after: Option<&Cursor>,
+        limit: u64,
+    ) -> Result<Vec<(ID, State)>, RetrievalError> {
+        // `Sql::after` takes one key value from the cursor for each ORDER BY item
+        if after.is_some_and(|after| after.key.len() != query.order_by.len()) {
+            return Err(RetrievalError::Other("Cursor does not match the query's ORDER BY clause".to_string()));
+        }
+        let query = ankql::ast::Query { limit: Some(limit), offset: None, ..query.clone() };
+        self.select_states(collection, &query, after, false).await
+    }
+
+    async fn aggregate(&self, collection: CollectionId, aggregation: &Aggregation) -> Result<Vec<AggregateRow>, RetrievalError> {
+        if !Postgres::sane_name(collection.as_str()) {
+            return Err(RetrievalError::InvalidBucketName);
+        }
+
+        let client = self.pool.get().await.map_err(|err| RetrievalError::StorageError(Box::new(err)))?;
+
+        let mut ankql_sql = predicate::Sql::new();
+        ankql_sql.predicate(&aggregation.predicate);
+        let (sql, args) = ankql_sql.collapse();
+
+        // Deleted entities are retained so their tombstones can be merged, but are never aggregated
+        let aggregate_query = format!(
+            r#"SELECT {} FROM "{}" WHERE NOT "tombstone" AND ({}){}"#,
+            predicate::select_list(aggregation),
+            collection.as_str(),
+            sql,
+            predicate::group_by(aggregation)
+        );
+
+        debug!("Running: {}", aggregate_query);
+        let rows = match client.query_raw(&aggregate_query, args).await {
+            Ok(stream) => stream.try_collect::<Vec<_>>().await.map_err(|err| RetrievalError::StorageError(err.into()))?,
+            // Nothing has been stored in the collection yet
+            Err(err) if err.code() == Some(&SqlState::UNDEFINED_TABLE) => {
+                return Ok(ankql::selection::aggregate::aggregate(std::iter::empty::<&Entity>(), aggregation)?);
+            }
+            Err(err) => {
+                // Tables created before tombstones existed
+                if let ErrorKind::UndefinedColumn { table: None, column } = error_kind(&err) {
+                    if column == "tombstone" {
+                        self.add_tombstone_column(&collection).await?;
+                        // retry
+                        return Box::pin(self.aggregate(collection, aggregation)).await;
+                    }
+                }
+                return Err(RetrievalError::StorageError(err.into()));
+            }
+        };
+
+        rows.iter()
+            .map(|row| aggregation.select.iter().enumerate().map(|(index, item)| aggregate_literal(row, index, item)).collect())
+            .collect()
+    }
+}
+
+impl Postgres {
+    /// Select the states of the rows matching a query, seeking past the cursor if there is one.
+    /// Pages must not be ranked by relevance, because their cursors only record a position in ORDER BY and ID order.
+    async fn select_states(
+        &self,
+        collection: CollectionId,
+        query: &ankql::ast::Query,
+        after: Option<&Cursor>,
+        ranked: bool,
+    ) -> Result<Vec<(ID, State)>, RetrievalError> {
         if !Postgres::sane_name(&collection.as_str()) {
             return Err(RetrievalError::InvalidBucketName);
         }
@@ -69,18 +150,20 @@ impl StorageEngine for Postgres {
 
         let mut results = Vec::new();
 
+        // Deleted entities are retained so their tombstones can be merged, but never match
         let mut ankql_sql = predicate::Sql::new();
-        ankql_sql.predicate(&predicate);
-
-        let (sql, args) = ankql_sql.collapse();
+        ankql_sql.sql(format!(r#"SELECT "id", "state_buffer", "head" FROM "{}" WHERE NOT "tombstone" AND ("#, collection.as_str()));
+        ankql_sql.predicate(&query.predicate);
+        ankql_sql.sql(")");
+        if let Some(after) = after {
+            ankql_sql.sql(" AND ");
+            ankql_sql.after(&query.order_by, after);
+        }
+        ankql_sql.order_and_window(query, ranked);
 
-        let filtered_query = if args.len() > 0 {
-            format!(r#"SELECT "id", "state_buffer", "head" FROM "{}" WHERE {}"#, collection.as_str(), sql,)
-        } else {
-            format!(r#"SELECT "id", "state_buffer", "head" FROM "{}""#, collection.as_str())
-        };
+        let (filtered_query, args) = ankql_sql.collapse();
 
-        eprintln!("Running: {}", filtered_query);
+        debug!("Running: {}", filtered_query);
         // `query_raw` fixes 2 problems here
         // - `query` only takes `&[&dyn ToSql + Sync]`... and rust can't coerce
         //   `&[&dyn ToSql + Send + Sync]` for reasons unknown to me.
@@ -102,6 +185,14 @@ impl StorageEngine for Postgres {
                             return Ok(Vec::new());
                         }
                     }
+                    ErrorKind::UndefinedColumn { table: None, column } => {
+                        // Tables created before tombstones existed
+                        if column == "tombstone" {
+                            self.add_tombstone_column(&collection).await?;
+                            // retry
+                            return Box::pin(self.select_states(collection, query, after, ranked)).await;
+                        }
+                    }
                     _ => {}
                 }
 
@@ -116,7 +207,7 @@ impl StorageEngine for Postgres {
 
             let state_buffers: BTreeMap<String, Vec<u8>> = bincode::deserialize(&state_buffer)?;
 
-            let entity_state = State { state_buffers, head: row.get::<_, Vec<uuid::Uuid>>(2).into() };
+            let entity_state = State { state_buffers, head: row.get::<_, Vec<uuid::Uuid>>(2).into(), tombstone: false };
 
             results.push((id, entity_state));
 
@@ -131,6 +222,38 @@ impl StorageEngine for Postgres {
 
         Ok(results)
     }
+
+    /// Add the tombstone column to a collection's state table, which was created without it before tombstones existed
+    async fn add_tombstone_column(&self, collection: &CollectionId) -> Result<(), RetrievalError> {
+        let bucket = PostgresBucket { pool: self.pool.clone(), collection_id: collection.clone() };
+        let mut client = self.pool.get().await.map_err(|err| RetrievalError::StorageError(Box::new(err)))?;
+        bucket.add_tombstone_column(&mut client, collection.as_str()).await.map_err(|e| RetrievalError::StorageError(e.into()))
+    }
+}
+
+/// Read a column of an aggregation result. SUMs are selected as text by `predicate::select_list`, so a whole number
+/// sum reads back as an integer, even if the summed values were floats
+fn aggregate_literal(row: &tokio_postgres::Row, index: usize, item: &SelectItem) -> Result<Option<Literal>, RetrievalError> {
+    let column_error = |err: tokio_postgres::Error| RetrievalError::StorageError(err.into());
+
+    if let SelectItem::Aggregate { function: AggregateFunction::Sum, .. } = item {
+        let sum: Option<String> = row.try_get(index).map_err(column_error)?;
+        return sum
+            .map(|sum| match sum.parse() {
+                Ok(integer) => Ok(Literal::Integer(integer)),
+                Err(_) => sum.parse().map(Literal::Float).map_err(|_| RetrievalError::Other(format!("Invalid sum: {}", sum))),
+            })
+            .transpose();
+    }
+
+    let column_type = row.columns()[index].type_();
+    Ok(match *column_type {
+        Type::INT8 => row.try_get::<_, Option<i64>>(index).map_err(column_error)?.map(Literal::Integer),
+        Type::FLOAT8 => row.try_get::<_, Option<f64>>(index).map_err(column_error)?.map(Literal::Float),
+        Type::BOOL => row.try_get::<_, Option<bool>>(index).map_err(column_error)?.map(Literal::Boolean),
+        Type::VARCHAR | Type::TEXT => row.try_get::<_, Option<String>>(index).map_err(column_error)?.map(Literal::String),
+        _ => return Err(RetrievalError::Other(format!("Unsupported aggregate column type: {}", column_type))),
+    })
 }
 
 pub struct PostgresBucket {
@@ -141,14 +264,71 @@ pub struct PostgresBucket {
 impl PostgresBucket {
     pub async fn create_table(&self, client: &mut tokio_postgres::Client) -> anyhow::Result<()> {
         // Create the table
+        let create_query = format!(
+            r#"CREATE TABLE "{}"("id" UUID UNIQUE, "state_buffer" BYTEA, "head" UUID[], "tombstone" BOOLEAN NOT NULL DEFAULT FALSE)"#,
+            self.collection_id.as_str()
+        );
+
+        error!("Running: {}", create_query);
+        client.execute(&create_query, &[]).await?;
+        Ok(())
+    }
+
+    /// Named with a `/`, which `sane_name` keeps out of collection IDs, so no collection's table can share its name
+    pub fn event_table(&self) -> String { format!("{}/event", self.collection_id.as_str()) }
+
+    pub async fn create_event_table(&self, client: &mut tokio_postgres::Client) -> anyhow::Result<()> {
+        let create_query = format!(
+            r#"CREATE TABLE "{0}"("id" UUID UNIQUE, "entity_id" UUID, "operations" BYTEA, "parent" UUID[], "tombstone" BOOLEAN NOT NULL DEFAULT FALSE);
+            CREATE INDEX "{0}_entity_id" ON "{0}"("entity_id")"#,
+            self.event_table()
+        );
+
+        error!("Running: {}", create_query);
+        client.batch_execute(&create_query).await?;
+        Ok(())
+    }
+
+    pub fn checkpoint_table(&self) -> String { format!("{}/checkpoint", self.collection_id.as_str()) }
+
+    pub async fn create_checkpoint_table(&self, client: &mut tokio_postgres::Client) -> anyhow::Result<()> {
+        let create_query = format!(
+            r#"CREATE TABLE "{}"("id" UUID UNIQUE, "state_buffer" BYTEA, "head" UUID[], "tombstone" BOOLEAN NOT NULL DEFAULT FALSE)"#,
+            self.checkpoint_table()
+        );
+
+        error!("Running: {}", create_query);
+        client.execute(&create_query, &[]).await?;
+        Ok(())
+    }
+
+    pub fn acknowledgement_table(&self) -> String { format!("{}/ack", self.collection_id.as_str()) }
+
+    pub async fn create_acknowledgement_table(&self, client: &mut tokio_postgres::Client) -> anyhow::Result<()> {
         let create_query =
-            format!(r#"CREATE TABLE "{}"("id" UUID UNIQUE, "state_buffer" BYTEA, "head" UUID[])"#, self.collection_id.as_str());
+            format!(r#"CREATE TABLE "{}"("event_id" UUID, "node_id" UUID, UNIQUE("event_id", "node_id"))"#, self.acknowledgement_table());
 
         error!("Running: {}", create_query);
         client.execute(&create_query, &[]).await?;
         Ok(())
     }
 
+    /// The names of the columns of the collection's table, which has none if it doesn't exist yet
+    pub async fn existing_columns(&self, client: &tokio_postgres::Client) -> anyhow::Result<BTreeSet<String>> {
+        let rows = client
+            .query(r#"SELECT "column_name"::text FROM information_schema.columns WHERE "table_name" = $1"#, &[&self.collection_id.as_str()])
+            .await?;
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    /// Add the tombstone column to the state or event table, which were created without it before tombstones existed
+    pub async fn add_tombstone_column(&self, client: &mut tokio_postgres::Client, table: &str) -> anyhow::Result<()> {
+        let alter_query = format!(r#"ALTER TABLE "{}" ADD COLUMN IF NOT EXISTS "tombstone" BOOLEAN NOT NULL DEFAULT FALSE"#, table);
+        error!("Running: {}", alter_query);
+        client.execute(&alter_query, &[]).await?;
+        Ok(())
+    }
+
     pub async fn add_missing_columns(
         &self,
         client: &mut tokio_postgres::Client,
@@ -159,6 +339,17 @@ impl PostgresBucket {
                 let alter_query = format!(r#"ALTER TABLE "{}" ADD COLUMN "{}" {}"#, self.collection_id.as_str(), column, datatype,);
                 error!("Running: {}", alter_query);
                 client.execute(&alter_query, &[]).await?;
+
+                // Text columns can be searched with MATCHES, which this index serves
+                if datatype == "varchar" {
+                    let index_query = format!(
+                        r#"CREATE INDEX IF NOT EXISTS "{0}/{1}/terms" ON "{0}" USING GIN (to_tsvector('simple', "{1}"))"#,
+                        self.collection_id.as_str(),
+                        column
+                    );
+                    error!("Running: {}", index_query);
+                    client.execute(&index_query, &[]).await?;
+                }
             }
         }
 
@@ -169,14 +360,29 @@ impl PostgresBucket {
 pub enum PostgresParams {
     String(String),
     Number(i64),
+    Float(f64),
+    Boolean(bool),
     Bytes(Vec<u8>),
 }
 
 impl PostgresParams {
+    pub fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::String(string) => Some(PostgresParams::String(string)),
+            Value::Integer(integer) => Some(PostgresParams::Number(integer)),
+            Value::Float(float) => Some(PostgresParams::Float(float)),
+            Value::Boolean(boolean) => Some(PostgresParams::Boolean(boolean)),
+            Value::Bytes(bytes) => Some(PostgresParams::Bytes(bytes)),
+            Value::Null => None,
+        }
+    }
+
     pub fn postgres_type(&self) -> &'static str {
         match self {
             PostgresParams::String(_) => "varchar",
-            PostgresParams::Number(_) => "int",
+            PostgresParams::Number(_) => "bigint",
+            PostgresParams::Float(_) => "double precision",
+            PostgresParams::Boolean(_) => "boolean",
             PostgresParams::Bytes(_) => "bytea",
         }
     }
@@ -193,63 +399,53 @@ impl StorageCollection for PostgresBucket {
         let head_uuids: Vec<uuid::Uuid> = (&state.head).into();
 
         let backends = Backends::from_state_buffers(state)?;
-        let mut columns: Vec<String> = vec!["id".to_owned(), "state_buffer".to_owned(), "head".to_owned()];
-        let mut params: Vec<&(dyn ToSql + Sync)> = Vec::new();
-        params.push(&uuid);
-        params.push(&state_buffers);
-        params.push(&head_uuids);
+        let mut columns: Vec<String> = vec!["id".to_owned(), "state_buffer".to_owned(), "head".to_owned(), "tombstone".to_owned()];
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![&uuid, &state_buffers, &head_uuids, &state.tombstone];
 
         let mut materialized_columns: Vec<String> = Vec::new();
         let mut materialized: Vec<PostgresParams> = Vec::new();
+        let mut null_columns: Vec<String> = Vec::new();
 
-        for backend in backends.downcasted() {
-            match backend {
-                BackendDowncasted::Yrs(yrs) => {
-                    info!("yrs found");
-                    for property in yrs.properties() {
-                        info!("property: {:?}", property);
-                        if let Some(string) = yrs.get_string(property.clone()) {
-                            materialized_columns.push(property);
-                            materialized.push(PostgresParams::String(string));
-                        }
-                    }
-                }
-                BackendDowncasted::LWW(lww) => {
-                    for property in lww.properties() {
-                        let Some(data) = lww.get(property.clone()) else {
-                            continue;
-                        };
-                        materialized_columns.push(property);
-                        materialized.push(PostgresParams::Bytes(data));
-                    }
+        for (property, value) in backends.materialized() {
+            match PostgresParams::from_value(value) {
+                Some(param) => {
+                    materialized_columns.push(property);
+                    materialized.push(param);
                 }
-                BackendDowncasted::PN(pn) => {
-                    for property in pn.properties() {
-                        let data = pn.get(property.clone());
-                        materialized_columns.push(property);
-                        materialized.push(PostgresParams::Number(data));
-                    }
-                }
-                _ => {}
+                None => null_columns.push(property),
             }
         }
 
+        let mut client = self.pool.get().await?;
+        // The column type can't be inferred from a null, so nulls are only written to columns which already exist
+        if !null_columns.is_empty() {
+            let existing = self.existing_columns(&client).await?;
+            null_columns.retain(|column| existing.contains(column));
+        }
+
         columns.extend(materialized_columns.clone());
         for parameter in &materialized {
             match &parameter {
                 PostgresParams::String(string) => params.push(string),
                 PostgresParams::Number(number) => params.push(number),
+                PostgresParams::Float(float) => params.push(float),
+                PostgresParams::Boolean(boolean) => params.push(boolean),
                 PostgresParams::Bytes(bytes) => params.push(bytes),
             }
         }
 
+        // Nulls are written as literals, as a parameter's type has to match the column's
+        let values: Vec<String> =
+            (1..=params.len()).map(|index| format!("${}", index)).chain(null_columns.iter().map(|_| "NULL".to_owned())).collect();
+        columns.extend(null_columns);
+
         let columns_str = columns.iter().map(|name| format!("\"{}\"", name)).collect::<Vec<String>>().join(", ");
-        let values_str = params.iter().enumerate().map(|(index, _)| format!("${}", index + 1)).collect::<Vec<String>>().join(", ");
+        let values_str = values.join(", ");
         let columns_update_str = columns
             .iter()
-            .enumerate()
+            .zip(&values)
             .skip(1) // Skip "id"
-            .map(|(index, name)| format!("\"{}\" = ${}", name, index + 1))
+            .map(|(name, value)| format!("\"{}\" = {}", name, value))
             .collect::<Vec<String>>()
             .join(", ");
 
@@ -269,7 +465,6 @@ impl StorageCollection for PostgresBucket {
             columns_update_str
         );
 
-        let mut client = self.pool.get().await?;
         error!("Running: {}", query);
         let row = match client.query_one(&query, params.as_slice()).await {
             Ok(row) => row,
@@ -282,9 +477,14 @@ impl StorageCollection for PostgresBucket {
                             return self.set_state(id, state).await; // retry
                         }
                     }
-                    ErrorKind::UndefinedColumn { table, column } => {
+                    ErrorKind::UndefinedColumn { table: Some(table), column } => {
                         // TODO: We should check the definition of this and add all
                         // needed columns rather than recursively doing it.
+                        if table == self.collection_id.as_str() && column == "tombstone" {
+                            // Tables created before tombstones existed
+                            self.add_tombstone_column(&mut client, &table).await?;
+                            return self.set_state(id, state).await; // retry
+                        }
                         if table == self.collection_id.as_str() {
                             let index = materialized_columns.iter().enumerate().find(|(_, name)| **name == column).map(|(index, _)| index);
                             if let Some(index) = index {
@@ -322,7 +522,7 @@ impl StorageCollection for PostgresBucket {
         let uuid: uuid::Uuid = ulid.into();
 
         // be careful with sql injection via bucket name
-        let query = format!(r#"SELECT "id", "state_buffer", "head" FROM "{}" WHERE "id" = $1"#, self.collection_id.as_str());
+        let query = format!(r#"SELECT "id", "state_buffer", "head", "tombstone" FROM "{}" WHERE "id" = $1"#, self.collection_id.as_str());
 
         let mut client = match self.pool.get().await {
             Ok(client) => client,
@@ -346,6 +546,13 @@ impl StorageCollection for PostgresBucket {
                             return Err(RetrievalError::NotFound(id));
                         }
                     }
+                    ErrorKind::UndefinedColumn { table: None, column } if column == "tombstone" => {
+                        // Tables created before tombstones existed
+                        self.add_tombstone_column(&mut client, self.collection_id.as_str())
+                            .await
+                            .map_err(|e| RetrievalError::StorageError(e.into()))?;
+                        return self.get_state(id).await; // retry
+                    }
                     _ => {}
                 }
 
@@ -360,7 +567,267 @@ impl StorageCollection for PostgresBucket {
         let serialized_buffers: Vec<u8> = row.get("state_buffer");
         let state_buffers: BTreeMap<String, Vec<u8>> = bincode::deserialize(&serialized_buffers)?;
 
-        Ok(State { state_buffers, head: row.get::<_, Vec<uuid::Uuid>>("head").into() })
+        Ok(State { state_buffers, head: row.get::<_, Vec<uuid::Uuid>>("head").into(), tombstone: row.get("tombstone") })
+    }
+
+    async fn add_event(&self, entity_event: &Event) -> anyhow::Result<bool> {
+        let event_uuid: uuid::Uuid = ulid::Ulid::from(entity_event.id).into();
+        let entity_uuid: uuid::Uuid = ulid::Ulid::from(entity_event.entity_id).into();
+        let operations = bincode::serialize(&entity_event.operations)?;
+        let parent: Vec<uuid::Uuid> = (&entity_event.parent).into();
+
+        // be careful with sql injection via bucket name
+        let query = format!(
+            r#"INSERT INTO "{}"("id", "entity_id", "operations", "parent", "tombstone") VALUES($1, $2, $3, $4, $5) ON CONFLICT ("id") DO NOTHING"#,
+            self.event_table()
+        );
+
+        let mut client = self.pool.get().await?;
+        error!("Running: {}", query);
+        let affected = match client.execute(&query, &[&event_uuid, &entity_uuid, &operations, &parent, &entity_event.tombstone]).await {
+            Ok(affected) => affected,
+            Err(err) => {
+                match error_kind(&err) {
+                    ErrorKind::UndefinedTable { table } if table == self.event_table() => {
+                        self.create_event_table(&mut client).await?;
+                        return self.add_event(entity_event).await; // retry
+                    }
+                    ErrorKind::UndefinedColumn { table: Some(table), column } if table == self.event_table() && column == "tombstone" => {
+                        // Event tables created before tombstones existed
+                        self.add_tombstone_column(&mut client, &table).await?;
+                        return self.add_event(entity_event).await; // retry
+                    }
+                    _ => {}
+                }
+
+                return Err(err.into());
+            }
+        };
+
+        Ok(affected > 0)
+    }
+
+    async fn get_events(&self, id: ID) -> Result<Vec<Event>, RetrievalError> {
+        let ulid: ulid::Ulid = id.into();
+        let uuid: uuid::Uuid = ulid.into();
+
+        // be careful with sql injection via bucket name
+        let query = format!(
+            r#"SELECT "id", "operations", "parent", "tombstone" FROM "{}" WHERE "entity_id" = $1 ORDER BY "id""#,
+            self.event_table()
+        );
+
+        let mut client = self.pool.get().await.map_err(|err| RetrievalError::StorageError(err.into()))?;
+
+        error!("Running: {}", query);
+        let rows = match client.query(&query, &[&uuid]).await {
+            Ok(rows) => rows,
+            Err(err) => {
+                match error_kind(&err) {
+                    ErrorKind::UndefinedTable { table } if table == self.event_table() => {
+                        self.create_event_table(&mut client).await.map_err(|e| RetrievalError::StorageError(e.into()))?;
+                        return Ok(Vec::new());
+                    }
+                    ErrorKind::UndefinedColumn { table: None, column } if column == "tombstone" => {
+                        // Event tables created before tombstones existed
+                        self.add_tombstone_column(&mut client, &self.event_table())
+                            .await
+                            .map_err(|e| RetrievalError::StorageError(e.into()))?;
+                        return self.get_events(id).await; // retry
+                    }
+                    _ => {}
+                }
+
+                return Err(RetrievalError::StorageError(err.into()));
+            }
+        };
+
+        let mut events = Vec::new();
+        for row in rows {
+            let event_uuid: uuid::Uuid = row.get("id");
+            let serialized_operations: Vec<u8> = row.get("operations");
+            let operations: BTreeMap<String, Vec<Operation>> = bincode::deserialize(&serialized_operations)?;
+
+            events.push(Event {
+                id: ID::from_ulid(ulid::Ulid::from(event_uuid)),
+                collection: self.collection_id.clone(),
+                entity_id: id,
+                operations,
+                parent: row.get::<_, Vec<uuid::Uuid>>("parent").into(),
+                tombstone: row.get("tombstone"),
+            });
+        }
+
+        Ok(events)
+    }
+
+    async fn remove_events(&self, _id: ID, event_ids: &[ID]) -> anyhow::Result<()> {
+        let event_uuids: Vec<uuid::Uuid> = event_ids.iter().map(|event_id| ulid::Ulid::from(*event_id).into()).collect();
+
+        // be careful with sql injection via bucket name
+        let query = format!(r#"DELETE FROM "{}" WHERE "id" = ANY($1)"#, self.event_table());
+
+        let mut client = self.pool.get().await?;
+        error!("Running: {}", query);
+        if let Err(err) = client.execute(&query, &[&event_uuids]).await {
+            let kind = error_kind(&err);
+            if let ErrorKind::UndefinedTable { table } = kind {
+                if table == self.event_table() {
+                    // Nothing was ever recorded, so there is nothing to remove
+                    self.create_event_table(&mut client).await?;
+                    return Ok(());
+                }
+            }
+
+            return Err(err.into());
+        }
+
+        // be careful with sql injection via bucket name
+        let query = format!(r#"DELETE FROM "{}" WHERE "event_id" = ANY($1)"#, self.acknowledgement_table());
+        error!("Running: {}", query);
+        if let Err(err) = client.execute(&query, &[&event_uuids]).await {
+            let kind = error_kind(&err);
+            if let ErrorKind::UndefinedTable { table } = kind {
+                if table == self.acknowledgement_table() {
+                    // Nothing was ever acknowledged, so there is nothing to remove
+                    self.create_acknowledgement_table(&mut client).await?;
+                    return Ok(());
+                }
+            }
+
+            return Err(err.into());
+        }
+
+        Ok(())
+    }
+
+    async fn acknowledge_events(&self, peer_id: &NodeId, event_ids: &[ID]) -> anyhow::Result<()> {
+        let event_uuids: Vec<uuid::Uuid> = event_ids.iter().map(|event_id| ulid::Ulid::from(*event_id).into()).collect();
+        let node_uuid: uuid::Uuid = ulid::Ulid::from(peer_id.clone()).into();
+
+        // be careful with sql injection via bucket name
+        let query = format!(
+            r#"INSERT INTO "{}"("event_id", "node_id") SELECT UNNEST($1::UUID[]), $2 ON CONFLICT DO NOTHING"#,
+            self.acknowledgement_table()
+        );
+
+        let mut client = self.pool.get().await?;
+        error!("Running: {}", query);
+        if let Err(err) = client.execute(&query, &[&event_uuids, &node_uuid]).await {
+            let kind = error_kind(&err);
+            if let ErrorKind::UndefinedTable { table } = kind {
+                if table == self.acknowledgement_table() {
+                    self.create_acknowledgement_table(&mut client).await?;
+                    return self.acknowledge_events(peer_id, event_ids).await; // retry
+                }
+            }
+
+            return Err(err.into());
+        }
+
+        Ok(())
+    }
+
+    async fn get_acknowledgements(&self, event_ids: &[ID]) -> Result<BTreeMap<ID, BTreeSet<NodeId>>, RetrievalError> {
+        let event_uuids: Vec<uuid::Uuid> = event_ids.iter().map(|event_id| ulid::Ulid::from(*event_id).into()).collect();
+
+        // be careful with sql injection via bucket name
+        let query = format!(r#"SELECT "event_id", "node_id" FROM "{}" WHERE "event_id" = ANY($1)"#, self.acknowledgement_table());
+
+        let mut client = self.pool.get().await.map_err(|err| RetrievalError::StorageError(err.into()))?;
+
+        error!("Running: {}", query);
+        let rows = match client.query(&query, &[&event_uuids]).await {
+            Ok(rows) => rows,
+            Err(err) => {
+                let kind = error_kind(&err);
+                if let ErrorKind::UndefinedTable { table } = kind {
+                    if table == self.acknowledgement_table() {
+                        self.create_acknowledgement_table(&mut client).await.map_err(|e| RetrievalError::StorageError(e.into()))?;
+                        return Ok(BTreeMap::new());
+                    }
+                }
+
+                return Err(RetrievalError::StorageError(err.into()));
+            }
+        };
+
+        let mut acknowledgements: BTreeMap<ID, BTreeSet<NodeId>> = BTreeMap::new();
+        for row in rows {
+            let event_uuid: uuid::Uuid = row.get("event_id");
+            let node_uuid: uuid::Uuid = row.get("node_id");
+            acknowledgements
+                .entry(ID::from_ulid(ulid::Ulid::from(event_uuid)))
+                .or_default()
+                .insert(NodeId::from_ulid(ulid::Ulid::from(node_uuid)));
+        }
+
+        Ok(acknowledgements)
+    }
+
+    async fn checkpoint(&self, id: ID, state: &State) -> anyhow::Result<()> {
+        let ulid: ulid::Ulid = id.into();
+        let uuid: uuid::Uuid = ulid.into();
+        let state_buffers = bincode::serialize(&state.state_buffers)?;
+        let head_uuids: Vec<uuid::Uuid> = (&state.head).into();
+
+        // be careful with sql injection via bucket name
+        let query = format!(
+            r#"INSERT INTO "{}"("id", "state_buffer", "head", "tombstone") VALUES($1, $2, $3, $4)
+            ON CONFLICT("id") DO UPDATE SET "state_buffer" = $2, "head" = $3, "tombstone" = $4"#,
+            self.checkpoint_table()
+        );
+
+        let mut client = self.pool.get().await?;
+        error!("Running: {}", query);
+        if let Err(err) = client.execute(&query, &[&uuid, &state_buffers, &head_uuids, &state.tombstone]).await {
+            let kind = error_kind(&err);
+            if let ErrorKind::UndefinedTable { table } = kind {
+                if table == self.checkpoint_table() {
+                    self.create_checkpoint_table(&mut client).await?;
+                    return self.checkpoint(id, state).await; // retry
+                }
+            }
+
+            return Err(err.into());
+        }
+
+        Ok(())
+    }
+
+    async fn get_checkpoint(&self, id: ID) -> Result<Option<State>, RetrievalError> {
+        let ulid: ulid::Ulid = id.into();
+        let uuid: uuid::Uuid = ulid.into();
+
+        // be careful with sql injection via bucket name
+        let query = format!(r#"SELECT "state_buffer", "head", "tombstone" FROM "{}" WHERE "id" = $1"#, self.checkpoint_table());
+
+        let mut client = self.pool.get().await.map_err(|err| RetrievalError::StorageError(err.into()))?;
+
+        error!("Running: {}", query);
+        let row = match client.query_opt(&query, &[&uuid]).await {
+            Ok(row) => row,
+            Err(err) => {
+                let kind = error_kind(&err);
+                if let ErrorKind::UndefinedTable { table } = kind {
+                    if table == self.checkpoint_table() {
+                        self.create_checkpoint_table(&mut client).await.map_err(|e| RetrievalError::StorageError(e.into()))?;
+                        return Ok(None);
+                    }
+                }
+
+                return Err(RetrievalError::StorageError(err.into()));
+            }
+        };
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let serialized_buffers: Vec<u8> = row.get("state_buffer");
+        let state_buffers: BTreeMap<String, Vec<u8>> = bincode::deserialize(&serialized_buffers)?;
+
+        Ok(Some(State { state_buffers, head: row.get::<_, Vec<uuid::Uuid>>("head").into(), tombstone: row.get("tombstone") }))
     }
 }
 
@@ -370,8 +837,14 @@ impl StorageCollection for PostgresBucket {
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum ErrorKind {
     RowCount,
-    UndefinedTable { table: String },
-    UndefinedColumn { table: String, column: String },
+    UndefinedTable {
+        table: String,
+    },
+    /// The table is only named when the column was being written
+    UndefinedColumn {
+        table: Option<String>,
+        column: String,
+    },
     Unknown,
 }
 
@@ -401,20 +874,22 @@ pub fn error_kind(err: &tokio_postgres::Error) -> ErrorKind {
         quotes
     };
 
-    match sql_code {
-        Some(SqlState::UNDEFINED_TABLE) => {
+    let quotes = quote_indices(&string);
+    match (sql_code, quotes.as_slice()) {
+        (Some(SqlState::UNDEFINED_TABLE), [start, end, ..]) => {
             // relation "album" does not exist
-            let quotes = quote_indices(&string);
-            let table = &string[quotes[0] + 1..quotes[1]];
+            let table = &string[start + 1..*end];
             ErrorKind::UndefinedTable { table: table.to_owned() }
         }
-        Some(SqlState::UNDEFINED_COLUMN) => {
-            // column "name" of relation "album" does not exist
-            let quotes = quote_indices(&string);
-            let column = &string[quotes[0] + 1..quotes[1]];
-            let table = &string[quotes[2] + 1..quotes[3]];
-
-            ErrorKind::UndefinedColumn { table: table.to_owned(), column: column.to_owned() }
+        (Some(SqlState::UNDEFINED_COLUMN), [start, end, rest @ ..]) => {
+            // column "name" of relation "album" does not exist, or column "name" does not exist when selecting
+            let column = &string[start + 1..*end];
+            let table = match rest {
+                [start, end, ..] => Some(string[start + 1..*end].to_owned()),
+                _ => None,
+            };
+
+            ErrorKind::UndefinedColumn { table, column: column.to_owned() }
         }
         _ => ErrorKind::Unknown,
     }
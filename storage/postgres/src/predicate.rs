@@ -1,4 +1,9 @@
-use ankql::ast::{ComparisonOperator, Expr, Identifier, Literal, Predicate};
+use ankql::ast::{
+    AggregateFunction, Aggregation, ComparisonOperator, Expr, Identifier, InfixOperator, Literal, OrderByItem, OrderDirection, Predicate,
+    Query, SelectItem,
+};
+use ankql::selection::search::searches;
+use ankurah_proto::Cursor;
 use tokio_postgres::types::ToSql;
 
 pub enum SqlExpr {
@@ -55,7 +60,29 @@ impl Sql {
                     self.sql(format!(r#""{}"."{}""#, collection, name));
                 }
             },
-            _ => unimplemented!("Only literal and identifier expressions are supported"),
+            Expr::ExprList(list) => {
+                self.sql("(");
+                for (i, expr) in list.iter().enumerate() {
+                    if i > 0 {
+                        self.sql(", ");
+                    }
+                    self.expr(expr);
+                }
+                self.sql(")");
+            }
+            Expr::InfixExpr { left, operator, right } => {
+                self.sql("(");
+                self.expr(left);
+                self.sql(format!(" {} ", infix_op_to_sql(operator)));
+                self.expr(right);
+                self.sql(")");
+            }
+            // The parser only accepts values here, but SQL can compare a predicate as a boolean too
+            Expr::Predicate(predicate) => {
+                self.sql("(");
+                self.predicate(predicate);
+                self.sql(")");
+            }
         }
     }
 
@@ -63,6 +90,28 @@ impl Sql {
 
     pub fn predicate(&mut self, predicate: &Predicate) {
         match predicate {
+            Predicate::Comparison { left, operator: ComparisonOperator::Between, right } => match &**right {
+                Expr::ExprList(bounds) if bounds.len() == 2 => {
+                    self.expr(left);
+                    self.sql(" BETWEEN ");
+                    self.expr(&bounds[0]);
+                    self.sql(" AND ");
+                    self.expr(&bounds[1]);
+                }
+                _ => unimplemented!("BETWEEN requires a lower and an upper bound"),
+            },
+            Predicate::Comparison { left, operator: ComparisonOperator::Matches, right } => {
+                self.tsvector(left);
+                self.sql(" @@ ");
+                self.tsquery(right);
+            }
+            Predicate::Comparison { left, operator: ComparisonOperator::Contains, right } => {
+                self.sql("strpos(");
+                self.expr(left);
+                self.sql(", ");
+                self.expr(right);
+                self.sql(") > 0");
+            }
             Predicate::Comparison { left, operator, right } => {
                 self.expr(left);
                 self.sql(" ");
@@ -85,7 +134,7 @@ impl Sql {
             Predicate::Not(pred) => {
                 self.sql("NOT (");
                 self.predicate(pred);
-                self.sql("NOT )");
+                self.sql(")");
             }
             Predicate::IsNull(expr) => {
                 self.expr(expr);
@@ -96,6 +145,158 @@ impl Sql {
             }
         }
     }
+
+    /// Full-text search uses the `simple` configuration, which lowercases words without stemming them, as the other engines do
+    fn tsvector(&mut self, expr: &Expr) {
+        self.sql("to_tsvector('simple', ");
+        self.expr(expr);
+        self.sql(")");
+    }
+
+    fn tsquery(&mut self, expr: &Expr) {
+        self.sql("plainto_tsquery('simple', ");
+        self.expr(expr);
+        self.sql(")");
+    }
+
+    /// The ORDER BY, LIMIT and OFFSET clauses for a query. Rows are always ordered by ID last, so that the order is total,
+    /// and matches the ID order which the other storage engines scan in. When ranked and without an ORDER BY, the results of a
+    /// full-text search are ordered from most to least relevant first.
+    pub fn order_and_window(&mut self, query: &Query, ranked: bool) {
+        self.sql(" ORDER BY ");
+        for item in &query.order_by {
+            let direction = match item.direction {
+                OrderDirection::Asc => "ASC",
+                OrderDirection::Desc => "DESC",
+            };
+            self.sql(format!("{} {}, ", identifier_to_sql(&item.identifier), direction));
+        }
+        let searches = searches(&query.predicate);
+        if ranked && query.order_by.is_empty() && !searches.is_empty() {
+            for (i, (property, search)) in searches.into_iter().enumerate() {
+                if i > 0 {
+                    self.sql(" + ");
+                }
+                self.sql("ts_rank(");
+                self.tsvector(&Expr::Identifier(Identifier::Property(property.to_string())));
+                self.sql(", ");
+                self.tsquery(&Expr::Literal(Literal::String(search.to_string())));
+                self.sql(")");
+            }
+            self.sql(" DESC, ");
+        }
+        self.sql(r#""id""#);
+        if let Some(limit) = query.limit {
+            self.sql(format!(" LIMIT {}", limit));
+        }
+        if let Some(offset) = query.offset {
+            self.sql(format!(" OFFSET {}", offset));
+        }
+    }
+
+    /// Restrict rows to those which come strictly after the cursor, ordering by each ORDER BY item in turn and then by ID.
+    /// NULLs sort last when ascending and first when descending, which is the Postgres default that `order_and_window` relies on.
+    /// The cursor must have one key value per ORDER BY item, which `fetch_page` checks.
+    pub fn after(&mut self, order_by: &[OrderByItem], cursor: &Cursor) {
+        self.sql("(");
+        for position in 0..=order_by.len() {
+            if position > 0 {
+                self.sql(" OR ");
+            }
+            self.sql("(");
+            for (item, key) in order_by[..position].iter().zip(&cursor.key) {
+                self.same_as(item, key);
+                self.sql(" AND ");
+            }
+            match order_by.get(position) {
+                Some(item) => self.beyond(item, &cursor.key[position]),
+                None => {
+                    self.sql(r#""id" > "#);
+                    self.arg(uuid::Uuid::from(ulid::Ulid::from(cursor.id)));
+                }
+            }
+            self.sql(")");
+        }
+        self.sql(")");
+    }
+
+    fn same_as(&mut self, item: &OrderByItem, key: &Option<Literal>) {
+        self.expr(&Expr::Identifier(item.identifier.clone()));
+        match key {
+            Some(key) => {
+                self.sql(" = ");
+                self.expr(&Expr::Literal(key.clone()));
+            }
+            None => self.sql(" IS NULL"),
+        }
+    }
+
+    fn beyond(&mut self, item: &OrderByItem, key: &Option<Literal>) {
+        let identifier = Expr::Identifier(item.identifier.clone());
+        match (item.direction, key) {
+            (OrderDirection::Asc, Some(key)) => {
+                self.sql("(");
+                self.expr(&identifier);
+                self.sql(" > ");
+                self.expr(&Expr::Literal(key.clone()));
+                self.sql(" OR ");
+                self.expr(&identifier);
+                self.sql(" IS NULL)");
+            }
+            // Nothing sorts after a NULL when ascending
+            (OrderDirection::Asc, None) => self.sql("FALSE"),
+            (OrderDirection::Desc, Some(key)) => {
+                self.expr(&identifier);
+                self.sql(" < ");
+                self.expr(&Expr::Literal(key.clone()));
+            }
+            (OrderDirection::Desc, None) => {
+                self.expr(&identifier);
+                self.sql(" IS NOT NULL");
+            }
+        }
+    }
+}
+
+/// The SELECT list for an aggregation. SUMs are cast to text, because Postgres sums integers as NUMERIC, which can't be
+/// read without loss, so they are parsed back into numbers instead
+pub fn select_list(aggregation: &Aggregation) -> String {
+    let items = aggregation.select.iter().map(|item| match item {
+        SelectItem::Property(identifier) => identifier_to_sql(identifier),
+        SelectItem::Aggregate { function, argument: None } => format!("{}(*)", aggregate_function_to_sql(function)),
+        SelectItem::Aggregate { function: AggregateFunction::Sum, argument: Some(identifier) } => {
+            format!("SUM({})::TEXT", identifier_to_sql(identifier))
+        }
+        SelectItem::Aggregate { function, argument: Some(identifier) } => {
+            format!("{}({})", aggregate_function_to_sql(function), identifier_to_sql(identifier))
+        }
+    });
+    items.collect::<Vec<_>>().join(", ")
+}
+
+/// The GROUP BY clause for an aggregation, if it has one. Groups are ordered by the same properties, ascending
+pub fn group_by(aggregation: &Aggregation) -> String {
+    if aggregation.group_by.is_empty() {
+        return String::new();
+    }
+    let properties = aggregation.group_by.iter().map(identifier_to_sql).collect::<Vec<_>>().join(", ");
+    format!(" GROUP BY {0} ORDER BY {0}", properties)
+}
+
+fn identifier_to_sql(identifier: &Identifier) -> String {
+    match identifier {
+        Identifier::Property(name) => format!(r#""{}""#, name),
+        Identifier::CollectionProperty(collection, name) => format!(r#""{}"."{}""#, collection, name),
+    }
+}
+
+fn aggregate_function_to_sql(function: &AggregateFunction) -> &'static str {
+    match function {
+        AggregateFunction::Count => "COUNT",
+        AggregateFunction::Sum => "SUM",
+        AggregateFunction::Min => "MIN",
+        AggregateFunction::Max => "MAX",
+    }
 }
 
 fn comparison_op_to_sql(op: &ComparisonOperator) -> &'static str {
@@ -106,14 +307,29 @@ fn comparison_op_to_sql(op: &ComparisonOperator) -> &'static str {
         ComparisonOperator::GreaterThanOrEqual => ">=",
         ComparisonOperator::LessThan => "<",
         ComparisonOperator::LessThanOrEqual => "<=",
-        _ => unimplemented!("Only basic comparison operators are supported"),
+        ComparisonOperator::In => "IN",
+        ComparisonOperator::Between => "BETWEEN",
+        ComparisonOperator::Like => "LIKE",
+        ComparisonOperator::ILike => "ILIKE",
+        ComparisonOperator::StartsWith => "^@",
+        ComparisonOperator::Contains => unreachable!("CONTAINS is rendered as a call to strpos"),
+        ComparisonOperator::Matches => unreachable!("MATCHES is rendered as a text search"),
+    }
+}
+
+fn infix_op_to_sql(op: &InfixOperator) -> &'static str {
+    match op {
+        InfixOperator::Add => "+",
+        InfixOperator::Subtract => "-",
+        InfixOperator::Multiply => "*",
+        InfixOperator::Divide => "/",
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ankql::parser::parse_selection;
+    use ankql::parser::{parse_aggregation, parse_query, parse_selection};
 
     fn assert_args<'a, 'b>(args: &Vec<Box<dyn ToSql + Send + Sync>>, expected: &Vec<Box<dyn ToSql + Send + Sync>>) {
         // TODO: Maybe actually encoding these and comparing bytes?
@@ -169,4 +385,111 @@ mod tests {
         let expected: Vec<Box<dyn ToSql + Send + Sync>> = vec![Box::new("Alice")];
         assert_args(&args, &expected);
     }
+
+    #[test]
+    fn test_order_and_window() {
+        let order_and_window = |query: &str| {
+            let mut sql = Sql::new();
+            sql.order_and_window(&parse_query(query).unwrap(), true);
+            sql.collapse()
+        };
+
+        let (sql_string, args) = order_and_window("name = 'Alice' ORDER BY age DESC, name LIMIT 10 OFFSET 20");
+        assert_eq!(sql_string, r#" ORDER BY "age" DESC, "name" ASC, "id" LIMIT 10 OFFSET 20"#);
+        assert!(args.is_empty());
+
+        let (sql_string, _) = order_and_window("name = 'Alice'");
+        assert_eq!(sql_string, r#" ORDER BY "id""#);
+    }
+
+    #[test]
+    fn test_matches() {
+        let query = parse_query("notes MATCHES 'rust async' AND year > 2020 LIMIT 5").unwrap();
+
+        let mut sql = Sql::new();
+        sql.predicate(&query.predicate);
+        sql.order_and_window(&query, true);
+        let (sql_string, args) = sql.collapse();
+
+        assert_eq!(
+            sql_string,
+            r#"to_tsvector('simple', "notes") @@ plainto_tsquery('simple', $1) AND "year" > $2 ORDER BY ts_rank(to_tsvector('simple', "notes"), plainto_tsquery('simple', $3)) DESC, "id" LIMIT 5"#
+        );
+        let expected: Vec<Box<dyn ToSql + Send + Sync>> = vec![Box::new("rust async"), Box::new(2020), Box::new("rust async")];
+        assert_args(&args, &expected);
+
+        // Pages keep to ID order
+        let mut sql = Sql::new();
+        sql.order_and_window(&query, false);
+        assert_eq!(sql.collapse().0, r#" ORDER BY "id" LIMIT 5"#);
+    }
+
+    #[test]
+    fn test_patterns() {
+        let predicate = parse_selection("name LIKE 'A%' AND name NOT ILIKE '%b' AND name STARTS WITH 'C' AND name CONTAINS 'd'").unwrap();
+
+        let mut sql = Sql::new();
+        sql.predicate(&predicate);
+        let (sql_string, args) = sql.collapse();
+
+        assert_eq!(sql_string, r#""name" LIKE $1 AND NOT ("name" ILIKE $2) AND "name" ^@ $3 AND strpos("name", $4) > 0"#);
+        let expected: Vec<Box<dyn ToSql + Send + Sync>> = vec![Box::new("A%"), Box::new("%b"), Box::new("C"), Box::new("d")];
+        assert_args(&args, &expected);
+    }
+
+    #[test]
+    fn test_aggregation() {
+        let aggregation = parse_aggregation("SELECT artist, COUNT(*), SUM(copies), MAX(price) WHERE year > 2000 GROUP BY artist").unwrap();
+        assert_eq!(select_list(&aggregation), r#""artist", COUNT(*), SUM("copies")::TEXT, MAX("price")"#);
+        assert_eq!(group_by(&aggregation), r#" GROUP BY "artist" ORDER BY "artist""#);
+
+        let aggregation = parse_aggregation("SELECT COUNT(copies)").unwrap();
+        assert_eq!(select_list(&aggregation), r#"COUNT("copies")"#);
+        assert_eq!(group_by(&aggregation), "");
+    }
+
+    #[test]
+    fn test_after_cursor() {
+        let query = parse_query("ORDER BY year DESC, name").unwrap();
+        let id = ankurah_proto::ID::new();
+        let cursor = Cursor::new(vec![None, Some(Literal::String("Drones".to_string()))], id);
+
+        let mut sql = Sql::new();
+        sql.after(&query.order_by, &cursor);
+        let (sql_string, args) = sql.collapse();
+
+        assert_eq!(
+            sql_string,
+            r#"(("year" IS NOT NULL) OR ("year" IS NULL AND ("name" > $1 OR "name" IS NULL)) OR ("year" IS NULL AND "name" = $2 AND "id" > $3))"#
+        );
+        let expected: Vec<Box<dyn ToSql + Send + Sync>> =
+            vec![Box::new("Drones"), Box::new("Drones"), Box::new(uuid::Uuid::from(ulid::Ulid::from(id)))];
+        assert_args(&args, &expected);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let predicate = parse_selection("price * quantity > 100 AND price - discount <= 9.5").unwrap();
+
+        let mut sql = Sql::new();
+        sql.predicate(&predicate);
+        let (sql_string, args) = sql.collapse();
+
+        assert_eq!(sql_string, r#"("price" * "quantity") > $1 AND ("price" - "discount") <= $2"#);
+        let expected: Vec<Box<dyn ToSql + Send + Sync>> = vec![Box::new(100), Box::new(9.5)];
+        assert_args(&args, &expected);
+    }
+
+    #[test]
+    fn test_in_and_between() {
+        let predicate = parse_selection("status NOT IN ('open', 'pending') AND year BETWEEN 1990 AND 2000").unwrap();
+
+        let mut sql = Sql::new();
+        sql.predicate(&predicate);
+        let (sql_string, args) = sql.collapse();
+
+        assert_eq!(sql_string, r#"NOT ("status" IN ($1, $2)) AND "year" BETWEEN $3 AND $4"#);
+        let expected: Vec<Box<dyn ToSql + Send + Sync>> = vec![Box::new("open"), Box::new("pending"), Box::new(1990), Box::new(2000)];
+        assert_args(&args, &expected);
+    }
 }
@@ -0,0 +1,166 @@
+//! Versioning of the encoding of each collection's trees. The version a collection was written with is recorded in the
+//! database's default tree, and older collections are migrated to the current version when they're opened.
+
+use std::collections::BTreeMap;
+
+use ankurah_core::storage::search_terms;
+use ankurah_proto::{Clock, CollectionId, Event, Operation, State, ID};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::sled::SledStorageCollection;
+
+/// The version of the encoding which collections are migrated to
+/// - 0: states and events without tombstones
+/// - 1: states and events with tombstones
+/// - 2: an index of the search terms in each state
+pub const CURRENT_VERSION: u32 = 2;
+
+/// The trees of a collection which hold encoded values
+pub struct Trees<'a> {
+    pub states: &'a sled::Tree,
+    pub events: &'a sled::Tree,
+    pub terms: &'a sled::Tree,
+}
+
+/// Migrate a collection to the current version of the encoding, if it was written with an older one
+pub fn migrate(db: &sled::Db, collection_id: &CollectionId, trees: Trees) -> anyhow::Result<()> {
+    let key = version_key(collection_id);
+    let version = match db.get(&key)? {
+        Some(bytes) => bincode::deserialize(&bytes)?,
+        None => 0,
+    };
+    if version >= CURRENT_VERSION {
+        return Ok(());
+    }
+
+    if version < 1 {
+        reencode::<State, UntombstonedState>(trees.states)?;
+        reencode::<Event, UntombstonedEvent>(trees.events)?;
+    }
+    if version < 2 {
+        index_terms(collection_id, trees.states, trees.terms)?;
+    }
+
+    db.insert(key, bincode::serialize(&CURRENT_VERSION)?)?;
+    Ok(())
+}
+
+fn version_key(collection_id: &CollectionId) -> Vec<u8> { format!("format/{}", collection_id.as_str()).into_bytes() }
+
+/// Re-encode each value in a tree which is still in the older encoding `O`.
+/// Versions before the encoding was recorded may have written either one. The older encoding is a prefix of the newer, so a
+/// value in the newer one would also decode as the older, but not the other way around.
+fn reencode<T, O>(tree: &sled::Tree) -> anyhow::Result<()>
+where
+    T: Serialize + DeserializeOwned,
+    O: DeserializeOwned + Into<T>,
+{
+    let mut batch = sled::Batch::default();
+    for item in tree.iter() {
+        let (key, value) = item?;
+        if bincode::deserialize::<T>(&value).is_err() {
+            let old: O = bincode::deserialize(&value)?;
+            batch.insert(key, bincode::serialize(&old.into())?);
+        }
+    }
+    tree.apply_batch(batch)?;
+    Ok(())
+}
+
+/// Index the search terms of every state, which were only indexed as states were set once the index existed
+fn index_terms(collection_id: &CollectionId, states: &sled::Tree, terms: &sled::Tree) -> anyhow::Result<()> {
+    let mut batch = sled::Batch::default();
+    for item in states.iter() {
+        let (key, value) = item?;
+        let id = ID::from_ulid(ulid::Ulid::from_bytes(key.as_ref().try_into()?));
+        for (property, term) in search_terms(id, collection_id.clone(), &bincode::deserialize(&value)?)? {
+            batch.insert(SledStorageCollection::term_key(&property, &term, id), sled::IVec::default());
+        }
+    }
+    terms.apply_batch(batch)?;
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
+struct UntombstonedState {
+    state_buffers: BTreeMap<String, Vec<u8>>,
+    head: Clock,
+}
+
+impl From<UntombstonedState> for State {
+    fn from(state: UntombstonedState) -> Self { State { state_buffers: state.state_buffers, head: state.head, tombstone: false } }
+}
+
+#[derive(Serialize, Deserialize)]
+struct UntombstonedEvent {
+    id: ID,
+    collection: CollectionId,
+    entity_id: ID,
+    operations: BTreeMap<String, Vec<Operation>>,
+    parent: Clock,
+}
+
+impl From<UntombstonedEvent> for Event {
+    fn from(event: UntombstonedEvent) -> Self {
+        Event {
+            id: event.id,
+            collection: event.collection,
+            entity_id: event.entity_id,
+            operations: event.operations,
+            parent: event.parent,
+            tombstone: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ankurah_core::property::backend::{PropertyBackend, YrsBackend};
+
+    #[test]
+    fn test_migrate_untombstoned() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let states = db.open_tree("album").unwrap();
+        let events = db.open_tree("album_events").unwrap();
+        let terms = db.open_tree("album_terms").unwrap();
+        let collection_id: CollectionId = "album".into();
+
+        let (old_id, new_id, event_id) = (ID::new(), ID::new(), ID::new());
+        let yrs = YrsBackend::new();
+        yrs.insert("name", 0, "Origin of Symmetry");
+        let buffers = BTreeMap::from([("yrs".to_string(), yrs.to_state_buffer().unwrap())]);
+        let head = Clock::new([event_id]);
+        let old_state = UntombstonedState { state_buffers: buffers.clone(), head: head.clone() };
+        let new_state = State { state_buffers: buffers.clone(), head: head.clone(), tombstone: true };
+        states.insert(old_id.to_bytes(), bincode::serialize(&old_state).unwrap()).unwrap();
+        states.insert(new_id.to_bytes(), bincode::serialize(&new_state).unwrap()).unwrap();
+        let old_event = UntombstonedEvent {
+            id: event_id,
+            collection: collection_id.clone(),
+            entity_id: old_id,
+            operations: BTreeMap::new(),
+            parent: Clock::default(),
+        };
+        events.insert(event_id.to_bytes(), bincode::serialize(&old_event).unwrap()).unwrap();
+
+        migrate(&db, &collection_id, Trees { states: &states, events: &events, terms: &terms }).unwrap();
+
+        let state = |id: ID| -> State { bincode::deserialize(&states.get(id.to_bytes()).unwrap().unwrap()).unwrap() };
+        assert_eq!(state(old_id), State { state_buffers: buffers, head, tombstone: false });
+        // States which were already in the newer encoding keep their tombstones
+        assert_eq!(state(new_id), new_state);
+        let event: Event = bincode::deserialize(&events.get(event_id.to_bytes()).unwrap().unwrap()).unwrap();
+        assert_eq!((event.entity_id, event.tombstone), (old_id, false));
+
+        // The terms of states written before they were indexed are indexed, except for deleted entities
+        let indexed = terms.iter().keys().collect::<Result<Vec<_>, _>>().unwrap();
+        let expected = ["origin", "of", "symmetry"].map(|term| SledStorageCollection::term_key("name", term, old_id));
+        assert_eq!(indexed.len(), expected.len());
+        assert!(expected.iter().all(|key| terms.contains_key(key).unwrap()));
+
+        // The version is recorded, so nothing is decoded again
+        assert_eq!(db.get(version_key(&collection_id)).unwrap().unwrap(), bincode::serialize(&CURRENT_VERSION).unwrap());
+        migrate(&db, &collection_id, Trees { states: &states, events: &events, terms: &terms }).unwrap();
+    }
+}
@@ -1,3 +1,4 @@
+mod format;
 mod sled;
 
 pub use sled::SledStorageEngine;
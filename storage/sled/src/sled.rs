@@ -1,20 +1,26 @@
-use ankurah_proto::{CollectionId, State, ID};
+use ankurah_proto::{CollectionId, Event, NodeId, State, ID};
 use anyhow::Result;
 use async_trait::async_trait;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
 
 use ankurah_core::{
     error::RetrievalError,
     model::Entity,
-    storage::{StorageCollection, StorageEngine},
+    storage::{search_terms, StorageCollection, StorageEngine},
 };
 
-use ankql::selection::filter::evaluate_predicate;
+use ankql::selection::{
+    filter::evaluate_predicate,
+    order::order_and_window,
+    search::{required_searches, searches, tokenize},
+};
 use sled::{Config, Db};
 use tokio::task;
 
+use crate::format;
+
 pub struct SledStorageEngine {
     pub db: Db,
 }
@@ -44,26 +50,109 @@ impl SledStorageEngine {
 }
 
 pub struct SledStorageCollection {
+    pub collection_id: CollectionId,
     pub tree: sled::Tree,
+    /// Event log for the collection, keyed by entity ID followed by event ID
+    pub events: sled::Tree,
+    /// Newest checkpoint of each entity, keyed by entity ID
+    pub checkpoints: sled::Tree,
+    /// Inverted index of the full-text search terms in each string property, keyed by property, term and entity ID
+    pub terms: sled::Tree,
+    /// Durable peers which have acknowledged each event, keyed by event ID followed by node ID
+    pub acknowledgements: sled::Tree,
 }
 
-#[async_trait]
-impl StorageEngine for SledStorageEngine {
-    async fn collection(&self, id: &CollectionId) -> anyhow::Result<Arc<dyn StorageCollection>> {
+impl SledStorageCollection {
+    /// The trees besides a collection's states are named with a `/`, which collection IDs can't contain, so they can't be
+    /// mistaken for another collection's states
+    fn event_tree_name(collection_id: &CollectionId) -> String { format!("{}/events", collection_id.as_str()) }
+
+    fn checkpoint_tree_name(collection_id: &CollectionId) -> String { format!("{}/checkpoints", collection_id.as_str()) }
+
+    fn terms_tree_name(collection_id: &CollectionId) -> String { format!("{}/terms", collection_id.as_str()) }
+
+    fn acknowledgement_tree_name(collection_id: &CollectionId) -> String { format!("{}/acks", collection_id.as_str()) }
+
+    fn event_key(entity_id: ID, event_id: ID) -> Vec<u8> { [entity_id.to_bytes(), event_id.to_bytes()].concat() }
+
+    fn acknowledgement_key(event_id: ID, peer_id: &NodeId) -> Vec<u8> { [event_id.to_bytes(), peer_id.to_bytes()].concat() }
+
+    fn term_prefix(property: &str, term: &str) -> Vec<u8> { [property.as_bytes(), &[0], term.as_bytes(), &[0]].concat() }
+
+    pub(crate) fn term_key(property: &str, term: &str, id: ID) -> Vec<u8> {
+        [Self::term_prefix(property, term), id.to_bytes().to_vec()].concat()
+    }
+}
+
+impl SledStorageEngine {
+    /// Open the trees of a collection, migrating them if they were written with an older version of the encoding
+    fn open_collection(&self, id: &CollectionId) -> anyhow::Result<SledStorageCollection> {
+        if id.as_str().contains('/') {
+            return Err(RetrievalError::InvalidBucketName.into());
+        }
+
         // could this block for any meaningful period of time? We might consider spawn_blocking
         let tree = self.db.open_tree(id.as_str())?;
-        Ok(Arc::new(SledStorageCollection { tree }))
+        let events = self.db.open_tree(SledStorageCollection::event_tree_name(id))?;
+        let checkpoints = self.db.open_tree(SledStorageCollection::checkpoint_tree_name(id))?;
+        let terms = self.db.open_tree(SledStorageCollection::terms_tree_name(id))?;
+        let acknowledgements = self.db.open_tree(SledStorageCollection::acknowledgement_tree_name(id))?;
+        format::migrate(&self.db, id, format::Trees { states: &tree, events: &events, terms: &terms })?;
+        Ok(SledStorageCollection { collection_id: id.clone(), tree, events, checkpoints, terms, acknowledgements })
+    }
+}
+
+/// The IDs of the entities containing every term searched for by the predicate's required MATCHES comparisons,
+/// or None if it has none and so every entity has to be scanned
+fn search_candidates(terms: &sled::Tree, predicate: &ankql::ast::Predicate) -> Result<Option<BTreeSet<ID>>, RetrievalError> {
+    let searches = required_searches(predicate);
+    if searches.is_empty() {
+        return Ok(None);
+    }
+
+    let mut candidates: Option<BTreeSet<ID>> = None;
+    for (property, search) in searches {
+        let search_terms = tokenize(search);
+        // A search without any terms matches nothing
+        if search_terms.is_empty() {
+            return Ok(Some(BTreeSet::new()));
+        }
+        for term in search_terms {
+            let prefix = SledStorageCollection::term_prefix(property, &term);
+            let mut ids = BTreeSet::new();
+            for item in terms.scan_prefix(&prefix) {
+                let (key, _) = item.map_err(SledRetrievalError::StorageError)?;
+                let id_bytes = key[prefix.len()..].try_into().map_err(RetrievalError::storage)?;
+                ids.insert(ID::from_ulid(ulid::Ulid::from_bytes(id_bytes)));
+            }
+            let narrowed = match candidates {
+                Some(candidates) => candidates.intersection(&ids).copied().collect(),
+                None => ids,
+            };
+            if narrowed.is_empty() {
+                return Ok(Some(narrowed));
+            }
+            candidates = Some(narrowed);
+        }
     }
+    Ok(candidates)
+}
+
+#[async_trait]
+impl StorageEngine for SledStorageEngine {
+    async fn collection(&self, id: &CollectionId) -> anyhow::Result<Arc<dyn StorageCollection>> { Ok(Arc::new(self.open_collection(id)?)) }
 
-    async fn fetch_states(
-        &self,
-        collection_id: CollectionId,
-        predicate: &ankql::ast::Predicate,
-    ) -> Result<Vec<(ID, State)>, RetrievalError> {
-        let tree = self.db.open_tree(collection_id.as_str()).map_err(SledRetrievalError::StorageError)?;
-        let bucket = SledStorageCollection { tree };
+    async fn fetch_states(&self, collection_id: CollectionId, query: &ankql::ast::Query) -> Result<Vec<(ID, State)>, RetrievalError> {
+        let SledStorageCollection { tree, terms, .. } = self.open_collection(&collection_id)?;
 
-        let predicate = predicate.clone();
+        let query = query.clone();
+        // Without an ORDER BY or a search to rank by, results are in ID order, so the scan can stop as soon as the window is filled
+        let enough = match query.limit {
+            Some(limit) if query.order_by.is_empty() && searches(&query.predicate).is_empty() => {
+                (query.offset.unwrap_or(0) + limit) as usize
+            }
+            _ => usize::MAX,
+        };
 
         // Use spawn_blocking for the full scan operation
         task::spawn_blocking(move || -> Result<Vec<(ID, State)>, RetrievalError> {
@@ -71,10 +160,22 @@ impl StorageEngine for SledStorageEngine {
             let mut seen_ids = HashSet::new();
             // println!("SledStorageEngine: Starting fetch_states scan");
 
-            // For now, do a full table scan
-            for item in bucket.tree.iter() {
-                let (key_bytes, value_bytes) = item.map_err(SledRetrievalError::StorageError)?;
-                let id = ID::from_ulid(ulid::Ulid::from_bytes(key_bytes.as_ref().try_into().map_err(RetrievalError::storage)?));
+            // A full-text search only needs to look at the entities with all of its terms, and otherwise we do a full table scan
+            let items: Box<dyn Iterator<Item = Result<(ID, sled::IVec), RetrievalError>>> =
+                match search_candidates(&terms, &query.predicate)? {
+                    Some(candidates) => Box::new(candidates.into_iter().filter_map(|id| match tree.get(id.to_bytes()) {
+                        Ok(value) => value.map(|value_bytes| Ok((id, value_bytes))),
+                        Err(e) => Some(Err(SledRetrievalError::StorageError(e).into())),
+                    })),
+                    None => Box::new(tree.iter().map(|item| {
+                        let (key_bytes, value_bytes) = item.map_err(SledRetrievalError::StorageError)?;
+                        let id = ID::from_ulid(ulid::Ulid::from_bytes(key_bytes.as_ref().try_into().map_err(RetrievalError::storage)?));
+                        Ok((id, value_bytes))
+                    })),
+                };
+
+            for item in items {
+                let (id, value_bytes) = item?;
 
                 // Skip if we've already seen this ID
                 if seen_ids.contains(&id) {
@@ -84,14 +185,22 @@ impl StorageEngine for SledStorageEngine {
 
                 let entity_state: State = bincode::deserialize(&value_bytes)?;
 
+                // Deleted entities are retained so their tombstones can be merged, but never match
+                if entity_state.tombstone {
+                    continue;
+                }
+
                 // Create entity to evaluate predicate
                 let entity = Entity::from_state(id, collection_id.clone(), &entity_state)?;
 
                 // Apply predicate filter
-                if evaluate_predicate(&entity, &predicate)? {
+                if evaluate_predicate(&entity, &query.predicate)? {
                     // println!("SledStorageEngine: Found matching entity with ID: {:?}", id);
                     seen_ids.insert(id);
-                    results.push((id, entity_state));
+                    results.push((id, entity_state, entity));
+                    if results.len() >= enough {
+                        break;
+                    }
                 }
             }
 
@@ -99,7 +208,8 @@ impl StorageEngine for SledStorageEngine {
             //     "SledStorageEngine: Finished fetch_states scan, found {} matches",
             //     results.len()
             // );
-            Ok(results)
+            let results = order_and_window(results, &query, |(_, _, entity)| entity);
+            Ok(results.into_iter().map(|(id, state, _)| (id, state)).collect())
         })
         .await
         .map_err(RetrievalError::future_join)?
@@ -110,17 +220,33 @@ impl StorageEngine for SledStorageEngine {
 impl StorageCollection for SledStorageCollection {
     async fn set_state(&self, id: ID, state: &State) -> anyhow::Result<bool> {
         let tree = self.tree.clone();
+        let terms = self.terms.clone();
+        let collection_id = self.collection_id.clone();
         let binary_state = bincode::serialize(state)?;
+        let new_terms = search_terms(id, collection_id.clone(), state)?;
         let id_bytes = id.to_bytes();
 
         // Use spawn_blocking since sled operations are not async
         task::spawn_blocking(move || {
             let last = tree.insert(id_bytes, binary_state.clone())?;
-            if let Some(last_bytes) = last {
-                Ok(last_bytes != binary_state)
-            } else {
-                Ok(true)
+            let changed = last.as_ref().is_none_or(|last_bytes| *last_bytes != binary_state);
+
+            // Keep the search index in step, replacing the terms of the state we overwrote with the new ones
+            if changed {
+                let old_terms = match last {
+                    Some(last_bytes) => search_terms(id, collection_id, &bincode::deserialize(&last_bytes)?)?,
+                    None => BTreeSet::new(),
+                };
+                let mut batch = sled::Batch::default();
+                for (property, term) in old_terms.difference(&new_terms) {
+                    batch.remove(Self::term_key(property, term, id));
+                }
+                for (property, term) in new_terms.difference(&old_terms) {
+                    batch.insert(Self::term_key(property, term, id), sled::IVec::default());
+                }
+                terms.apply_batch(batch)?;
             }
+            Ok(changed)
         })
         .await?
     }
@@ -142,6 +268,117 @@ impl StorageCollection for SledStorageCollection {
             None => Err(SledRetrievalError::NotFound(id).into()),
         }
     }
+
+    async fn add_event(&self, entity_event: &Event) -> anyhow::Result<bool> {
+        let events = self.events.clone();
+        let key = Self::event_key(entity_event.entity_id, entity_event.id);
+        let binary_event = bincode::serialize(entity_event)?;
+
+        // Events are immutable, so we only insert if the key is not already present
+        task::spawn_blocking(move || {
+            let result = events.compare_and_swap(key, None as Option<&[u8]>, Some(binary_event))?;
+            Ok(result.is_ok())
+        })
+        .await?
+    }
+
+    async fn get_events(&self, id: ID) -> Result<Vec<Event>, RetrievalError> {
+        let events = self.events.clone();
+
+        task::spawn_blocking(move || -> Result<Vec<Event>, RetrievalError> {
+            let mut results = Vec::new();
+            // Keys are prefixed by the entity ID and sorted by event ID, which is a ULID
+            for item in events.scan_prefix(id.to_bytes()) {
+                let (_key, value_bytes) = item.map_err(SledRetrievalError::StorageError)?;
+                results.push(bincode::deserialize(&value_bytes)?);
+            }
+            Ok(results)
+        })
+        .await
+        .map_err(RetrievalError::future_join)?
+    }
+
+    async fn remove_events(&self, id: ID, event_ids: &[ID]) -> anyhow::Result<()> {
+        let events = self.events.clone();
+        let acknowledgements = self.acknowledgements.clone();
+        let event_ids = event_ids.to_vec();
+
+        task::spawn_blocking(move || {
+            let mut batch = sled::Batch::default();
+            let mut acknowledgement_batch = sled::Batch::default();
+            for event_id in event_ids {
+                batch.remove(Self::event_key(id, event_id));
+                for item in acknowledgements.scan_prefix(event_id.to_bytes()) {
+                    acknowledgement_batch.remove(item?.0);
+                }
+            }
+            events.apply_batch(batch)?;
+            acknowledgements.apply_batch(acknowledgement_batch)?;
+            Ok(())
+        })
+        .await?
+    }
+
+    async fn acknowledge_events(&self, peer_id: &NodeId, event_ids: &[ID]) -> anyhow::Result<()> {
+        let acknowledgements = self.acknowledgements.clone();
+        let keys: Vec<Vec<u8>> = event_ids.iter().map(|event_id| Self::acknowledgement_key(*event_id, peer_id)).collect();
+
+        task::spawn_blocking(move || {
+            let mut batch = sled::Batch::default();
+            for key in keys {
+                batch.insert(key, &[]);
+            }
+            acknowledgements.apply_batch(batch)?;
+            Ok(())
+        })
+        .await?
+    }
+
+    async fn get_acknowledgements(&self, event_ids: &[ID]) -> Result<BTreeMap<ID, BTreeSet<NodeId>>, RetrievalError> {
+        let acknowledgements = self.acknowledgements.clone();
+        let event_ids = event_ids.to_vec();
+
+        task::spawn_blocking(move || -> Result<BTreeMap<ID, BTreeSet<NodeId>>, RetrievalError> {
+            let mut results: BTreeMap<ID, BTreeSet<NodeId>> = BTreeMap::new();
+            for event_id in event_ids {
+                // Keys are the event ID followed by the node ID, so each key under the event's prefix names a peer
+                for item in acknowledgements.scan_prefix(event_id.to_bytes()) {
+                    let (key, _) = item.map_err(SledRetrievalError::StorageError)?;
+                    let node_bytes: [u8; 16] = key[16..].try_into().map_err(|e| SledRetrievalError::Other(Box::new(e)))?;
+                    results.entry(event_id).or_default().insert(NodeId::from_ulid(ulid::Ulid::from_bytes(node_bytes)));
+                }
+            }
+            Ok(results)
+        })
+        .await
+        .map_err(RetrievalError::future_join)?
+    }
+
+    async fn checkpoint(&self, id: ID, state: &State) -> anyhow::Result<()> {
+        let checkpoints = self.checkpoints.clone();
+        let binary_state = bincode::serialize(state)?;
+        let id_bytes = id.to_bytes();
+
+        task::spawn_blocking(move || {
+            checkpoints.insert(id_bytes, binary_state)?;
+            Ok(())
+        })
+        .await?
+    }
+
+    async fn get_checkpoint(&self, id: ID) -> Result<Option<State>, RetrievalError> {
+        let checkpoints = self.checkpoints.clone();
+        let id_bytes = id.to_bytes();
+
+        let result = task::spawn_blocking(move || -> Result<Option<sled::IVec>, sled::Error> { checkpoints.get(id_bytes) })
+            .await
+            .map_err(|e| SledRetrievalError::Other(Box::new(e)))?;
+
+        match result.map_err(SledRetrievalError::StorageError)? {
+            Some(ivec) => Ok(Some(bincode::deserialize(&ivec)?)),
+            None => Ok(None),
+        }
+    }
 }
 
 enum SledRetrievalError {
@@ -0,0 +1,70 @@
+mod common;
+
+use ankql::ast::Literal;
+use ankurah::{Model, Node};
+use ankurah_connector_local_process::LocalProcessConnection;
+use ankurah_storage_sled::SledStorageEngine;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use common::for_each_engine;
+
+#[derive(Model, Debug, Serialize, Deserialize)]
+pub struct Sale {
+    pub artist: String,
+    #[active_type(LWW)]
+    pub copies: i64,
+    #[active_type(LWW)]
+    pub price: f64,
+}
+
+async fn create_sales(node: &Arc<Node>) -> Result<()> {
+    let trx = node.begin();
+    trx.create(&Sale { artist: "Muse".into(), copies: 3, price: 9.5 }).await;
+    trx.create(&Sale { artist: "Blur".into(), copies: 2, price: 7.0 }).await;
+    trx.create(&Sale { artist: "Muse".into(), copies: 4, price: 8.0 }).await;
+    trx.create(&Sale { artist: "Muse".into(), copies: 1, price: 12.0 }).await;
+    trx.commit().await?;
+    Ok(())
+}
+
+async fn assert_aggregates(node: &Arc<Node>) -> Result<()> {
+    let int = |value: i64| Some(Literal::Integer(value));
+    let float = |value: f64| Some(Literal::Float(value));
+    let string = |value: &str| Some(Literal::String(value.to_string()));
+
+    assert_eq!(
+        node.aggregate::<SaleView>("SELECT artist, COUNT(*), SUM(copies), MIN(price), MAX(price) GROUP BY artist").await?,
+        [vec![string("Blur"), int(1), int(2), float(7.0), float(7.0)], vec![string("Muse"), int(3), int(8), float(8.0), float(12.0)]]
+    );
+    assert_eq!(node.aggregate::<SaleView>("SELECT COUNT(*), SUM(copies) WHERE price > 8.5").await?, [vec![int(2), int(4)]]);
+    assert_eq!(node.aggregate::<SaleView>("SELECT SUM(price)").await?, [vec![float(36.5)]]);
+
+    // Without a GROUP BY there is always a row, even when nothing matches
+    assert_eq!(node.aggregate::<SaleView>("SELECT COUNT(*), SUM(copies) WHERE artist = 'Oasis'").await?, [vec![int(0), None]]);
+    assert!(node.aggregate::<SaleView>("SELECT artist, COUNT(*) WHERE artist = 'Oasis' GROUP BY artist").await?.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn aggregate() -> Result<()> {
+    for_each_engine(|node| async move {
+        create_sales(&node).await?;
+        assert_aggregates(&node).await
+    })
+    .await
+}
+
+#[tokio::test]
+async fn peer_aggregate() -> Result<()> {
+    let server = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
+    let client = Node::new(Arc::new(SledStorageEngine::new_test().unwrap()));
+    create_sales(&server).await?;
+
+    // A node without the whole collection can't aggregate it by itself
+    assert!(client.aggregate::<SaleView>("SELECT COUNT(*)").await.is_err());
+
+    let _conn = LocalProcessConnection::new(&server, &client).await?;
+    assert_aggregates(&client).await
+}
@@ -0,0 +1,57 @@
+mod common;
+use ankurah::{
+    model::View,
+    proto::{Clock, ClockOrdering},
+    ID,
+};
+use anyhow::Result;
+use common::*;
+use std::collections::BTreeMap;
+
+#[tokio::test]
+async fn concurrent_edits() -> Result<()> {
+    for_each_engine(|node| async move {
+        let album: AlbumView = create_and_read(&node, &Album { name: "The rest of the owl".to_owned(), year: "2024".to_owned() }).await?;
+        let created = album.entity().head();
+        assert_eq!(created.len(), 1);
+
+        // Two transactions which start from the same head
+        let trx1 = node.begin();
+        let trx2 = node.begin();
+        album.edit(&trx1).await?.name().overwrite(0, 3, "Any");
+        album.edit(&trx2).await?.year().overwrite(0, 4, "2025");
+        trx1.commit().await?;
+        trx2.commit().await?;
+
+        // Both edits are retained, and the head tracks both branches
+        assert_eq!(album.name(), "Any rest of the owl");
+        assert_eq!(album.year(), "2025");
+        let merged = album.entity().head();
+        assert_eq!(merged.len(), 2);
+
+        let events = node.collection(&"album".into()).await.get_events(album.id()).await?;
+        let parents: BTreeMap<_, _> = events.iter().map(|e| (e.id, &e.parent)).collect();
+        let parent_of = |id: &ID| parents.get(id).copied();
+        let branches: Vec<Clock> = merged.iter().map(|id| Clock::new([*id])).collect();
+        assert_eq!(branches[0].compare(&branches[1], parent_of), ClockOrdering::Concurrent);
+        assert_eq!(merged.compare(&created, parent_of), ClockOrdering::Descends);
+        assert_eq!(created.compare(&merged, parent_of), ClockOrdering::Ancestor);
+
+        // The next edit descends from both branches, collapsing the head again
+        {
+            let trx = node.begin();
+            album.edit(&trx).await?.name().overwrite(0, 3, "The");
+            trx.commit().await?;
+        }
+        let collapsed = album.entity().head();
+        assert_eq!(collapsed.len(), 1);
+        assert_eq!(album.name(), "The rest of the owl");
+
+        let events = node.collection(&"album".into()).await.get_events(album.id()).await?;
+        let last = events.iter().find(|e| collapsed.contains(&e.id)).unwrap();
+        assert_eq!(last.parent, merged);
+
+        Ok(())
+    })
+    .await
+}
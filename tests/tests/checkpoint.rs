@@ -0,0 +1,89 @@
+mod common;
+use ankurah::{error::RetrievalError, model::View, Node};
+use ankurah_connector_local_process::LocalProcessConnection;
+use ankurah_storage_sled::SledStorageEngine;
+use anyhow::Result;
+use common::*;
+use std::sync::Arc;
+
+#[tokio::test]
+async fn checkpoint() -> Result<()> {
+    for_each_engine(|node| async move {
+        let album: AlbumView = create_and_read(&node, &Album { name: "Showbiz".into(), year: "1999".into() }).await?;
+        let created = album.entity().head();
+
+        {
+            let trx = node.begin();
+            album.edit(&trx).await?.name().overwrite(0, 7, "Origin of Symmetry");
+            trx.commit().await?;
+        }
+
+        // Without a checkpoint there is nothing to compact against
+        assert_eq!(node.compact(&"album".into(), album.id()).await?, 0);
+
+        let checkpointed = node.checkpoint(&"album".into(), album.id()).await?;
+        assert_eq!(checkpointed, album.entity().head());
+
+        {
+            let trx = node.begin();
+            album.edit(&trx).await?.year().overwrite(0, 4, "2001");
+            trx.commit().await?;
+        }
+        let latest = album.entity().head();
+
+        // Only the event after the checkpoint is retained
+        assert_eq!(node.compact(&"album".into(), album.id()).await?, 2);
+        let events = node.collection(&"album".into()).await.get_events(album.id()).await?;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].parent, checkpointed);
+
+        // Compacting again is a no-op
+        assert_eq!(node.compact(&"album".into(), album.id()).await?, 0);
+
+        // Versions at or after the checkpoint can still be reconstructed
+        let current: AlbumView = node.get_at(album.id(), &latest).await?;
+        assert_eq!((current.name(), current.year()), ("Origin of Symmetry".to_string(), "2001".to_string()));
+        let at_checkpoint: AlbumView = node.get_at(album.id(), &checkpointed).await?;
+        assert_eq!((at_checkpoint.name(), at_checkpoint.year()), ("Origin of Symmetry".to_string(), "1999".to_string()));
+
+        // ...but anything before it is gone
+        match node.get_at::<AlbumView>(album.id(), &created).await {
+            Err(RetrievalError::MissingEvent(_)) => {}
+            other => panic!("Expected MissingEvent, got {:?}", other.map(|a| a.name())),
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn compaction_requires_durable_acknowledgement() -> Result<()> {
+    let server = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
+    let storage = Arc::new(SledStorageEngine::new_test().unwrap());
+    let client = Node::new(storage.clone());
+
+    let album: AlbumView = {
+        let _conn = LocalProcessConnection::new(&server, &client).await?;
+        create_and_read(&client, &Album { name: "Showbiz".into(), year: "1999".into() }).await?
+    };
+    client.checkpoint(&"album".into(), album.id()).await?;
+
+    // The server's acknowledgement is recorded in storage, so it survives the client restarting
+    let restarted = Node::new(storage.clone());
+    assert_eq!(restarted.compact(&"album".into(), album.id()).await?, 1);
+
+    // An edit made while disconnected has never been acknowledged, so it has to be retained
+    {
+        let trx = client.begin();
+        album.edit(&trx).await?.year().overwrite(0, 4, "2001");
+        trx.commit().await?;
+    }
+    client.checkpoint(&"album".into(), album.id()).await?;
+
+    let restarted = Node::new(storage);
+    assert_eq!(restarted.compact(&"album".into(), album.id()).await?, 0);
+    assert_eq!(restarted.collection(&"album".into()).await.get_events(album.id()).await?.len(), 1);
+
+    Ok(())
+}
@@ -2,12 +2,19 @@ use tracing::Level;
 
 use ankurah::{
     changes::{ChangeKind, ChangeSet},
-    model::View,
-    proto, Model,
+    model::{Mutable, View},
+    proto, Model, Node, ResultSet,
 };
+use ankurah_storage_sled::SledStorageEngine;
 use serde::{Deserialize, Serialize};
+use std::future::Future;
 use std::sync::{mpsc, Arc, Mutex};
 
+#[cfg(feature = "postgres")]
+#[allow(unused)]
+#[path = "pg_common.rs"]
+mod pg_common;
+
 #[derive(Debug, Clone, Model)]
 pub struct Pet {
     pub name: String,
@@ -20,6 +27,74 @@ pub struct Album {
     pub year: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Format {
+    Vinyl,
+    CD,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dimensions {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Model, Debug, Serialize, Deserialize)]
+pub struct Release {
+    pub title: String,
+    #[active_type(LWW)]
+    pub year: i64,
+    #[active_type(LWW)]
+    pub remastered: bool,
+    #[active_type(LWW)]
+    pub rating: f64,
+    #[active_type(LWW)]
+    pub format: Format,
+    #[active_type(LWW)]
+    pub sleeve: Dimensions,
+}
+
+/// Create an album for each name and year in a single transaction
+#[allow(unused)]
+pub async fn create_albums(node: &Arc<Node>, albums: &[(&str, &str)]) -> anyhow::Result<()> {
+    let trx = node.begin();
+    for (name, year) in albums {
+        trx.create(&Album { name: name.to_string(), year: year.to_string() }).await;
+    }
+    trx.commit().await?;
+    Ok(())
+}
+
+/// Run `test` against a durable node backed by sled, and then against one backed by postgres if that feature is enabled
+#[allow(unused)]
+pub async fn for_each_engine<F, Fut>(test: F) -> anyhow::Result<()>
+where
+    F: Fn(Arc<Node>) -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    test(Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()))).await?;
+
+    #[cfg(feature = "postgres")]
+    {
+        let (_container, storage_engine) = pg_common::create_postgres_container().await?;
+        test(Node::new_durable(Arc::new(storage_engine))).await?;
+    }
+
+    Ok(())
+}
+
+/// Create an entity in its own transaction, and return a view of it
+#[allow(unused)]
+pub async fn create_and_read<R: View>(node: &Arc<Node>, model: &R::Model) -> anyhow::Result<R> {
+    let trx = node.begin();
+    let view = R::from_entity(trx.create(model).await.entity().clone());
+    trx.commit().await?;
+    Ok(view)
+}
+
+#[allow(unused)]
+pub fn names(resultset: ResultSet<AlbumView>) -> Vec<String> { resultset.items.iter().map(|r| r.name()).collect() }
+
 // Initialize tracing for tests
 #[ctor::ctor]
 fn init_tracing() { tracing_subscriber::fmt().with_max_level(Level::INFO).with_test_writer().init(); }
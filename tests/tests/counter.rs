@@ -0,0 +1,170 @@
+mod common;
+use ankurah::{
+    property::backend::{GBackend, PNBackend, PropertyBackend},
+    proto::{ClockOrdering, ID},
+    Model,
+};
+use anyhow::Result;
+use common::*;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Model, Debug, Serialize, Deserialize)]
+pub struct Track {
+    pub title: String,
+    #[active_type(PNCounter)]
+    pub score: i64,
+    #[active_type(GCounter)]
+    pub plays: u64,
+}
+
+#[tokio::test]
+async fn counters() -> Result<()> {
+    for_each_engine(|node| async move {
+        let track: TrackView = create_and_read(&node, &Track { title: "Starlight".into(), score: 10, plays: 1 }).await?;
+
+        // Concurrent transactions each count from the same starting point, and none of the changes are lost
+        let trx1 = node.begin();
+        let trx2 = node.begin();
+        let trx3 = node.begin();
+        {
+            let track = track.edit(&trx1).await?;
+            track.score().add(5);
+            track.plays().add(2);
+        }
+        {
+            let track = track.edit(&trx2).await?;
+            track.score().add(-3);
+            track.plays().add(1);
+        }
+        track.edit(&trx3).await?.score().add(-20);
+        trx1.commit().await?;
+        trx2.commit().await?;
+        trx3.commit().await?;
+
+        assert_eq!(track.score(), -8);
+        assert_eq!(track.plays(), 4);
+
+        let fetched: TrackView = node.get(track.id()).await?;
+        assert_eq!((fetched.score(), fetched.plays()), (-8, 4));
+
+        Ok(())
+    })
+    .await
+}
+
+#[test]
+fn pn_operations_are_idempotent() -> Result<()> {
+    let origin = PNBackend::new();
+    origin.set_replica(ID::new());
+    let replica = PNBackend::new();
+
+    origin.add("score".into(), 3);
+    let first_id = ID::new();
+    let first = origin.to_operations(first_id)?;
+    origin.add("score".into(), -1);
+    let second_id = ID::new();
+    let second = origin.to_operations(second_id)?;
+
+    // Collecting again without changes yields nothing
+    assert!(origin.to_operations(ID::new())?.is_empty());
+
+    // Applying both operations from the same replica doesn't double count, in either order and with repeats
+    replica.apply_operations(second_id, &second)?;
+    replica.apply_operations(first_id, &first)?;
+    replica.apply_operations(second_id, &second)?;
+    assert_eq!(replica.get("score".into()), 2);
+
+    // Merging concurrent states sums the contributions of each replica
+    let other = PNBackend::new();
+    other.set_replica(ID::new());
+    other.add("score".into(), 10);
+    other.to_operations(ID::new())?;
+    replica.merge_state(&other.to_state_buffer()?, ClockOrdering::Concurrent)?;
+    replica.merge_state(&other.to_state_buffer()?, ClockOrdering::Concurrent)?;
+    assert_eq!(replica.get("score".into()), 12);
+
+    Ok(())
+}
+
+#[test]
+fn forks_count_as_one_replica() -> Result<()> {
+    let node = ID::new();
+    let origin = PNBackend::new();
+    origin.set_replica(node);
+    let replica = PNBackend::new();
+
+    // Forks share their node's replica, and each commit takes the node's next count, so concurrent forks lose nothing
+    let fork = || Arc::<dyn PropertyBackend>::from(origin.fork()).as_arc_dyn_any().downcast::<PNBackend>().unwrap();
+    let (first, second) = (fork(), fork());
+    first.add("score".into(), 5);
+    second.add("score".into(), -3);
+    for (fork, event_id) in [(first, ID::new()), (second, ID::new())] {
+        let operations = fork.to_operations(event_id)?;
+        origin.apply_operations(event_id, &operations)?;
+        replica.apply_operations(event_id, &operations)?;
+    }
+    assert_eq!((origin.get("score".into()), replica.get("score".into())), (2, 2));
+
+    // The state holds the node's counts, rather than counts for each fork
+    let single = PNBackend::new();
+    single.set_replica(node);
+    single.add("score".into(), 5);
+    single.to_operations(ID::new())?;
+    single.add("score".into(), -3);
+    single.to_operations(ID::new())?;
+    assert_eq!(origin.to_state_buffer()?.len(), single.to_state_buffer()?.len());
+
+    // Changes can't be collected until they are attributed to a replica
+    let unattributed = PNBackend::new();
+    unattributed.add("score".into(), 1);
+    assert!(unattributed.to_operations(ID::new()).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn collected_counts_wait_for_the_commit() -> Result<()> {
+    let origin = PNBackend::new();
+    origin.set_replica(ID::new());
+    let fork = Arc::<dyn PropertyBackend>::from(origin.fork()).as_arc_dyn_any().downcast::<PNBackend>().unwrap();
+    fork.add("score".into(), 5);
+    let event_id = ID::new();
+    let operations = fork.to_operations(event_id)?;
+
+    // The node's count only changes once the event is applied, so a commit which fails leaves it alone
+    assert_eq!(origin.get("score".into()), 0);
+    origin.apply_operations(event_id, &operations)?;
+    assert_eq!(origin.get("score".into()), 5);
+
+    Ok(())
+}
+
+#[test]
+fn dropped_operations_are_not_counted_again() -> Result<()> {
+    let (pn, g) = (PNBackend::new(), GBackend::new());
+    pn.set_replica(ID::new());
+    g.set_replica(ID::new());
+    let pn_fork = || Arc::<dyn PropertyBackend>::from(pn.fork()).as_arc_dyn_any().downcast::<PNBackend>().unwrap();
+    let g_fork = || Arc::<dyn PropertyBackend>::from(g.fork()).as_arc_dyn_any().downcast::<GBackend>().unwrap();
+
+    // A commit which fails drops its forks along with the operations they collected, so the event is never applied
+    let (failed_pn, failed_g) = (pn_fork(), g_fork());
+    failed_pn.add("score".into(), 5);
+    failed_g.add("plays".into(), 5);
+    failed_pn.to_operations(ID::new())?;
+    failed_g.to_operations(ID::new())?;
+    drop((failed_pn, failed_g));
+
+    // The next commit only carries its own amount
+    let (pn_replica, g_replica) = (PNBackend::new(), GBackend::new());
+    let (next_pn, next_g) = (pn_fork(), g_fork());
+    next_pn.add("score".into(), 3);
+    next_g.add("plays".into(), 3);
+    let event_id = ID::new();
+    pn_replica.apply_operations(event_id, &next_pn.to_operations(event_id)?)?;
+    g_replica.apply_operations(event_id, &next_g.to_operations(event_id)?)?;
+    assert_eq!((pn_replica.get("score".into()), g_replica.get("plays".into())), (3, 3));
+
+    Ok(())
+}
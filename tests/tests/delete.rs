@@ -0,0 +1,69 @@
+mod common;
+use ankurah::{changes::ChangeKind, model::View, Mutable, Node};
+use ankurah_connector_local_process::LocalProcessConnection;
+use ankurah_storage_sled::SledStorageEngine;
+use anyhow::Result;
+use common::*;
+use std::sync::Arc;
+
+#[tokio::test]
+async fn delete() -> Result<()> {
+    for_each_engine(|node| async move {
+        let (watcher, check) = common::changeset_watcher::<AlbumView>();
+        let _handle = node.subscribe("year = '2001'", watcher).await?;
+
+        let (origin, absolution) = {
+            let trx = node.begin();
+            let origin = trx.create(&Album { name: "Origin of Symmetry".into(), year: "2001".into() }).await.read();
+            let absolution = trx.create(&Album { name: "Absolution".into(), year: "2001".into() }).await.read();
+            trx.commit().await?;
+            (origin, absolution)
+        };
+        assert_eq!(check(), vec![vec![(origin.id(), ChangeKind::Add), (absolution.id(), ChangeKind::Add)]]);
+
+        {
+            let trx = node.begin();
+            trx.delete::<Album>(origin.id()).await?;
+            trx.commit().await?;
+        }
+
+        assert!(origin.entity().is_deleted());
+        assert_eq!(check(), vec![vec![(origin.id(), ChangeKind::Remove)]]);
+        assert_eq!(names(node.fetch("year = '2001'").await?), ["Absolution"]);
+
+        // The tombstone is recorded in the event log
+        let events = node.collection(&"album".into()).await.get_events(origin.id()).await?;
+        assert_eq!(events.iter().filter(|e| e.tombstone).count(), 1);
+
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn delete_propagates_to_peers() -> Result<()> {
+    let server = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
+    let client = Node::new(Arc::new(SledStorageEngine::new_test().unwrap()));
+    let _conn = LocalProcessConnection::new(&client, &server).await?;
+
+    let (client_watcher, check_client) = common::changeset_watcher::<AlbumView>();
+    let _client_sub = client.subscribe("name = 'Showbiz'", client_watcher).await?;
+
+    let album: AlbumView = create_and_read(&server, &Album { name: "Showbiz".into(), year: "1999".into() }).await?;
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    assert_eq!(check_client(), vec![vec![(album.id(), ChangeKind::Add)]]);
+
+    // Delete on the server, and the client should see the removal
+    {
+        let trx = server.begin();
+        trx.delete::<Album>(album.id()).await?;
+        trx.commit().await?;
+    }
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    assert_eq!(check_client(), vec![vec![(album.id(), ChangeKind::Remove)]]);
+    assert_eq!(names(client.fetch("name = 'Showbiz'").await?), [] as [&str; 0]);
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+mod common;
+use ankurah::{proto, storage::StorageEngine};
+use ankurah_storage_sled::SledStorageEngine;
+use anyhow::Result;
+use common::*;
+
+#[tokio::test]
+async fn event_log() -> Result<()> {
+    for_each_engine(|node| async move {
+        let album: AlbumView = create_and_read(&node, &Album { name: "The rest of the owl".to_owned(), year: "2024".to_owned() }).await?;
+        {
+            let trx = node.begin();
+            album.edit(&trx).await?.year().overwrite(0, 4, "2025");
+            trx.commit().await?;
+        }
+
+        let events = node.collection(&"album".into()).await.get_events(album.id()).await?;
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|e| e.entity_id == album.id()));
+
+        // The second event must descend from the first
+        let (first, second) =
+            if events[1].parent.as_slice().contains(&events[0].id) { (&events[0], &events[1]) } else { (&events[1], &events[0]) };
+        assert!(first.parent.is_empty());
+        assert_eq!(second.parent, proto::Clock::new([first.id]));
+
+        // Events are immutable, so recording one again must be a no-op
+        assert!(!node.collection(&"album".into()).await.add_event(second).await?);
+        assert_eq!(node.collection(&"album".into()).await.get_events(album.id()).await?.len(), 2);
+
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn event_log_is_separate_from_other_collections() -> Result<()> {
+    let engine = SledStorageEngine::new_test()?;
+    let albums = engine.collection(&"album".into()).await?;
+    // A collection which would share its name with the album event log if the names weren't kept apart
+    let others = engine.collection(&"album_events".into()).await?;
+
+    let id = proto::ID::new();
+    let event = proto::Event {
+        id: proto::ID::new(),
+        collection: "album".into(),
+        entity_id: id,
+        operations: Default::default(),
+        parent: proto::Clock::default(),
+        tombstone: false,
+    };
+    albums.add_event(&event).await?;
+    others.set_state(id, &proto::State { state_buffers: Default::default(), head: proto::Clock::default(), tombstone: false }).await?;
+
+    assert_eq!(albums.get_events(id).await?.len(), 1);
+    assert!(others.get_state(id).await.is_ok());
+
+    // The separator of the event log's name can't be used in a collection ID
+    assert!(engine.collection(&"album/events".into()).await.is_err());
+
+    Ok(())
+}
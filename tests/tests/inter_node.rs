@@ -1,13 +1,13 @@
 mod common;
 
-use ankurah::{changes::ChangeKind, FetchArgs, Mutable, Node, ResultSet, ID};
+use ankurah::{changes::ChangeKind, FetchArgs, Mutable, Node, ResultSet, View, ID};
 use ankurah_connector_local_process::LocalProcessConnection;
 use ankurah_storage_sled::SledStorageEngine;
 use anyhow::Result;
 use std::sync::Arc;
 use tracing::info;
 
-use common::{Album, AlbumView, Pet, PetView};
+use common::{create_and_read, Album, AlbumView, Pet, PetView};
 
 pub fn names(resultset: ResultSet<AlbumView>) -> Vec<String> { resultset.items.iter().map(|r| r.name()).collect::<Vec<String>>() }
 
@@ -30,7 +30,7 @@ async fn inter_node_fetch() -> Result<()> {
     assert_eq!(names(node1.fetch(p).await?), ["Walking on a Dream"]);
 
     // But node2 because they arent connected
-    assert_eq!(names(node2.fetch(FetchArgs { predicate: p.try_into()?, cached: true }).await?), [] as [&str; 0]);
+    assert_eq!(names(node2.fetch(FetchArgs { query: p.try_into()?, cached: true }).await?), [] as [&str; 0]);
 
     // Connect the nodes
     let _conn = LocalProcessConnection::new(&node1, &node2).await?;
@@ -183,3 +183,65 @@ async fn test_client_server_subscription_propagation() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn fetch_merges_unsynced_local_edits() -> Result<()> {
+    let server = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
+    let client = Node::new(Arc::new(SledStorageEngine::new_test().unwrap()));
+
+    let album: AlbumView = create_and_read(&server, &Album { name: "Walking on a Dream".into(), year: "2008".into() }).await?;
+
+    let query = "year = '2008'";
+    let client_album = {
+        let _conn = LocalProcessConnection::new(&server, &client).await?;
+        client.fetch::<AlbumView>(query).await?.items.pop().unwrap()
+    };
+
+    // Both sides edit while disconnected
+    {
+        let trx = client.begin();
+        client_album.edit(&trx).await?.name().overwrite(0, 7, "Running");
+        trx.commit().await?;
+    }
+    {
+        let trx = server.begin();
+        album.edit(&trx).await?.name().insert(18, " (Live)");
+        trx.commit().await?;
+    }
+
+    // Fetching again must merge the server's state rather than discarding the client's edit
+    let _conn = LocalProcessConnection::new(&server, &client).await?;
+    assert_eq!(names(client.fetch(query).await?), ["Running on a Dream (Live)"]);
+    assert_eq!(client_album.entity().head().len(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn fetch_adopts_remote_state_several_events_ahead() -> Result<()> {
+    let server = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
+    let client = Node::new(Arc::new(SledStorageEngine::new_test().unwrap()));
+
+    let album: AlbumView = create_and_read(&server, &Album { name: "Walking on a Dream".into(), year: "2008".into() }).await?;
+
+    let query = "name = 'Walking on a Dream'";
+    let client_album = {
+        let _conn = LocalProcessConnection::new(&server, &client).await?;
+        client.fetch::<AlbumView>(query).await?.items.pop().unwrap()
+    };
+
+    // The server edits several times while the client is disconnected, so the client never receives those events
+    for year in ["2009", "2010", "2011"] {
+        let trx = server.begin();
+        album.edit(&trx).await?.year().overwrite(0, 4, year);
+        trx.commit().await?;
+    }
+
+    // The server's head descends from the client's, so the client adopts it rather than treating the two as concurrent
+    let _conn = LocalProcessConnection::new(&server, &client).await?;
+    client.fetch::<AlbumView>(query).await?;
+    assert_eq!(client_album.year(), "2011");
+    assert_eq!(client_album.entity().head(), album.entity().head());
+
+    Ok(())
+}
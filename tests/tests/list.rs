@@ -0,0 +1,119 @@
+mod common;
+use ankurah::{
+    property::backend::{list::position_between, ListBackend, PropertyBackend},
+    proto::ID,
+    Model, View,
+};
+use anyhow::Result;
+use common::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Model, Debug, Serialize, Deserialize)]
+pub struct Column {
+    pub name: String,
+    #[active_type(List)]
+    pub cards: Vec<String>,
+}
+
+fn cards(names: &[&str]) -> Vec<String> { names.iter().map(|name| name.to_string()).collect() }
+
+#[tokio::test]
+async fn list() -> Result<()> {
+    for_each_engine(|node| async move {
+        let column: ColumnView = create_and_read(&node, &Column { name: "Todo".into(), cards: cards(&["a", "b", "c", "d"]) }).await?;
+        assert_eq!(column.cards(), cards(&["a", "b", "c", "d"]));
+
+        // Both transactions move "a" to different places, and one of them also inserts a card
+        let trx1 = node.begin();
+        let trx2 = node.begin();
+        {
+            let column = column.edit(&trx1).await?;
+            column.cards().move_item(0, 3);
+            assert_eq!(column.cards().value(), cards(&["b", "c", "d", "a"]));
+        }
+        {
+            let column = column.edit(&trx2).await?;
+            column.cards().move_item(0, 2);
+            column.cards().insert_at(0, &"e".to_string())?;
+        }
+        trx1.commit().await?;
+        trx2.commit().await?;
+
+        // "a" ends up at exactly one of the places it was moved to
+        let moved = column.cards();
+        assert_eq!(moved.len(), 5);
+        assert_eq!(moved.iter().filter(|card| *card == "a").count(), 1);
+        assert!(moved == cards(&["e", "b", "c", "d", "a"]) || moved == cards(&["e", "b", "c", "a", "d"]), "{moved:?}");
+
+        // A removal wins over a concurrent move
+        let trx1 = node.begin();
+        let trx2 = node.begin();
+        assert_eq!(column.edit(&trx1).await?.cards().remove(1), Some("b".to_string()));
+        column.edit(&trx2).await?.cards().move_item(1, 0);
+        trx1.commit().await?;
+        trx2.commit().await?;
+
+        let remaining = column.cards();
+        assert_eq!(remaining.len(), 4);
+        assert!(!remaining.contains(&"b".to_string()));
+
+        let fetched: ColumnView = node.get(column.id()).await?;
+        assert_eq!(fetched.cards(), remaining);
+
+        // The materialized value holds the items in order, for storage engines to store alongside the state
+        let materialized = fetched.backends().get::<ListBackend>()?.materialized();
+        assert_eq!(materialized["cards"].decode::<Vec<String>>()?, remaining);
+
+        Ok(())
+    })
+    .await
+}
+
+#[test]
+fn positions_always_fit_between() {
+    let mut before = None;
+    let after = position_between(None, None);
+    // Repeatedly inserting at the same place has to keep finding room
+    for _ in 0..200 {
+        let position = position_between(before.as_ref(), Some(&after));
+        if let Some(before) = &before {
+            assert!(*before < position);
+        }
+        assert!(position < after);
+        before = Some(position);
+    }
+
+    let last = position_between(Some(&vec![u32::MAX]), None);
+    assert!(last > vec![u32::MAX]);
+}
+
+#[test]
+fn concurrent_inserts_at_the_same_place_are_kept() -> Result<()> {
+    let origin = ListBackend::new();
+    origin.insert_at("cards".into(), 0, b"a".to_vec());
+    origin.insert_at("cards".into(), 1, b"d".to_vec());
+    let first_id = ID::new();
+    let first = origin.to_operations(first_id)?;
+
+    let replica = ListBackend::new();
+    replica.apply_operations(first_id, &first)?;
+
+    origin.insert_at("cards".into(), 1, b"b".to_vec());
+    let origin_id = ID::new();
+    let origin_insert = origin.to_operations(origin_id)?;
+    replica.insert_at("cards".into(), 1, b"c".to_vec());
+    let replica_id = ID::new();
+    let replica_insert = replica.to_operations(replica_id)?;
+
+    origin.apply_operations(replica_id, &replica_insert)?;
+    replica.apply_operations(origin_id, &origin_insert)?;
+    // Operations are idempotent
+    replica.apply_operations(first_id, &first)?;
+
+    assert_eq!(origin.get("cards"), replica.get("cards"));
+    assert_eq!(origin.len("cards"), 4);
+    assert_eq!(origin.get("cards").first(), Some(&b"a".to_vec()));
+    assert_eq!(origin.get("cards").last(), Some(&b"d".to_vec()));
+
+    Ok(())
+}
@@ -140,3 +140,115 @@ async fn complex_local_subscription() {
     // Verify Rex's "removal" was received
     assert_eq!(check(), vec![vec![(rex.id(), ChangeKind::Remove)]]);
 }
+
+#[tokio::test]
+async fn in_and_between_local_subscription() {
+    let node = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
+    let (watcher, check) = common::changeset_watcher::<PetView>();
+
+    let _handle = node.subscribe("name IN ('Rex', 'Fido') OR age BETWEEN 3 AND 4", watcher).await.unwrap();
+
+    let (rex, snuffy);
+    {
+        let trx = node.begin();
+        rex = trx.create(&Pet { name: "Rex".to_string(), age: "1".to_string() }).await.read();
+        snuffy = trx.create(&Pet { name: "Snuffy".to_string(), age: "2".to_string() }).await.read();
+        trx.commit().await.unwrap();
+    };
+
+    assert_eq!(check(), vec![vec![(rex.id(), ChangeKind::Add)]]);
+
+    // Snuffy enters the range at its upper bound
+    {
+        let trx = node.begin();
+        snuffy.edit(&trx).await.unwrap().age().overwrite(0, 1, "4");
+        trx.commit().await.unwrap();
+    }
+    assert_eq!(check(), vec![vec![(snuffy.id(), ChangeKind::Add)]]);
+
+    // And leaves it again
+    {
+        let trx = node.begin();
+        snuffy.edit(&trx).await.unwrap().age().overwrite(0, 1, "5");
+        trx.commit().await.unwrap();
+    }
+    assert_eq!(check(), vec![vec![(snuffy.id(), ChangeKind::Remove)]]);
+
+    // A name in the list matches regardless of age
+    {
+        let trx = node.begin();
+        snuffy.edit(&trx).await.unwrap().name().overwrite(0, 6, "Fido");
+        trx.commit().await.unwrap();
+    }
+    assert_eq!(check(), vec![vec![(snuffy.id(), ChangeKind::Add)]]);
+}
+
+#[tokio::test]
+async fn arithmetic_local_subscription() {
+    let node = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
+    let (watcher, check) = common::changeset_watcher::<PetView>();
+
+    // Arithmetic can't be looked up in an index, so this is evaluated against every change to the collection
+    let _handle = node.subscribe("age * 2 + 1 > 7", watcher).await.unwrap();
+
+    let (rex, snuffy);
+    {
+        let trx = node.begin();
+        rex = trx.create(&Pet { name: "Rex".to_string(), age: "1".to_string() }).await.read();
+        snuffy = trx.create(&Pet { name: "Snuffy".to_string(), age: "5".to_string() }).await.read();
+        trx.commit().await.unwrap();
+    };
+
+    assert_eq!(check(), vec![vec![(snuffy.id(), ChangeKind::Add)]]);
+
+    {
+        let trx = node.begin();
+        rex.edit(&trx).await.unwrap().age().overwrite(0, 1, "4");
+        trx.commit().await.unwrap();
+    }
+    assert_eq!(check(), vec![vec![(rex.id(), ChangeKind::Add)]]);
+
+    {
+        let trx = node.begin();
+        snuffy.edit(&trx).await.unwrap().age().overwrite(0, 1, "3");
+        trx.commit().await.unwrap();
+    }
+    assert_eq!(check(), vec![vec![(snuffy.id(), ChangeKind::Remove)]]);
+
+    let pets: ResultSet<PetView> = node.fetch("(age + 1) * 2 = 10").await.unwrap();
+    assert_eq!(pets.items.iter().map(|pet| pet.name()).collect::<Vec<String>>(), ["Rex"]);
+}
+
+#[tokio::test]
+async fn not_in_local_subscription() {
+    let node = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
+    let (watcher, check) = common::changeset_watcher::<PetView>();
+
+    let _handle = node.subscribe("name NOT IN ('Rex', 'Fido')", watcher).await.unwrap();
+
+    let (rex, snuffy);
+    {
+        let trx = node.begin();
+        rex = trx.create(&Pet { name: "Rex".to_string(), age: "1".to_string() }).await.read();
+        snuffy = trx.create(&Pet { name: "Snuffy".to_string(), age: "2".to_string() }).await.read();
+        trx.commit().await.unwrap();
+    };
+
+    assert_eq!(check(), vec![vec![(snuffy.id(), ChangeKind::Add)]]);
+
+    // Rex starts matching once renamed to anything outside the list
+    {
+        let trx = node.begin();
+        rex.edit(&trx).await.unwrap().name().overwrite(0, 3, "Max");
+        trx.commit().await.unwrap();
+    }
+    assert_eq!(check(), vec![vec![(rex.id(), ChangeKind::Add)]]);
+
+    // And Snuffy stops matching once renamed into it
+    {
+        let trx = node.begin();
+        snuffy.edit(&trx).await.unwrap().name().overwrite(0, 6, "Fido");
+        trx.commit().await.unwrap();
+    }
+    assert_eq!(check(), vec![vec![(snuffy.id(), ChangeKind::Remove)]]);
+}
@@ -0,0 +1,196 @@
+mod common;
+use ankurah::{
+    model::View,
+    property::{
+        backend::{LWWBackend, PropertyBackend},
+        value::LWW,
+    },
+    proto::{ClockOrdering, ID},
+    value::Value,
+    Model, Mutable, Node, ResultSet,
+};
+use ankurah_storage_sled::SledStorageEngine;
+use anyhow::Result;
+use common::{create_and_read, for_each_engine, Dimensions, Format, Release, ReleaseView};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[test]
+fn lww_operations_only_carry_changes() -> Result<()> {
+    let backend = LWWBackend::new();
+    backend.set("name".into(), Value::String("Showbiz".into()));
+    backend.set("year".into(), Value::Integer(1999));
+    assert_eq!(backend.to_operations(ID::new())?.len(), 1);
+
+    // Nothing changed since the last commit
+    assert!(backend.to_operations(ID::new())?.is_empty());
+
+    // Only the changed property is sent
+    backend.set("name".into(), Value::String("Origin of Symmetry".into()));
+    let operations = backend.to_operations(ID::new())?;
+    let replica = LWWBackend::new();
+    replica.apply_operations(ID::new(), &operations)?;
+    assert_eq!(replica.properties(), ["name"]);
+    assert_eq!(replica.get("name".into()), Some(Value::String("Origin of Symmetry".into())));
+
+    Ok(())
+}
+
+#[test]
+fn lww_concurrent_sets_converge() -> Result<()> {
+    let first = LWWBackend::new();
+    first.set("name".into(), Value::String("Showbiz".into()));
+    let first_id = ID::new();
+    let first_ops = first.to_operations(first_id)?;
+
+    let second = LWWBackend::new();
+    second.set("name".into(), Value::String("Absolution".into()));
+    let second_id = ID::new();
+    let second_ops = second.to_operations(second_id)?;
+
+    // Receive the other replica's write, in opposite orders
+    first.apply_operations(second_id, &second_ops)?;
+    second.apply_operations(first_id, &first_ops)?;
+
+    let winner = if first_id > second_id { Value::String("Showbiz".into()) } else { Value::String("Absolution".into()) };
+    assert_eq!(first.get("name".into()), Some(winner.clone()));
+    assert_eq!(second.get("name".into()), Some(winner.clone()));
+
+    // Replaying an event is idempotent
+    first.apply_operations(first_id, &first_ops)?;
+    assert_eq!(first.get("name".into()), Some(winner.clone()));
+
+    // Merging whole states converges the same way
+    let third = LWWBackend::new();
+    third.apply_operations(first_id, &first_ops)?;
+    third.merge_state(&second.to_state_buffer()?, ClockOrdering::Concurrent)?;
+    assert_eq!(third.get("name".into()), Some(winner));
+
+    Ok(())
+}
+
+#[test]
+fn lww_causally_later_write_wins() -> Result<()> {
+    // The later write is stamped with the lower event ID, as if its replica's clock were behind
+    let (earlier_id, later_id) = {
+        let (a, b) = (ID::new(), ID::new());
+        (a.max(b), a.min(b))
+    };
+
+    let first = LWWBackend::new();
+    first.set("name".into(), Value::String("Showbiz".into()));
+    let earlier_ops = first.to_operations(earlier_id)?;
+
+    let second = LWWBackend::new();
+    second.apply_operations(earlier_id, &earlier_ops)?;
+    second.set("name".into(), Value::String("Absolution".into()));
+    let later_ops = second.to_operations(later_id)?;
+
+    // The later write observed the earlier one, so it wins whatever order they're received in
+    first.apply_operations(later_id, &later_ops)?;
+    assert_eq!(first.get("name".into()), Some(Value::String("Absolution".into())));
+
+    let third = LWWBackend::new();
+    third.apply_operations(later_id, &later_ops)?;
+    third.apply_operations(earlier_id, &earlier_ops)?;
+    assert_eq!(third.get("name".into()), Some(Value::String("Absolution".into())));
+
+    let fourth = LWWBackend::new();
+    fourth.apply_operations(earlier_id, &earlier_ops)?;
+    fourth.merge_state(&second.to_state_buffer()?, ClockOrdering::Descends)?;
+    assert_eq!(fourth.get("name".into()), Some(Value::String("Absolution".into())));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn lww_typed_model_values() -> Result<()> {
+    let node = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
+
+    let release = {
+        let trx = node.begin();
+        let release = trx
+            .create(&Release {
+                title: "Showbiz".into(),
+                year: 1999,
+                remastered: false,
+                rating: 3.5,
+                format: Format::CD,
+                sleeve: Dimensions { width: 12, height: 12 },
+            })
+            .await;
+        assert_eq!(release.year().get()?, Some(1999));
+        let release = release.read();
+        trx.commit().await?;
+        release
+    };
+
+    {
+        let trx = node.begin();
+        let release = release.edit(&trx).await?;
+        release.remastered().set(&true)?;
+        release.format().set(&Format::Vinyl)?;
+        trx.commit().await?;
+    }
+
+    let fetched: ReleaseView = node.get(release.id()).await?;
+    assert_eq!(fetched.year(), 1999);
+    assert!(fetched.remastered());
+    assert_eq!(fetched.rating(), 3.5);
+    assert_eq!(fetched.format(), Format::Vinyl);
+    assert_eq!(fetched.sleeve(), Dimensions { width: 12, height: 12 });
+
+    // Scalars are stored typed, so storage engines and queries see them as such, and anything else is stored as bytes
+    let entity = fetched.entity();
+    assert_eq!(entity.property_value("year"), Some(Value::Integer(1999)));
+    assert_eq!(entity.property_value("remastered"), Some(Value::Boolean(true)));
+    assert_eq!(entity.property_value("rating"), Some(Value::Float(3.5)));
+    assert_eq!(entity.property_value("format"), Some(Value::String("Vinyl".into())));
+    assert!(matches!(entity.property_value("sleeve"), Some(Value::Bytes(_))));
+    assert_eq!(entity.backends().materialized().get("title"), Some(&Value::String("Showbiz".into())));
+
+    // Only the properties which were set are in the edit's event
+    let events = node.collection(&"release".into()).await.get_events(release.id()).await?;
+    let edit = events.iter().find(|e| !e.parent.is_empty()).unwrap();
+    let changed = LWWBackend::new();
+    changed.apply_operations(edit.id, &edit.operations["lww"])?;
+    assert_eq!(changed.properties(), ["format", "remastered"]);
+
+    // A value which can't be decoded as the property's type is an error to get
+    entity.backends().get::<LWWBackend>()?.set("year".into(), Value::String("MCMXCIX".into()));
+    assert!(LWW::<i64>::from_backends("year".into(), entity.backends()).get().is_err());
+    // ...and one which was never set is simply missing
+    assert_eq!(LWW::<i64>::from_backends("tracks".into(), entity.backends()).get()?, None);
+
+    Ok(())
+}
+
+#[derive(Model, Debug, Serialize, Deserialize)]
+pub struct Demo {
+    pub title: String,
+    #[active_type(LWW)]
+    pub producer: Option<String>,
+}
+
+#[tokio::test]
+async fn lww_cleared_values_no_longer_match() -> Result<()> {
+    for_each_engine(|node| async move {
+        let demo: DemoView = create_and_read(&node, &Demo { title: "Sunburn".into(), producer: Some("John Leckie".into()) }).await?;
+        let produced: ResultSet<DemoView> = node.fetch("producer = 'John Leckie'").await?;
+        assert_eq!(produced.items.len(), 1);
+
+        {
+            let trx = node.begin();
+            demo.edit(&trx).await?.producer().set(&None)?;
+            trx.commit().await?;
+        }
+
+        // Clearing the value stores a null, rather than leaving the old value behind
+        let produced: ResultSet<DemoView> = node.fetch("producer = 'John Leckie'").await?;
+        assert!(produced.items.is_empty());
+        assert_eq!(node.get::<DemoView>(demo.id()).await?.producer(), None);
+
+        Ok(())
+    })
+    .await
+}
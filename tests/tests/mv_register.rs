@@ -0,0 +1,107 @@
+mod common;
+use ankurah::{
+    changes::{ChangeSet, ItemChange},
+    property::backend::{MVBackend, PropertyBackend},
+    proto::{ClockOrdering, ID},
+    value::Value,
+    Model,
+};
+use anyhow::Result;
+use common::*;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+#[derive(Model, Debug, Serialize, Deserialize)]
+pub struct Document {
+    pub folder: String,
+    #[active_type(MVRegister)]
+    pub title: String,
+}
+
+#[tokio::test]
+async fn mv_register() -> Result<()> {
+    for_each_engine(|node| async move {
+        let document: DocumentView = create_and_read(&node, &Document { folder: "drafts".into(), title: "Untitled".into() }).await?;
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let _handle = {
+            let received = received.clone();
+            node.subscribe("folder = 'drafts'", move |changeset: ChangeSet<DocumentView>| received.lock().unwrap().push(changeset)).await?
+        };
+
+        // Two concurrent transactions both overwrite the initial title
+        let trx1 = node.begin();
+        let trx2 = node.begin();
+        document.edit(&trx1).await?.title().set(&"Meeting notes".to_string())?;
+        document.edit(&trx2).await?.title().set(&"Minutes".to_string())?;
+        trx1.commit().await?;
+        trx2.commit().await?;
+
+        let mut values = document.edit(&node.begin()).await?.title().values();
+        values.sort();
+        assert_eq!(values, vec!["Meeting notes".to_string(), "Minutes".to_string()]);
+
+        // The conflict is reported on the update which introduced it
+        {
+            let received = received.lock().unwrap();
+            let last = received.last().unwrap();
+            assert!(matches!(last.changes[..], [ItemChange::Update { .. }]));
+            assert_eq!(last.changes[0].conflicts(), ["title".to_string()]);
+        }
+
+        // Resolving the conflict supersedes both values
+        let trx = node.begin();
+        document.edit(&trx).await?.title().resolve(&"Meeting minutes".to_string())?;
+        trx.commit().await?;
+
+        assert_eq!(document.title(), "Meeting minutes");
+        {
+            let received = received.lock().unwrap();
+            assert!(received.last().unwrap().changes[0].conflicts().is_empty());
+        }
+
+        let fetched: DocumentView = node.get(document.id()).await?;
+        assert_eq!(fetched.edit(&node.begin()).await?.title().values(), vec!["Meeting minutes".to_string()]);
+
+        Ok(())
+    })
+    .await
+}
+
+#[test]
+fn writes_only_supersede_what_they_observed() -> Result<()> {
+    let origin = MVBackend::new();
+    origin.set("title".into(), Value::String("first".into()));
+    let first_id = ID::new();
+    let first = origin.to_operations(first_id)?;
+
+    let replica = MVBackend::new();
+    replica.apply_operations(first_id, &first)?;
+
+    // Both sides overwrite the first value without seeing each other's write
+    origin.set("title".into(), Value::String("origin".into()));
+    let origin_id = ID::new();
+    let origin_write = origin.to_operations(origin_id)?;
+    replica.set("title".into(), Value::String("replica".into()));
+    let replica_id = ID::new();
+    let replica_write = replica.to_operations(replica_id)?;
+
+    origin.apply_operations(replica_id, &replica_write)?;
+    replica.apply_operations(origin_id, &origin_write)?;
+    // Replaying the first write doesn't bring it back
+    replica.apply_operations(first_id, &first)?;
+
+    assert!(origin.is_conflicted("title"));
+    assert_eq!(origin.get_all("title"), replica.get_all("title"));
+    assert_eq!(origin.get_all("title").len(), 2);
+    assert_eq!(origin.conflicted_properties(), vec!["title".to_string()]);
+
+    // A write which observed both values resolves the conflict everywhere, including via state merges
+    origin.set("title".into(), Value::String("resolved".into()));
+    origin.to_operations(ID::new())?;
+    replica.merge_state(&origin.to_state_buffer()?, ClockOrdering::Descends)?;
+    assert_eq!(replica.get_all("title"), vec![Value::String("resolved".into())]);
+    assert!(!replica.is_conflicted("title"));
+
+    Ok(())
+}
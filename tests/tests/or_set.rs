@@ -0,0 +1,119 @@
+mod common;
+use ankurah::{
+    property::backend::{ORSetBackend, PropertyBackend},
+    proto::{ClockOrdering, ID},
+    Model, Node,
+};
+use ankurah_storage_sled::SledStorageEngine;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, sync::Arc};
+
+use common::create_and_read;
+
+#[derive(Model, Debug, Serialize, Deserialize)]
+pub struct Playlist {
+    pub name: String,
+    #[active_type(ORSet)]
+    pub tags: BTreeSet<String>,
+}
+
+fn tags(names: &[&str]) -> BTreeSet<String> { names.iter().map(|name| name.to_string()).collect() }
+
+#[tokio::test]
+async fn concurrent_insert_wins_over_remove() -> Result<()> {
+    let node = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
+
+    let playlist: PlaylistView = create_and_read(&node, &Playlist { name: "Drive".into(), tags: tags(&["rock", "night"]) }).await?;
+    assert_eq!(playlist.tags(), tags(&["night", "rock"]));
+
+    // One transaction removes "rock" while another concurrently re-inserts it
+    let trx1 = node.begin();
+    let trx2 = node.begin();
+    {
+        let playlist = playlist.edit(&trx1).await?;
+        playlist.tags().remove(&"rock".to_string());
+        playlist.tags().remove(&"night".to_string());
+    }
+    {
+        let playlist = playlist.edit(&trx2).await?;
+        playlist.tags().insert(&"rock".to_string());
+        playlist.tags().insert(&"synth".to_string());
+    }
+    trx1.commit().await?;
+    trx2.commit().await?;
+
+    assert_eq!(playlist.tags(), tags(&["rock", "synth"]));
+
+    let fetched: PlaylistView = node.get(playlist.id()).await?;
+    assert_eq!(fetched.tags(), tags(&["rock", "synth"]));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn query_set_membership() -> Result<()> {
+    let node = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
+
+    {
+        let trx = node.begin();
+        trx.create(&Playlist { name: "Drive".into(), tags: tags(&["rock", "night"]) }).await;
+        trx.create(&Playlist { name: "Morning".into(), tags: tags(&["acoustic"]) }).await;
+        trx.create(&Playlist { name: "Gym".into(), tags: tags(&["rock"]) }).await;
+        trx.commit().await?;
+    }
+
+    let playlists: ankurah::ResultSet<PlaylistView> = node.fetch("tags = 'rock'").await?;
+    let mut names = playlists.items.iter().map(|playlist| playlist.name()).collect::<Vec<String>>();
+    names.sort();
+    assert_eq!(names, vec!["Drive".to_string(), "Gym".to_string()]);
+
+    let playlists: ankurah::ResultSet<PlaylistView> = node.fetch("tags != 'rock'").await?;
+    assert_eq!(playlists.items.iter().map(|playlist| playlist.name()).collect::<Vec<String>>(), vec!["Morning".to_string()]);
+
+    Ok(())
+}
+
+#[test]
+fn remove_only_affects_observed_inserts() -> Result<()> {
+    let origin = ORSetBackend::new();
+    origin.insert("tags".into(), "rock".into());
+    let insert_id = ID::new();
+    let insert = origin.to_operations(insert_id)?;
+
+    let replica = ORSetBackend::new();
+    replica.apply_operations(insert_id, &insert)?;
+    assert!(replica.contains("tags", "rock"));
+
+    // The replica removes the insert it observed while the origin concurrently inserts again
+    replica.remove("tags".into(), "rock".into());
+    let remove_id = ID::new();
+    let remove = replica.to_operations(remove_id)?;
+    origin.insert("tags".into(), "rock".into());
+    let reinsert_id = ID::new();
+    let reinsert = origin.to_operations(reinsert_id)?;
+
+    origin.apply_operations(remove_id, &remove)?;
+    replica.apply_operations(reinsert_id, &reinsert)?;
+    assert!(origin.contains("tags", "rock"));
+    assert!(replica.contains("tags", "rock"));
+
+    // A remove and re-insert within the same transaction cancel out without producing operations
+    let local = ORSetBackend::new();
+    local.insert("tags".into(), "jazz".into());
+    local.remove("tags".into(), "jazz".into());
+    assert!(local.to_operations(ID::new())?.is_empty());
+
+    // Merging states is a union of inserts and removals
+    let other = ORSetBackend::new();
+    other.insert("tags".into(), "pop".into());
+    other.to_operations(ID::new())?;
+    origin.merge_state(&other.to_state_buffer()?, ClockOrdering::Concurrent)?;
+    assert_eq!(origin.members("tags"), vec!["pop".to_string(), "rock".to_string()]);
+
+    // The materialized value holds the members, for storage engines to store alongside the state
+    let materialized = origin.materialized();
+    assert_eq!(materialized["tags"].decode::<Vec<String>>()?, vec!["pop".to_string(), "rock".to_string()]);
+
+    Ok(())
+}
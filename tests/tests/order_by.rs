@@ -0,0 +1,49 @@
+mod common;
+
+use ankurah::Node;
+use ankurah_connector_local_process::LocalProcessConnection;
+use ankurah_storage_sled::SledStorageEngine;
+use anyhow::Result;
+use std::sync::Arc;
+
+use common::{create_albums, for_each_engine, names, AlbumView};
+
+const ALBUMS: &[(&str, &str)] = &[
+    ("Walking on a Dream", "2008"),
+    ("Ice on the Dune", "2013"),
+    ("Two Vines", "2016"),
+    ("Ask That God", "2024"),
+    ("Sound of Silver", "2007"),
+];
+
+#[tokio::test]
+async fn order_by_limit_offset() -> Result<()> {
+    for_each_engine(|node| async move {
+        create_albums(&node, ALBUMS).await?;
+        assert_eq!(
+            names(node.fetch("ORDER BY year").await?),
+            ["Sound of Silver", "Walking on a Dream", "Ice on the Dune", "Two Vines", "Ask That God"]
+        );
+        assert_eq!(names(node.fetch("ORDER BY year DESC LIMIT 2").await?), ["Ask That God", "Two Vines"]);
+        assert_eq!(names(node.fetch("year > '2010' ORDER BY name LIMIT 2 OFFSET 1").await?), ["Ice on the Dune", "Two Vines"]);
+        assert_eq!(names(node.fetch("year < '2010' ORDER BY year OFFSET 1").await?), ["Walking on a Dream"]);
+        // Without an ORDER BY the window is still applied, in an unspecified order
+        assert_eq!(node.fetch::<AlbumView>("LIMIT 3").await?.items.len(), 3);
+        assert_eq!(node.fetch::<AlbumView>("name != 'Two Vines' LIMIT 10 OFFSET 2").await?.items.len(), 2);
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn peer_fetch_order_by_limit() -> Result<()> {
+    let server = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
+    let client = Node::new(Arc::new(SledStorageEngine::new_test().unwrap()));
+    create_albums(&server, ALBUMS).await?;
+    let _conn = LocalProcessConnection::new(&server, &client).await?;
+
+    // The durable peer applies the order and window before sending states
+    assert_eq!(names(client.fetch("ORDER BY year DESC LIMIT 2").await?), ["Ask That God", "Two Vines"]);
+    assert_eq!(names(client.fetch("ORDER BY year DESC").await?).len(), 5);
+    Ok(())
+}
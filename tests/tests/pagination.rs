@@ -0,0 +1,81 @@
+mod common;
+
+use ankurah::{Cursor, Node, Page};
+use ankurah_connector_local_process::LocalProcessConnection;
+use ankurah_storage_sled::SledStorageEngine;
+use anyhow::Result;
+use std::sync::Arc;
+
+use common::{create_albums, for_each_engine, AlbumView};
+
+fn years(page: &Page<AlbumView>) -> Vec<String> { page.items.iter().map(|r| r.year()).collect() }
+
+/// Page through albums with `reader`, while `writer` inserts albums both before and after the cursor between pages
+async fn assert_pagination(reader: &Arc<Node>, writer: &Arc<Node>) -> Result<()> {
+    create_albums(
+        writer,
+        &[
+            ("Sound of Silver", "2007"),
+            ("Walking on a Dream", "2008"),
+            ("Ice on the Dune", "2013"),
+            ("Two Vines", "2016"),
+            ("Before the Dawn Heals Us", "2016"),
+            ("Ask That God", "2024"),
+        ],
+    )
+    .await?;
+
+    let query = "year > '2000' ORDER BY year";
+    let first = reader.fetch_page::<AlbumView>(query, None, 2).await?;
+    assert_eq!(years(&first), ["2007", "2008"]);
+
+    // Neither of these may shift the following pages: one sorts before the cursor and the other after it
+    create_albums(writer, &[("Tourist History", "2001"), ("Hurry Up, We're Dreaming", "2020")]).await?;
+
+    // The cursor survives a round trip through its opaque string form
+    let cursor = Cursor::try_from(first.next.clone().expect("more pages").to_string())?;
+    let second = reader.fetch_page::<AlbumView>(query, Some(cursor), 2).await?;
+    // Ties are broken by ID, so both of the 2016 albums are seen exactly once across the page boundary
+    assert_eq!(years(&second), ["2013", "2016"]);
+
+    let third = reader.fetch_page::<AlbumView>(query, second.next.clone(), 2).await?;
+    assert_eq!(years(&third), ["2016", "2020"]);
+
+    let fourth = reader.fetch_page::<AlbumView>(query, third.next.clone(), 2).await?;
+    assert_eq!(years(&fourth), ["2024"]);
+    assert!(fourth.next.is_none());
+
+    let mut names: Vec<String> = [&first, &second, &third, &fourth].iter().flat_map(|page| page.items.iter().map(|r| r.name())).collect();
+    names.sort();
+    names.dedup();
+    assert_eq!(names.len(), 7);
+
+    // Descending pages run from the end, and the query's own LIMIT is replaced by the page size
+    let query = "ORDER BY year DESC LIMIT 1";
+    let first = reader.fetch_page::<AlbumView>(query, None, 3).await?;
+    assert_eq!(years(&first), ["2024", "2020", "2016"]);
+    let second = reader.fetch_page::<AlbumView>(query, first.next.clone(), 3).await?;
+    assert_eq!(years(&second), ["2016", "2013", "2008"]);
+    let third = reader.fetch_page::<AlbumView>(query, second.next.clone(), 3).await?;
+    assert_eq!(years(&third), ["2007", "2001"]);
+    assert!(third.next.is_none());
+
+    // A page which exactly exhausts the results has no next cursor
+    let only = reader.fetch_page::<AlbumView>("year < '2008'", None, 2).await?;
+    assert_eq!(only.items.len(), 2);
+    assert!(only.next.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn fetch_page() -> Result<()> { for_each_engine(|node| async move { assert_pagination(&node, &node).await }).await }
+
+#[tokio::test]
+async fn peer_fetch_page() -> Result<()> {
+    let server = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
+    let client = Node::new(Arc::new(SledStorageEngine::new_test().unwrap()));
+    let _conn = LocalProcessConnection::new(&server, &client).await?;
+
+    // The durable peer decides what is on each page, including albums the client has never seen
+    assert_pagination(&client, &server).await
+}
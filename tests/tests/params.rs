@@ -0,0 +1,47 @@
+mod common;
+
+use ankql::ast::Literal;
+use ankurah::{changes::ChangeKind, Mutable, Node};
+use ankurah_storage_sled::SledStorageEngine;
+use anyhow::Result;
+use std::sync::Arc;
+
+use common::{create_albums, for_each_engine, names, Album, AlbumView};
+
+const ALBUMS: &[(&str, &str)] = &[("Walking on a Dream", "2008"), ("Don't Stop", "2016"), ("Ask That God", "2024")];
+
+#[tokio::test]
+async fn params() -> Result<()> {
+    for_each_engine(|node| async move {
+        create_albums(&node, ALBUMS).await?;
+        let name = Literal::String("Don't Stop".into());
+        assert_eq!(names(node.fetch(("name = ?", [name.clone()])).await?), ["Don't Stop"]);
+        assert_eq!(names(node.fetch(("name = $1 OR year = $2", vec![name, Literal::String("2008".into())])).await?).len(), 2);
+        assert_eq!(names(node.fetch(("year > ? ORDER BY year DESC LIMIT 1", [Literal::String("2000".into())])).await?), ["Ask That God"]);
+
+        // A value which would change the meaning of the query if it were spliced into it is only compared against
+        let injection = Literal::String("x' OR name != 'x".into());
+        assert!(node.fetch::<AlbumView>(("name = ?", [injection])).await?.items.is_empty());
+
+        assert!(node.fetch::<AlbumView>(("name = ?", Vec::<Literal>::new())).await.is_err());
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn params_subscription() -> Result<()> {
+    let node = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
+    let (watcher, check) = common::changeset_watcher::<AlbumView>();
+    let _handle = node.subscribe(("name = ?", [Literal::String("Don't Stop".into())]), watcher).await?;
+
+    let album;
+    {
+        let trx = node.begin();
+        album = trx.create(&Album { name: "Don't Stop".into(), year: "2016".into() }).await.read();
+        trx.create(&Album { name: "Two Vines".into(), year: "2016".into() }).await;
+        trx.commit().await?;
+    }
+    assert_eq!(check(), vec![vec![(album.id(), ChangeKind::Add)]]);
+    Ok(())
+}
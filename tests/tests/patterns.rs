@@ -0,0 +1,94 @@
+mod common;
+
+use ankurah::{changes::ChangeKind, Mutable, Node};
+use ankurah_storage_sled::SledStorageEngine;
+use anyhow::Result;
+use std::sync::Arc;
+
+use common::{create_albums, for_each_engine, names, Album, AlbumView};
+
+const ALBUMS: &[(&str, &str)] =
+    &[("Walking on a Dream", "2008"), ("Ice on the Dune", "2013"), ("Two Vines", "2016"), ("Ask That God", "2024")];
+
+#[tokio::test]
+async fn patterns() -> Result<()> {
+    for_each_engine(|node| async move {
+        create_albums(&node, ALBUMS).await?;
+        assert_eq!(names(node.fetch("name LIKE '%on%' ORDER BY year").await?), ["Walking on a Dream", "Ice on the Dune"]);
+        assert_eq!(names(node.fetch("name LIKE 'T_o %'").await?), ["Two Vines"]);
+        assert_eq!(names(node.fetch("name ILIKE 'ice%'").await?), ["Ice on the Dune"]);
+        assert_eq!(names(node.fetch("name NOT LIKE '%i%' ORDER BY name").await?), ["Ask That God", "Ice on the Dune"]);
+        assert_eq!(names(node.fetch("name STARTS WITH 'Wa'").await?), ["Walking on a Dream"]);
+        assert_eq!(names(node.fetch("name CONTAINS 'That'").await?), ["Ask That God"]);
+        // Matching is case sensitive, except for ILIKE
+        assert!(node.fetch::<AlbumView>("name STARTS WITH 'wa'").await?.items.is_empty());
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn prefix_subscription() -> Result<()> {
+    let node = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
+    let (watcher, check) = common::changeset_watcher::<AlbumView>();
+    let _handle = node.subscribe("name STARTS WITH 'Ice' OR name LIKE 'Two%'", watcher).await?;
+
+    let (ice, walking);
+    {
+        let trx = node.begin();
+        ice = trx.create(&Album { name: "Ice on the Dune".into(), year: "2013".into() }).await.read();
+        walking = trx.create(&Album { name: "Walking on a Dream".into(), year: "2008".into() }).await.read();
+        trx.commit().await?;
+    }
+    assert_eq!(check(), vec![vec![(ice.id(), ChangeKind::Add)]]);
+
+    // Renaming an album so that it matches the prefix adds it
+    {
+        let trx = node.begin();
+        walking.edit(&trx).await?.name().overwrite(0, 7, "Two");
+        trx.commit().await?;
+    }
+    assert_eq!(check(), vec![vec![(walking.id(), ChangeKind::Add)]]);
+
+    // And renaming it so that it no longer does removes it
+    {
+        let trx = node.begin();
+        ice.edit(&trx).await?.name().overwrite(0, 3, "Fire");
+        trx.commit().await?;
+    }
+    assert_eq!(check(), vec![vec![(ice.id(), ChangeKind::Remove)]]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn negated_pattern_subscription() -> Result<()> {
+    let node = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
+    let (watcher, check) = common::changeset_watcher::<AlbumView>();
+    let _handle = node.subscribe("name NOT LIKE 'Ice%' AND name NOT STARTS WITH 'Two'", watcher).await?;
+
+    let (ice, walking);
+    {
+        let trx = node.begin();
+        ice = trx.create(&Album { name: "Ice on the Dune".into(), year: "2013".into() }).await.read();
+        walking = trx.create(&Album { name: "Walking on a Dream".into(), year: "2008".into() }).await.read();
+        trx.commit().await?;
+    }
+    assert_eq!(check(), vec![vec![(walking.id(), ChangeKind::Add)]]);
+
+    // Renaming an album so that it matches the negated prefix removes it
+    {
+        let trx = node.begin();
+        walking.edit(&trx).await?.name().overwrite(0, 7, "Two");
+        trx.commit().await?;
+    }
+    assert_eq!(check(), vec![vec![(walking.id(), ChangeKind::Remove)]]);
+
+    // And renaming one so that it no longer matches the negated pattern adds it
+    {
+        let trx = node.begin();
+        ice.edit(&trx).await?.name().overwrite(0, 3, "Fire");
+        trx.commit().await?;
+    }
+    assert_eq!(check(), vec![vec![(ice.id(), ChangeKind::Add)]]);
+    Ok(())
+}
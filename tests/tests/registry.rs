@@ -0,0 +1,101 @@
+use ankurah::{
+    error::RetrievalError,
+    property::{
+        backend::{register_backend, registry::register_backend_with, BackendDowncasted, BackendRegistration, PropertyBackend},
+        Backends, PropertyName,
+    },
+    proto::{ClockOrdering, Operation, State, ID},
+    value::Value,
+};
+use anyhow::Result;
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+
+/// A grow-only maximum per property, standing in for a CRDT which lives outside of core
+#[derive(Debug, Default)]
+struct MaxBackend {
+    values: Mutex<BTreeMap<PropertyName, i64>>,
+}
+
+impl PropertyBackend for MaxBackend {
+    fn as_arc_dyn_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync + 'static> { self as Arc<dyn Any + Send + Sync + 'static> }
+
+    fn as_debug(&self) -> &dyn Debug { self as &dyn Debug }
+
+    fn fork(&self) -> Box<dyn PropertyBackend> { Box::new(Self { values: Mutex::new(self.values.lock().unwrap().clone()) }) }
+
+    fn properties(&self) -> Vec<String> { self.values.lock().unwrap().keys().cloned().collect() }
+
+    fn materialized(&self) -> BTreeMap<PropertyName, Value> {
+        self.values.lock().unwrap().iter().map(|(property, value)| (property.clone(), Value::Integer(*value))).collect()
+    }
+
+    fn property_value(&self, property_name: &str) -> Option<Value> {
+        self.values.lock().unwrap().get(property_name).map(|v| Value::Integer(*v))
+    }
+
+    fn property_backend_name() -> String { "max".to_owned() }
+
+    fn to_state_buffer(&self) -> Result<Vec<u8>> {
+        let values = self.values.lock().unwrap();
+        Ok(values.iter().map(|(property, value)| format!("{}={}", property, value)).collect::<Vec<_>>().join(",").into_bytes())
+    }
+
+    fn from_state_buffer(state_buffer: &Vec<u8>) -> std::result::Result<Self, RetrievalError> {
+        let mut values = BTreeMap::new();
+        for pair in String::from_utf8_lossy(state_buffer).split(',').filter(|pair| !pair.is_empty()) {
+            let (property, value) = pair.split_once('=').ok_or_else(|| RetrievalError::Other(format!("Bad pair {}", pair)))?;
+            values.insert(property.to_owned(), value.parse().map_err(|_| RetrievalError::Other(format!("Bad value {}", value)))?);
+        }
+        Ok(Self { values: Mutex::new(values) })
+    }
+
+    fn to_operations(&self, _event_id: ID) -> Result<Vec<Operation>> { Ok(vec![Operation { diff: self.to_state_buffer()? }]) }
+
+    fn apply_operations(&self, _event_id: ID, operations: &Vec<Operation>) -> Result<()> {
+        for operation in operations {
+            self.merge_state(&operation.diff, ClockOrdering::Concurrent)?;
+        }
+        Ok(())
+    }
+
+    fn merge_state(&self, state_buffer: &Vec<u8>, _ordering: ClockOrdering) -> Result<()> {
+        let incoming = Self::from_state_buffer(state_buffer)?.values.into_inner().unwrap();
+        let mut values = self.values.lock().unwrap();
+        for (property, value) in incoming {
+            let entry = values.entry(property).or_insert(value);
+            *entry = (*entry).max(value);
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn custom_backend_registration() -> Result<()> {
+    let state =
+        State { state_buffers: BTreeMap::from([("max".to_owned(), b"plays=3".to_vec())]), head: Default::default(), tombstone: false };
+
+    // Unknown backends are an error rather than a panic
+    assert!(matches!(Backends::from_state_buffers(&state), Err(RetrievalError::UnknownBackend(name)) if name == "max"));
+
+    register_backend::<MaxBackend>()?;
+    let backends = Backends::from_state_buffers(&state)?;
+    let max = backends.get::<MaxBackend>()?;
+    assert_eq!(max.property_value("plays"), Some(Value::Integer(3)));
+    assert_eq!(backends.property_value("plays"), Some(Value::Integer(3)));
+    assert!(matches!(backends.get_with_name("max".to_owned())?, BackendDowncasted::Unknown(_)));
+
+    // The built in backends are registered out of the box, including PN counters
+    assert!(matches!(backends.get_with_name("pn".to_owned())?, BackendDowncasted::PN(_)));
+
+    // ...and can't be replaced
+    let replaced = register_backend_with("pn".to_owned(), BackendRegistration::of::<MaxBackend>());
+    assert!(matches!(replaced, Err(RetrievalError::BuiltinBackend(name)) if name == "pn"));
+    assert!(matches!(Backends::new().get_with_name("pn".to_owned())?, BackendDowncasted::PN(_)));
+
+    Ok(())
+}
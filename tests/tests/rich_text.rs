@@ -0,0 +1,61 @@
+mod common;
+use ankurah::{
+    property::value::{DeltaSpan, YrsAny, YrsString},
+    Mutable, Node, View,
+};
+use ankurah_connector_local_process::LocalProcessConnection;
+use ankurah_storage_sled::SledStorageEngine;
+use anyhow::Result;
+use common::*;
+use std::{collections::BTreeMap, sync::Arc};
+
+fn span(insert: &str, attributes: &[(&str, YrsAny)]) -> DeltaSpan {
+    DeltaSpan { insert: insert.to_owned(), attributes: attributes.iter().map(|(k, v)| (k.to_string(), v.clone())).collect() }
+}
+
+fn name_delta(album: &AlbumView) -> Vec<DeltaSpan> { YrsString::from_backends("name".into(), album.entity().backends()).to_delta() }
+
+#[tokio::test]
+async fn formatting_syncs_between_peers() -> Result<()> {
+    let server = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
+    let client = Node::new(Arc::new(SledStorageEngine::new_test().unwrap()));
+    let _conn = LocalProcessConnection::new(&server, &client).await?;
+
+    let album = {
+        let trx = client.begin();
+        let album = trx.create(&Album { name: "Black Holes".into(), year: "2006".into() }).await;
+        album.name().insert_formatted(11, " and Revelations", BTreeMap::from([("italic".to_owned(), YrsAny::Bool(true))]));
+        let album = album.read();
+        trx.commit().await?;
+        album
+    };
+
+    {
+        let trx = client.begin();
+        album.edit(&trx).await?.name().format(0..5, BTreeMap::from([("bold".to_owned(), YrsAny::Bool(true))]));
+        trx.commit().await?;
+    }
+
+    let expected = vec![
+        span("Black", &[("bold", YrsAny::Bool(true))]),
+        span(" Holes", &[]),
+        span(" and Revelations", &[("italic", YrsAny::Bool(true))]),
+    ];
+    assert_eq!(album.name(), "Black Holes and Revelations");
+    assert_eq!(name_delta(&album), expected);
+
+    // The formatting travelled to the server along with the text
+    let remote: AlbumView = server.get(album.id()).await?;
+    assert_eq!(remote.name(), "Black Holes and Revelations");
+    assert_eq!(name_delta(&remote), expected);
+
+    // Removing an attribute
+    {
+        let trx = client.begin();
+        album.edit(&trx).await?.name().format(0..5, BTreeMap::from([("bold".to_owned(), YrsAny::Null)]));
+        trx.commit().await?;
+    }
+    assert_eq!(name_delta(&album), vec![span("Black Holes", &[]), span(" and Revelations", &[("italic", YrsAny::Bool(true))])]);
+
+    Ok(())
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use ankurah::{changes::ChangeKind, Model, Mutable, Node, ResultSet};
+use ankurah_storage_sled::SledStorageEngine;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use common::for_each_engine;
+
+#[derive(Model, Debug, Serialize, Deserialize)]
+pub struct Note {
+    pub title: String,
+    pub body: String,
+}
+
+fn titles(resultset: &ResultSet<NoteView>) -> Vec<String> { resultset.items.iter().map(|r| r.title()).collect() }
+
+async fn create_notes(node: &Arc<Node>) -> Result<()> {
+    let trx = node.begin();
+    trx.create(&Note { title: "Borrow checker".into(), body: "The borrow checker keeps references valid".into() }).await;
+    trx.create(&Note { title: "Async Rust".into(), body: "Async rust needs an executor. Rust futures are lazy".into() }).await;
+    trx.create(&Note { title: "Groceries".into(), body: "Milk, eggs, and rust remover".into() }).await;
+    trx.commit().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn search() -> Result<()> {
+    for_each_engine(|node| async move {
+        create_notes(&node).await?;
+
+        // Without an ORDER BY, results are ranked by relevance
+        let results = node.fetch("body MATCHES 'rust'").await?;
+        assert_eq!(titles(&results), ["Async Rust", "Groceries"]);
+        let scores = results.scores.as_ref().expect("a search is scored");
+        assert!(scores[0] > scores[1]);
+        assert_eq!(titles(&node.fetch("body MATCHES 'rust' LIMIT 1").await?), ["Async Rust"]);
+
+        // Every term has to match, in any order and regardless of case
+        assert_eq!(titles(&node.fetch("body MATCHES 'FUTURES rust'").await?), ["Async Rust"]);
+        assert!(node.fetch::<NoteView>("body MATCHES 'rust python'").await?.items.is_empty());
+
+        assert_eq!(titles(&node.fetch("body MATCHES 'rust' AND title = 'Groceries'").await?), ["Groceries"]);
+        assert_eq!(
+            titles(&node.fetch("title MATCHES 'borrow' OR body MATCHES 'executor' ORDER BY title").await?),
+            ["Async Rust", "Borrow checker"]
+        );
+        assert_eq!(titles(&node.fetch("body MATCHES 'rust' ORDER BY title DESC").await?), ["Groceries", "Async Rust"]);
+
+        // Other queries aren't scored
+        assert!(node.fetch::<NoteView>("title = 'Groceries'").await?.scores.is_none());
+
+        // Editing a note replaces its terms
+        {
+            let trx = node.begin();
+            let groceries: ResultSet<NoteView> = node.fetch("title = 'Groceries'").await?;
+            groceries.items[0].edit(&trx).await?.body().overwrite(0, 4, "Oats");
+            trx.commit().await?;
+        }
+        assert!(node.fetch::<NoteView>("body MATCHES 'milk'").await?.items.is_empty());
+        assert_eq!(titles(&node.fetch("body MATCHES 'oats'").await?), ["Groceries"]);
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn search_subscription() -> Result<()> {
+    let node = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
+    let (watcher, check) = common::changeset_watcher::<NoteView>();
+    let _handle = node.subscribe("body MATCHES 'rust'", watcher).await?;
+
+    let note;
+    {
+        let trx = node.begin();
+        note = trx.create(&Note { title: "Lifetimes".into(), body: "Rust lifetimes".into() }).await.read();
+        trx.create(&Note { title: "Groceries".into(), body: "Milk and eggs".into() }).await;
+        trx.commit().await?;
+    }
+    assert_eq!(check(), vec![vec![(note.id(), ChangeKind::Add)]]);
+
+    // Editing the note so that it no longer mentions rust removes it
+    {
+        let trx = node.begin();
+        note.edit(&trx).await?.body().overwrite(0, 4, "Swift");
+        trx.commit().await?;
+    }
+    assert_eq!(check(), vec![vec![(note.id(), ChangeKind::Remove)]]);
+    Ok(())
+}
@@ -0,0 +1,40 @@
+mod common;
+use ankurah::model::View;
+use anyhow::Result;
+use common::*;
+
+#[tokio::test]
+async fn time_travel() -> Result<()> {
+    for_each_engine(|node| async move {
+        let album: AlbumView = create_and_read(&node, &Album { name: "Showbiz".into(), year: "1999".into() }).await?;
+        let created = album.entity().head();
+
+        {
+            let trx = node.begin();
+            album.edit(&trx).await?.name().overwrite(0, 7, "Origin of Symmetry");
+            trx.commit().await?;
+        }
+        let renamed = album.entity().head();
+
+        {
+            let trx = node.begin();
+            album.edit(&trx).await?.year().overwrite(0, 4, "2001");
+            trx.commit().await?;
+        }
+
+        assert_eq!((album.name(), album.year()), ("Origin of Symmetry".to_string(), "2001".to_string()));
+
+        let original: AlbumView = node.get_at(album.id(), &created).await?;
+        assert_eq!((original.name(), original.year()), ("Showbiz".to_string(), "1999".to_string()));
+        assert_eq!(original.entity().head(), created);
+
+        let intermediate = album.as_of(&node, &renamed).await?;
+        assert_eq!((intermediate.name(), intermediate.year()), ("Origin of Symmetry".to_string(), "1999".to_string()));
+
+        // Historical views are detached from the live entity
+        assert_eq!(album.year(), "2001");
+
+        Ok(())
+    })
+    .await
+}
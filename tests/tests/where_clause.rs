@@ -1,10 +1,14 @@
 mod common;
-use ankurah::{Mutable, Node};
+use ankurah::{changes::ChangeSet, Mutable, Node};
 use ankurah_storage_sled::SledStorageEngine;
 use anyhow::Result;
 
-use common::{Album, AlbumView};
-use std::sync::Arc;
+use common::{create_albums, names, Album, AlbumView, Dimensions, Format, Release, ReleaseView};
+use std::sync::{Arc, Mutex};
+
+const ALBUMS: &[(&str, &str)] =
+    &[("Walking on a Dream", "2008"), ("Ice on the Dune", "2013"), ("Two Vines", "2016"), ("Ask That God", "2024")];
+
 #[tokio::test]
 async fn basic_where_clause() -> Result<()> {
     let client = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
@@ -29,6 +33,82 @@ async fn basic_where_clause() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn in_and_between_where_clause() -> Result<()> {
+    let client = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
+    create_albums(&client, ALBUMS).await?;
+    assert_in_and_between(&client).await
+}
+
+async fn assert_in_and_between(client: &Arc<Node>) -> Result<()> {
+    assert_eq!(
+        names(client.fetch("name IN ('Two Vines', 'Ask That God', 'Oracular Spectacular') ORDER BY name").await?),
+        ["Ask That God", "Two Vines"]
+    );
+    assert_eq!(
+        names(client.fetch("name NOT IN ('Two Vines', 'Ask That God') ORDER BY name").await?),
+        ["Ice on the Dune", "Walking on a Dream"]
+    );
+    // Both bounds are inclusive
+    assert_eq!(names(client.fetch("year BETWEEN '2013' AND '2016' ORDER BY name").await?), ["Ice on the Dune", "Two Vines"]);
+    assert_eq!(names(client.fetch("year BETWEEN '2010' AND '2020' AND name IN ('Two Vines')").await?), ["Two Vines"]);
+
+    Ok(())
+}
+
+/// A CD release, since the format and sleeve don't matter to these queries
+fn release(title: &str, year: i64, remastered: bool, rating: f64) -> Release {
+    Release { title: title.into(), year, remastered, rating, format: Format::CD, sleeve: Dimensions { width: 12, height: 12 } }
+}
+
+#[tokio::test]
+async fn typed_where_clause() -> Result<()> {
+    let client = Node::new_durable(Arc::new(SledStorageEngine::new_test().unwrap()));
+
+    let drones = {
+        let trx = client.begin();
+        trx.create(&release("Showbiz", 1999, false, 3.5)).await;
+        trx.create(&release("Origin of Symmetry", 2001, true, 4.5)).await;
+        let drones = trx.create(&release("Drones", 2015, false, 2.0)).await.read();
+        trx.commit().await?;
+        drones
+    };
+
+    // Numbers compare numerically rather than as text, including integers against floats
+    assert_eq!(titles(&client, "year > 2000").await?, ["Drones", "Origin of Symmetry"]);
+    assert_eq!(titles(&client, "year < 2001.5").await?, ["Origin of Symmetry", "Showbiz"]);
+    assert_eq!(titles(&client, "rating >= 3.5").await?, ["Origin of Symmetry", "Showbiz"]);
+    assert_eq!(titles(&client, "rating > 2").await?, ["Origin of Symmetry", "Showbiz"]);
+    assert_eq!(titles(&client, "remastered = true").await?, ["Origin of Symmetry"]);
+    // Quoted literals are coerced to the type of the property
+    assert_eq!(titles(&client, "year = '1999'").await?, ["Showbiz"]);
+    // Incomparable types are simply unequal
+    assert_eq!(titles(&client, "year = 'Showbiz'").await?, Vec::<String>::new());
+
+    // Subscriptions find typed values with quoted literals too
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let _handle = {
+        let received = received.clone();
+        client.subscribe("year = '2016'", move |changeset: ChangeSet<ReleaseView>| received.lock().unwrap().push(changeset)).await?
+    };
+    let trx = client.begin();
+    drones.edit(&trx).await?.year().set(&2016)?;
+    trx.commit().await?;
+
+    let received = received.lock().unwrap();
+    let added = received.last().unwrap().changes.iter().map(|change| change.entity().title()).collect::<Vec<String>>();
+    assert_eq!(added, ["Drones"]);
+
+    Ok(())
+}
+
+async fn titles(client: &Arc<Node>, query: &str) -> Result<Vec<String>> {
+    let releases: ankurah::ResultSet<ReleaseView> = client.fetch(query).await?;
+    let mut titles = releases.items.iter().map(|r| r.title()).collect::<Vec<String>>();
+    titles.sort();
+    Ok(titles)
+}
+
 #[cfg(feature = "postgres")]
 mod pg_common;
 
@@ -71,3 +151,12 @@ async fn pg_basic_where_clause() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(feature = "postgres")]
+#[tokio::test]
+async fn pg_in_and_between_where_clause() -> Result<()> {
+    let (_container, storage_engine) = pg_common::create_postgres_container().await?;
+    let client = Node::new_durable(Arc::new(storage_engine));
+    create_albums(&client, ALBUMS).await?;
+    assert_in_and_between(&client).await
+}
@@ -0,0 +1,64 @@
+mod common;
+use ankurah::Model;
+use anyhow::Result;
+use common::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Model, Debug, Serialize, Deserialize)]
+pub struct Document {
+    pub title: String,
+    #[active_type(YrsMap)]
+    pub metadata: BTreeMap<String, String>,
+    #[active_type(YrsArray)]
+    pub revisions: Vec<i64>,
+    #[active_type(YrsXmlFragment)]
+    pub body: String,
+}
+
+#[tokio::test]
+async fn yrs_structured_types() -> Result<()> {
+    for_each_engine(|node| async move {
+        let document: DocumentView = create_and_read(
+            &node,
+            &Document {
+                title: "Notes".into(),
+                metadata: BTreeMap::from([("author".to_owned(), "Matt".to_owned())]),
+                revisions: vec![1],
+                body: "".into(),
+            },
+        )
+        .await?;
+        assert_eq!(document.metadata(), BTreeMap::from([("author".to_owned(), "Matt".to_owned())]));
+        assert_eq!(document.revisions(), vec![1]);
+
+        // Concurrent edits to different parts of the same structures are all retained
+        let trx1 = node.begin();
+        let trx2 = node.begin();
+        {
+            let edit = document.edit(&trx1).await?;
+            edit.metadata().insert("status", "draft".to_owned());
+            edit.revisions().push(2);
+            edit.body().insert_element(0, "p");
+        }
+        {
+            let edit = document.edit(&trx2).await?;
+            edit.metadata().remove("author");
+            edit.revisions().insert(0, 0);
+        }
+        trx1.commit().await?;
+        trx2.commit().await?;
+
+        assert_eq!(document.metadata(), BTreeMap::from([("status".to_owned(), "draft".to_owned())]));
+        assert_eq!(document.revisions(), vec![0, 1, 2]);
+        assert_eq!(document.body(), "<p></p>");
+
+        let fetched: DocumentView = node.get(document.id()).await?;
+        assert_eq!(fetched.metadata(), document.metadata());
+        assert_eq!(fetched.revisions(), document.revisions());
+        assert_eq!(fetched.body(), document.body());
+
+        Ok(())
+    })
+    .await
+}